@@ -6,7 +6,6 @@ use serde::{Deserialize, Serialize};
 use serde_big_array::BigArray;
 
 // defaults to a ~440Hz (444.44) square wave
-#[cfg(feature = "xo-chip")]
 const PITCH_BIAS: f32 = 64.0;
 pub const SAMPLE_RATE: u32 = 48000;
 const CHIP8_AUDIO_BUFFER_SIZE: usize = 128;
@@ -112,6 +111,56 @@ impl Audio {
     }
 }
 
+// Accessors for save states, which store the pattern as the 16 bytes it was
+// loaded from rather than as samples.
+impl Audio {
+    pub(crate) fn pattern(&self) -> [u8; 16] {
+        #[cfg(feature = "xo-chip")]
+        let samples = &self.buffer;
+        #[cfg(not(feature = "xo-chip"))]
+        let samples = &DEFAULT_CHIP8_AUDIO_BUFFER;
+
+        let mut pattern = [0; 16];
+        for (byte, bits) in pattern.iter_mut().zip(samples.chunks(8)) {
+            *byte = bits
+                .iter()
+                .fold(0, |byte, &bit| byte << 1 | (bit != 0.0) as u8);
+        }
+        pattern
+    }
+
+    #[inline]
+    pub(crate) fn pitch(&self) -> u8 {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "xo-chip")] {
+                self.pitch as u8
+            } else {
+                PITCH_BIAS as u8
+            }
+        }
+    }
+
+    // Index of the next sample in the pattern.
+    #[inline]
+    pub(crate) fn position(&self) -> usize {
+        self.next
+    }
+
+    // Returns false if this build can only play the default pattern and pitch.
+    pub(crate) fn restore(&mut self, pattern: &[u8; 16], pitch: u8, position: usize) -> bool {
+        self.next = position % CHIP8_AUDIO_BUFFER_SIZE;
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "xo-chip")] {
+                self.write_pattern(pattern);
+                self.set_pitch(pitch as f32);
+                true
+            } else {
+                *pattern == Self::new().pattern() && pitch == PITCH_BIAS as u8
+            }
+        }
+    }
+}
+
 impl Default for Audio {
     #[inline]
     fn default() -> Self {
@@ -5,25 +5,33 @@ use serde::{Deserialize, Serialize};
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Error {
     UnknownOpcode(u16),
-    Breakpoint(u16),
     InvalidFile,
     OutOfBounds,
+    MemoryFault(u16, u32),
+    StackOverflow(u16),
+    StackUnderflow(u16),
     NoRomLoaded,
     Exited,
     NotDefined(&'static str, Chip8Mode),
-    EarlyExitRequested,
 }
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::UnknownOpcode(op) => write!(f, "unknown opcode {:04x}", op),
-            Self::Breakpoint(at) => write!(f, "reached breakpoint at {:04x}", at),
             Self::InvalidFile => write!(f, "supplied file is not a valid ROM"),
             Self::OutOfBounds => write!(f, "attempted an out-of-bounds memory access"),
+            Self::MemoryFault(pc, addr) => write!(
+                f,
+                "instruction at {:04x} accessed out-of-bounds address {:04x}",
+                pc, addr
+            ),
+            Self::StackOverflow(pc) => write!(f, "call stack overflowed at {:04x}", pc),
+            Self::StackUnderflow(pc) => {
+                write!(f, "returned with an empty call stack at {:04x}", pc)
+            }
             Self::NoRomLoaded => write!(f, "no ROM is loaded to execute from"),
             Self::Exited => write!(f, "program has exited"),
             Self::NotDefined(op, mode) => write!(f, "`{}` isn't defined for {}", op, mode),
-            Self::EarlyExitRequested => write!(f, "emulator paused execution"),
         }
     }
 }
@@ -156,3 +164,35 @@ impl core::str::FromStr for Chip8Mode {
         }
     }
 }
+
+impl TryFrom<u8> for Chip8Mode {
+    type Error = Chip8ModeParseError;
+    fn try_from(value: u8) -> Result<Self, Chip8ModeParseError> {
+        match value {
+            #[cfg(feature = "cosmac")]
+            0 => Ok(Chip8Mode::Cosmac),
+            #[cfg(not(feature = "cosmac"))]
+            0 => Err(Chip8ModeParseError::NotEnabled("cosmac")),
+            #[cfg(feature = "super-chip")]
+            1 => Ok(Chip8Mode::SuperChip),
+            #[cfg(not(feature = "super-chip"))]
+            1 => Err(Chip8ModeParseError::NotEnabled("super-chip")),
+            #[cfg(feature = "xo-chip")]
+            2 => Ok(Chip8Mode::XoChip),
+            #[cfg(not(feature = "xo-chip"))]
+            2 => Err(Chip8ModeParseError::NotEnabled("xo-chip")),
+            _ => Err(Chip8ModeParseError::InvalidMode),
+        }
+    }
+}
+
+// Splits `len` bytes off the front of `bytes`, for decoding binary formats.
+#[inline]
+pub(crate) fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if bytes.len() < len {
+        return None;
+    }
+    let (head, tail) = bytes.split_at(len);
+    *bytes = tail;
+    Some(head)
+}
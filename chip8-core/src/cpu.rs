@@ -1,22 +1,39 @@
 use crate::audio::Audio;
+#[cfg(feature = "alloc")]
+use crate::common::take;
+use crate::disassembly::DecodedInstruction;
 use crate::display::Display;
-use crate::font;
+#[cfg(all(feature = "alloc", any(feature = "super-chip", feature = "xo-chip")))]
+use crate::flags::{FlagStorage, FlagStorageSlot};
+use crate::font::{Font, FontError};
+#[cfg(feature = "alloc")]
+use crate::input::KeyQueue;
 use crate::instruction::Instruction;
+use crate::observer::{Chip8Observer, StepEffects};
+#[cfg(feature = "alloc")]
+use crate::profile::Profile;
+use crate::quirks::{IndexOverflow, Quirks};
+#[cfg(feature = "alloc")]
+use crate::script::{InputReplay, InputScript};
+use crate::timing::{
+    Budget, ClockSpeed, FrameReport, StopConditions, StopReason, CYCLE, NANOS_PER_SEC,
+};
 use crate::Register;
 use crate::{Chip8Mode, Error};
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
 
 use core::time::Duration;
 
-use nanorand::{Rng, SeedableRng, WyRand};
+#[cfg(feature = "alloc")]
+use crate::rng::Rng;
+use crate::rng::{RngSlot, WyRand};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "serde")]
 use serde_big_array::BigArray;
 
-const TIMER_SPEED: f64 = 60.0;
-const CLOCK_SPEED: f64 = 500.0;
-
 #[cfg(not(feature = "xo-chip"))]
 pub const CHIP8_MEM_SIZE: usize = 0x1000;
 #[cfg(feature = "xo-chip")]
@@ -34,6 +51,9 @@ cfg_if::cfg_if! {
         }
 
         impl CallStack {
+            // the stack grows without bound with an allocator available
+            pub const CAPACITY: Option<usize> = None;
+
             #[inline]
             pub fn new() -> Self {
                 Self::default()
@@ -47,7 +67,15 @@ cfg_if::cfg_if! {
                 self.stack.pop()
             }
             #[inline]
-            pub fn iter(&self) -> core::slice::Iter<u16> {
+            pub fn len(&self) -> usize {
+                self.stack.len()
+            }
+            #[inline]
+            pub fn is_empty(&self) -> bool {
+                self.stack.is_empty()
+            }
+            #[inline]
+            pub fn iter(&self) -> core::slice::Iter<'_, u16> {
                 self.stack.iter()
             }
         }
@@ -73,6 +101,8 @@ cfg_if::cfg_if! {
         }
 
         impl CallStack {
+            pub const CAPACITY: Option<usize> = Some(CALL_STACK_SIZE);
+
             #[inline]
             pub fn new() -> Self {
                 CallStack {
@@ -81,8 +111,8 @@ cfg_if::cfg_if! {
                 }
             }
             pub fn push(&mut self, addr: u16) {
+                // `CPU` checks against `CAPACITY` before pushing
                 if self.call_stack_idx == CALL_STACK_SIZE {
-                    // TODO raise error instead of no-op if out of space?
                     return;
                 }
                 self.call_stack[self.call_stack_idx] = addr;
@@ -97,7 +127,15 @@ cfg_if::cfg_if! {
                 }
             }
             #[inline]
-            pub fn iter(&self) -> core::slice::Iter<u16> {
+            pub fn len(&self) -> usize {
+                self.call_stack_idx
+            }
+            #[inline]
+            pub fn is_empty(&self) -> bool {
+                self.call_stack_idx == 0
+            }
+            #[inline]
+            pub fn iter(&self) -> core::slice::Iter<'_, u16> {
                 self.call_stack[0..self.call_stack_idx].iter()
             }
         }
@@ -120,11 +158,56 @@ cfg_if::cfg_if! {
     }
 }
 
+// How far `FX0A` has got in waiting for a key. Only keys pressed after it
+// started count, so a key that was already held has to be let go of first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum KeyWait {
+    // waiting for a key to be pressed, to store in the register
+    Press(Register),
+    // the key was pressed and is stored once it's released
+    Release(Register, u8),
+}
+
+impl KeyWait {
+    #[inline]
+    pub fn register(&self) -> Register {
+        match *self {
+            Self::Press(reg) | Self::Release(reg, _) => reg,
+        }
+    }
+
+    // Register and pressed key as bytes, 0xFF for none, as save states store
+    // them.
+    pub(crate) fn to_bytes(wait: Option<KeyWait>) -> [u8; 2] {
+        match wait {
+            None => [0xFF, 0xFF],
+            Some(Self::Press(reg)) => [reg.into(), 0xFF],
+            Some(Self::Release(reg, key)) => [reg.into(), key],
+        }
+    }
+
+    pub(crate) fn from_bytes([reg, key]: [u8; 2]) -> Option<Option<KeyWait>> {
+        if reg == 0xFF {
+            return (key == 0xFF).then(|| None);
+        }
+        let reg = Register::try_from(reg).ok()?;
+        match key {
+            0xFF => Some(Some(Self::Press(reg))),
+            0..=0xF => Some(Some(Self::Release(reg, key))),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct CPU {
-    cycles_pending: f64,
-    timers_pending: f64,
+    cycles_pending: i64,
+    timers_pending: u64,
     pub mode: Chip8Mode,
+    pub quirks: Quirks,
+    pub clock_speed: ClockSpeed,
+    font: Font,
 
     pub pc: u16,
     pub index: u16,
@@ -137,43 +220,165 @@ pub struct CPU {
     pub audio: Audio,
 
     input: [bool; 16],
-    awaiting_key: Option<Register>,
-    random_state: WyRand,
-
-    #[cfg(feature = "cosmac")]
+    key_wait: Option<KeyWait>,
+    random_state: RngSlot,
     pub(crate) vblank_wait: bool,
+    pub(crate) effects: StepEffects,
+    #[cfg(feature = "alloc")]
+    profile: Option<Box<Profile>>,
+    #[cfg(feature = "alloc")]
+    input_replay: Option<InputReplay>,
+    #[cfg(feature = "alloc")]
+    key_queue: KeyQueue,
+    // keys pressed with `press_key_at` stay down for at least a frame, so
+    // that programs polling with `EX9E` and `EXA1` still see quick taps
+    #[cfg(feature = "alloc")]
+    pub hold_taps: bool,
 
     #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
     pub exited: bool,
     #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
     pub(crate) persistent_registers: enum_map::EnumMap<Register, u8>,
+    #[cfg(all(feature = "alloc", any(feature = "super-chip", feature = "xo-chip")))]
+    flag_storage: FlagStorageSlot,
 }
 
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SavedState {
-    mode: Chip8Mode,
-    pc: u16,
-    index: u16,
-    registers: enum_map::EnumMap<Register, u8>,
+    pub(crate) mode: Chip8Mode,
+    pub(crate) pc: u16,
+    pub(crate) index: u16,
+    pub(crate) registers: enum_map::EnumMap<Register, u8>,
     #[cfg_attr(feature = "serde", serde(with = "BigArray"))]
-    memory: [u8; CHIP8_MEM_SIZE],
-    screen: Display,
-    call_stack: CallStack,
-    delay_timer: u8,
-    sound_timer: u8,
-    awaiting_key: Option<Register>,
-    seed: [u8; 8],
+    pub(crate) memory: [u8; CHIP8_MEM_SIZE],
+    pub(crate) screen: Display,
+    pub(crate) call_stack: CallStack,
+    pub(crate) delay_timer: u8,
+    pub(crate) sound_timer: u8,
+    pub(crate) key_wait: Option<KeyWait>,
+    pub(crate) rng_state: u64,
+    pub(crate) audio: Audio,
+    pub(crate) input: [bool; 16],
+    pub(crate) cycles_pending: i64,
+    pub(crate) timers_pending: u64,
+    pub(crate) vblank_wait: bool,
     #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
-    persistent_registers: enum_map::EnumMap<Register, u8>,
+    pub(crate) persistent_registers: enum_map::EnumMap<Register, u8>,
+}
+
+#[cfg(feature = "alloc")]
+impl SavedState {
+    // Unversioned encoding for in-memory history. Fixed-size fields come first
+    // so that consecutive states line up byte-for-byte.
+    pub(crate) fn write_raw(&self, out: &mut Vec<u8>) {
+        out.push(self.mode as u8);
+        out.extend(self.pc.to_le_bytes());
+        out.extend(self.index.to_le_bytes());
+        out.extend(self.registers.values());
+        out.push(self.delay_timer);
+        out.push(self.sound_timer);
+        out.extend(KeyWait::to_bytes(self.key_wait));
+        out.extend(self.rng_state.to_le_bytes());
+        #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
+        out.extend(self.persistent_registers.values());
+        out.extend(self.audio.pattern());
+        out.push(self.audio.pitch());
+        out.extend((self.audio.position() as u16).to_le_bytes());
+        out.extend(keys_to_bits(&self.input).to_le_bytes());
+        out.extend(self.cycles_pending.to_le_bytes());
+        out.extend(self.timers_pending.to_le_bytes());
+        out.push(self.vblank_wait as u8);
+        out.extend(self.memory);
+        self.screen.write_raw(out);
+        out.extend((self.call_stack.len() as u16).to_le_bytes());
+        for addr in &self.call_stack {
+            out.extend(addr.to_le_bytes());
+        }
+    }
+
+    pub(crate) fn read_raw(mut bytes: &[u8]) -> Option<SavedState> {
+        let bytes = &mut bytes;
+        let word = |bytes: &mut &[u8]| Some(u16::from_le_bytes(take(bytes, 2)?.try_into().ok()?));
+        let registers = |bytes: &mut &[u8]| {
+            let values: [u8; 16] = take(bytes, 16)?.try_into().ok()?;
+            Some(enum_map::EnumMap::<Register, u8>::from_array(values))
+        };
+        let mode = Chip8Mode::try_from(take(bytes, 1)?[0]).ok()?;
+        let pc = word(bytes)?;
+        let index = word(bytes)?;
+        let regs = registers(bytes)?;
+        let timers = take(bytes, 4)?;
+        let key_wait = KeyWait::from_bytes([timers[2], timers[3]])?;
+        let rng_state = u64::from_le_bytes(take(bytes, 8)?.try_into().ok()?);
+        #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
+        let persistent_registers = registers(bytes)?;
+        let mut audio = Audio::new();
+        let pattern = take(bytes, 16)?.try_into().ok()?;
+        let pitch = take(bytes, 1)?[0];
+        audio.restore(pattern, pitch, word(bytes)? as usize);
+        let input = bits_to_keys(word(bytes)?);
+        let cycles_pending = i64::from_le_bytes(take(bytes, 8)?.try_into().ok()?);
+        let timers_pending = u64::from_le_bytes(take(bytes, 8)?.try_into().ok()?);
+        let vblank_wait = take(bytes, 1)?[0] != 0;
+        let memory = take(bytes, CHIP8_MEM_SIZE)?.try_into().ok()?;
+        let screen = Display::read_raw(bytes)?;
+        let mut call_stack = CallStack::new();
+        for _ in 0..word(bytes)? {
+            call_stack.push(word(bytes)?);
+        }
+        Some(SavedState {
+            mode,
+            pc,
+            index,
+            registers: regs,
+            memory,
+            screen,
+            call_stack,
+            delay_timer: timers[0],
+            sound_timer: timers[1],
+            key_wait,
+            rng_state,
+            audio,
+            input,
+            cycles_pending,
+            timers_pending,
+            vblank_wait,
+            #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
+            persistent_registers,
+        })
+    }
+}
+
+// Held keys as a bitmask, key 0 in the lowest bit.
+pub(crate) fn keys_to_bits(keys: &[bool; 16]) -> u16 {
+    (0..16)
+        .filter(|&key| keys[key])
+        .fold(0, |bits, key| bits | 1 << key)
+}
+
+pub(crate) fn bits_to_keys(bits: u16) -> [bool; 16] {
+    let mut keys = [false; 16];
+    for (key, held) in keys.iter_mut().enumerate() {
+        *held = bits & (1 << key) != 0;
+    }
+    keys
 }
 
 impl CPU {
+    #[inline]
     pub fn new(mode: Chip8Mode) -> CPU {
+        Self::with_quirks(mode, Quirks::for_mode(mode))
+    }
+
+    pub fn with_quirks(mode: Chip8Mode, quirks: Quirks) -> CPU {
         let mut cpu = CPU {
-            cycles_pending: 0.0,
-            timers_pending: 0.0,
+            cycles_pending: 0,
+            timers_pending: 0,
             mode,
+            quirks,
+            clock_speed: ClockSpeed::default(),
+            font: Font::default(),
 
             pc: 0x200,
             index: 0,
@@ -186,57 +391,96 @@ impl CPU {
             audio: Audio::new(),
 
             input: [false; 16],
-            awaiting_key: None,
-            random_state: WyRand::new(),
-
-            #[cfg(feature = "cosmac")]
+            key_wait: None,
+            random_state: RngSlot::WyRand(WyRand::new()),
             vblank_wait: false,
+            effects: StepEffects::default(),
+            #[cfg(feature = "alloc")]
+            profile: None,
+            #[cfg(feature = "alloc")]
+            input_replay: None,
+            #[cfg(feature = "alloc")]
+            key_queue: KeyQueue::default(),
+            #[cfg(feature = "alloc")]
+            hold_taps: false,
 
             #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
             exited: false,
             #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
             persistent_registers: enum_map::enum_map! { _ => 0 },
+            #[cfg(all(feature = "alloc", any(feature = "super-chip", feature = "xo-chip")))]
+            flag_storage: FlagStorageSlot::default(),
         };
         cpu.clear_memory();
         cpu
     }
 
-    #[cfg(not(any(feature = "super-chip", feature = "xo-chip")))]
     #[inline]
     fn clear_memory(&mut self) {
         self.memory.fill(0);
-        self.memory[0x50..0xA0].copy_from_slice(&font::FONT_SET);
+        self.font.load_into(&mut self.memory);
     }
-    #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
+
     #[inline]
-    fn clear_memory(&mut self) {
-        self.memory.fill(0);
-        self.memory[0x50..0xA0].copy_from_slice(&font::FONT_SET);
-        self.memory[0xA0..0x140].copy_from_slice(&font::BIG_FONT_SET);
+    pub fn step(&mut self) -> Result<u32, Error> {
+        self.step_observed(&mut ())
     }
 
-    pub fn step(&mut self) -> Result<u32, Error> {
+    #[inline]
+    pub fn step_observed(&mut self, observer: &mut dyn Chip8Observer) -> Result<u32, Error> {
+        let res = self.step_and_notify(observer);
+        self.effects.clear();
+        res
+    }
+
+    // Leaves what the instruction did in `effects` for the caller to clear.
+    fn step_and_notify(&mut self, observer: &mut dyn Chip8Observer) -> Result<u32, Error> {
         #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
         if self.exited {
             return Err(Error::Exited);
         }
 
-        if self.awaiting_key.is_some() {
+        if self.key_wait.is_some() {
             return Ok(1);
         }
-        let opcode = self.read_memory_word(self.pc)?;
+        let pc = self.pc;
+        let opcode = self
+            .read_memory_word(pc)
+            .map_err(|_| Error::MemoryFault(pc, pc as u32))?;
+        let sound_was_playing = self.is_sound_playing();
+        #[cfg(feature = "alloc")]
+        if let Some(profile) = self.profile.as_deref_mut() {
+            profile.enter(&self.call_stack, &self.memory);
+        }
+        let cycles = self.execute(opcode)?;
+        #[cfg(feature = "alloc")]
+        if let Some(profile) = self.profile.as_deref_mut() {
+            profile.record(pc, opcode, cycles);
+        }
+
+        observer.instruction_executed(self, pc, opcode);
+        for &(addr, value) in self.effects.writes() {
+            observer.memory_written(self, addr, value);
+        }
+        if let Some(rect) = self.effects.screen {
+            observer.screen_modified(self, rect);
+        }
+        self.notify_sound(sound_was_playing, observer);
+        if let Some(wait) = self.key_wait {
+            observer.key_wait(self, wait.register());
+        }
+        #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
+        if self.exited {
+            observer.exited(self);
+        }
+        Ok(cycles)
+    }
+
+    fn execute(&mut self, opcode: u16) -> Result<u32, Error> {
         let inst = Instruction::lookup(opcode);
         if let Some(inst) = inst {
             let cycles = inst.cycles;
             self.pc += 2;
-            let op: Option<crate::instruction::OpcodeExecute> = match self.mode {
-                #[cfg(feature = "cosmac")]
-                Chip8Mode::Cosmac => inst.cosmac,
-                #[cfg(feature = "super-chip")]
-                Chip8Mode::SuperChip => inst.schip,
-                #[cfg(feature = "xo-chip")]
-                Chip8Mode::XoChip => inst.xochip,
-            };
             // Code will only be unreachable if none of the mode features are
             // enabled; in the interest of having only the `compile_error!`
             // saying that one of them needs to be enabled, disable this warning
@@ -245,8 +489,8 @@ impl CPU {
                 not(any(feature = "cosmac", feature = "super-chip", feature = "xo-chip")),
                 allow(unreachable_code)
             )]
-            if let Some(op) = op {
-                let extra_cycles = op(self, opcode);
+            if let Some(op) = inst.for_mode(self.mode) {
+                let extra_cycles = op(self, opcode)?;
                 Ok(cycles + extra_cycles)
             } else {
                 Err(Error::NotDefined(inst.disassembly, self.mode))
@@ -256,92 +500,207 @@ impl CPU {
         }
     }
 
-    pub fn emulate_for(&mut self, dur: Duration) -> Result<(), Error> {
+    // Runs until `budget` is used up or one of `stop` is met.
+    #[inline]
+    pub fn run(&mut self, budget: Budget, stop: &StopConditions) -> Result<StopReason, Error> {
+        self.run_observed(budget, stop, &mut ())
+    }
+
+    #[inline]
+    pub fn run_observed(
+        &mut self,
+        budget: Budget,
+        stop: &StopConditions,
+        observer: &mut dyn Chip8Observer,
+    ) -> Result<StopReason, Error> {
+        let mut report = FrameReport::EMPTY;
+        self.run_with_report(budget, stop, observer, &mut report)
+    }
+
+    // Runs a single 60 Hz frame: timers tick down once, then one frame's worth
+    // of cycles are executed unless the program has to wait first.
+    pub fn run_frame(&mut self) -> Result<FrameReport, Error> {
+        let mut report = FrameReport::EMPTY;
         #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
         if self.exited {
-            return Err(Error::Exited);
+            report.stop_reason = StopReason::Exited;
+            return Ok(report);
         }
 
-        let dt = dur.as_secs_f64();
-        self.timers_pending += dt * TIMER_SPEED;
-        let timer_diff = self.timers_pending as u8;
-        self.delay_timer = self.delay_timer.saturating_sub(timer_diff);
-        self.sound_timer = self.sound_timer.saturating_sub(timer_diff);
-        self.timers_pending -= timer_diff as f64;
-        #[cfg(feature = "cosmac")]
-        if timer_diff > 0 {
-            self.vblank_wait = false;
-        } else if self.vblank_wait {
-            return Ok(());
+        let stop = StopConditions::default();
+        self.tick();
+        let reason = self.run_frame_cycles(&stop, &mut (), &mut report)?;
+        if !stop.stops_on(reason) {
+            self.run_units(NANOS_PER_SEC, &stop, &mut (), &mut report)?;
         }
+        // scripted input moves on once the frame is over, as it does at the
+        // tick when running by time
+        #[cfg(feature = "alloc")]
+        self.replay_next_frame();
+        report.sound_active = self.is_sound_playing();
+        Ok(report)
+    }
 
-        if self.awaiting_key.is_some() {
-            return Ok(());
+    fn run_with_report(
+        &mut self,
+        budget: Budget,
+        stop: &StopConditions,
+        observer: &mut dyn Chip8Observer,
+        report: &mut FrameReport,
+    ) -> Result<StopReason, Error> {
+        #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
+        if self.exited {
+            report.stop_reason = StopReason::Exited;
+            return Ok(StopReason::Exited);
         }
 
-        self.cycles_pending += dt * CLOCK_SPEED;
-        while self.cycles_pending > 0.0 {
-            let cycles_taken = self.step()?;
-            self.cycles_pending -= cycles_taken as f64;
-
-            // checks for early exit
-            #[cfg(feature = "cosmac")]
-            if self.vblank_wait {
-                return Ok(());
+        // Execution is split at every timer tick so the same total time always
+        // runs the same instructions between ticks, however it was divided up
+        // between calls.
+        let mut remaining = budget.clock_units();
+        while remaining > 0 {
+            let elapsed = remaining.min(NANOS_PER_SEC - self.timers_pending);
+            remaining -= elapsed;
+            self.timers_pending += elapsed;
+            let reason = self.run_units(elapsed, stop, observer, report)?;
+            if stop.stops_on(reason) {
+                return Ok(reason);
             }
-            if self.awaiting_key.is_some() {
-                return Ok(());
+
+            if self.timers_pending == NANOS_PER_SEC {
+                self.timers_pending = 0;
+                let sound_was_playing = self.is_sound_playing();
+                self.tick();
+                #[cfg(feature = "alloc")]
+                self.replay_next_frame();
+                self.notify_sound(sound_was_playing, observer);
+                let reason = self.run_frame_cycles(stop, observer, report)?;
+                if stop.stops_on(reason) {
+                    return Ok(reason);
+                }
             }
         }
-        Ok(())
+        Ok(StopReason::BudgetExhausted)
     }
 
-    pub fn emulate_for_until(&mut self, dur: Duration, halt: impl Fn(&CPU) -> bool) -> Result<(), Error> {
-        #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
-        if self.exited {
-            return Err(Error::Exited);
+    // Runs `units` clock units of time between two timer ticks. Only a clock
+    // speed in Hz runs anything here.
+    fn run_units(
+        &mut self,
+        mut units: u64,
+        stop: &StopConditions,
+        observer: &mut dyn Chip8Observer,
+        report: &mut FrameReport,
+    ) -> Result<StopReason, Error> {
+        while units > 0 {
+            #[cfg(feature = "alloc")]
+            self.apply_key_events(observer);
+            #[cfg_attr(not(feature = "alloc"), allow(unused_mut))]
+            let mut elapsed = units;
+            // queued keys split up the time so they land between the right
+            // instructions
+            #[cfg(feature = "alloc")]
+            if let Some(until) = self.key_queue.until_next() {
+                elapsed = elapsed.min(until);
+            }
+            units -= elapsed;
+            #[cfg(feature = "alloc")]
+            self.key_queue.advance(elapsed);
+            if let ClockSpeed::Hertz(hz) = self.clock_speed {
+                // one clock unit is worth `hz` cycle units
+                if !self.vblank_wait && self.key_wait.is_none() {
+                    let units = (elapsed as i64).saturating_mul(hz as i64);
+                    self.cycles_pending = self.cycles_pending.saturating_add(units);
+                }
+                let reason = self.run_pending_cycles(stop, observer, report)?;
+                if stop.stops_on(reason) {
+                    return Ok(reason);
+                }
+            }
         }
+        Ok(StopReason::BudgetExhausted)
+    }
 
-        let dt = dur.as_secs_f64();
-        self.timers_pending += dt * TIMER_SPEED;
-        let timer_diff = self.timers_pending as u8;
-        self.delay_timer = self.delay_timer.saturating_sub(timer_diff);
-        self.sound_timer = self.sound_timer.saturating_sub(timer_diff);
-        self.timers_pending -= timer_diff as f64;
-        #[cfg(feature = "cosmac")]
-        if timer_diff > 0 {
-            self.vblank_wait = false;
-        } else if self.vblank_wait {
-            return Ok(());
-        }
+    // The 60 Hz timer tick.
+    fn tick(&mut self) {
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+        self.vblank_wait = false;
+    }
 
-        if self.awaiting_key.is_some() {
-            return Ok(());
+    // With a clock speed in instructions per frame, a whole frame's worth of
+    // cycles runs at once after each tick.
+    fn run_frame_cycles(
+        &mut self,
+        stop: &StopConditions,
+        observer: &mut dyn Chip8Observer,
+        report: &mut FrameReport,
+    ) -> Result<StopReason, Error> {
+        if let ClockSpeed::InstructionsPerFrame(ipf) = self.clock_speed {
+            self.cycles_pending = ipf as i64 * CYCLE;
+            return self.run_pending_cycles(stop, observer, report);
         }
+        Ok(StopReason::BudgetExhausted)
+    }
 
-        self.cycles_pending += dt * CLOCK_SPEED;
-        while self.cycles_pending > 0.0 {
-            let cycles_taken = self.step()?;
-            self.cycles_pending -= cycles_taken as f64;
-
-            // checks for early exit
-            if halt(self) {
-                return Err(Error::EarlyExitRequested);
+    fn run_pending_cycles(
+        &mut self,
+        stop: &StopConditions,
+        observer: &mut dyn Chip8Observer,
+        report: &mut FrameReport,
+    ) -> Result<StopReason, Error> {
+        let reason = loop {
+            if self.key_wait.is_some() {
+                break StopReason::WaitingForKey;
             }
-            #[cfg(feature = "cosmac")]
             if self.vblank_wait {
-                return Ok(());
+                break StopReason::WaitingForVBlank;
+            }
+            if self.cycles_pending <= 0 {
+                break StopReason::BudgetExhausted;
             }
-            if self.awaiting_key.is_some() {
-                return Ok(());
+            let pc = self.pc;
+            let res = self.step_and_notify(observer);
+            report.screen_changed |= self.effects.screen.is_some();
+            self.effects.clear();
+            self.cycles_pending -= res? as i64 * CYCLE;
+            report.instructions += 1;
+
+            #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
+            if self.exited {
+                break StopReason::Exited;
             }
+            if stop.predicate.map_or(false, |halt| halt(self)) {
+                break StopReason::PredicateHit(self.pc);
+            }
+            if stop.infinite_loop && self.pc == pc {
+                break StopReason::InfiniteLoop(pc);
+            }
+        };
+        report.stop_reason = reason;
+        Ok(reason)
+    }
+
+    // Whether the sound timer is running, or `FX0A` is beeping for a held key
+    // with `Quirks::key_wait_beep`.
+    #[inline]
+    pub fn is_sound_playing(&self) -> bool {
+        let key_beep = matches!(self.key_wait, Some(KeyWait::Release(..)));
+        self.sound_timer > 0 || key_beep && self.quirks.key_wait_beep
+    }
+
+    // Tells `observer` if the sound started or stopped since it was last
+    // checked.
+    fn notify_sound(&self, was_playing: bool, observer: &mut dyn Chip8Observer) {
+        let playing = self.is_sound_playing();
+        if playing != was_playing {
+            observer.sound_changed(self, playing);
         }
-        Ok(())
     }
 
     #[inline]
     pub fn read_beep_samples_to(&mut self, dur: Duration, buf: &mut [f32]) -> usize {
-        if self.sound_timer > 0 {
+        if self.is_sound_playing() {
             self.audio.read_samples_to(dur, buf)
         } else {
             0
@@ -351,25 +710,35 @@ impl CPU {
     #[cfg(feature = "std")]
     #[inline]
     pub fn get_beep_samples(&mut self, dur: Duration) -> Option<Vec<f32>> {
-        if self.sound_timer > 0 {
+        if self.is_sound_playing() {
             Some(self.audio.get_samples(dur))
         } else {
             None
         }
     }
 
-    #[inline]
-    pub(crate) fn call_routine(&mut self, pos: u16) {
+    // PC is bumped before calling the instruction, so errors report `pc - 2`
+    pub(crate) fn call_routine(&mut self, pos: u16) -> Result<(), Error> {
+        let depth = self.call_stack.len();
+        let limit = match (self.quirks.stack_limit, CallStack::CAPACITY) {
+            (Some(limit), Some(cap)) => Some(limit.min(cap)),
+            (limit, cap) => limit.or(cap),
+        };
+        if limit.map_or(false, |limit| depth >= limit) {
+            return Err(Error::StackOverflow(self.pc - 2));
+        }
         self.call_stack.push(self.pc);
         self.pc = pos;
+        Ok(())
     }
 
-    #[inline]
-    pub(crate) fn return_routine(&mut self) {
+    pub(crate) fn return_routine(&mut self) -> Result<(), Error> {
         if let Some(addr) = self.call_stack.pop() {
             self.pc = addr;
+            Ok(())
+        } else {
+            Err(Error::StackUnderflow(self.pc - 2))
         }
-        // TODO raise error instead of no-op if no return address?
     }
 
     #[inline]
@@ -417,6 +786,55 @@ impl CPU {
         }
     }
 
+    // Resolves `I + offset` to a memory address according to the
+    // `index_overflow` quirk; `None` means the access should be ignored.
+    fn index_address(&self, offset: u16) -> Result<Option<usize>, Error> {
+        let addr = self.wrap_index(self.index as usize + offset as usize);
+        if addr < CHIP8_MEM_SIZE {
+            Ok(Some(addr))
+        } else if self.quirks.index_overflow == IndexOverflow::Ignore {
+            Ok(None)
+        } else {
+            // PC is bumped before calling the instruction
+            Err(Error::MemoryFault(self.pc.wrapping_sub(2), addr as u32))
+        }
+    }
+
+    // Wraps an address computed from I according to the `index_overflow`
+    // quirk.
+    #[inline]
+    fn wrap_index(&self, addr: usize) -> usize {
+        match self.quirks.index_overflow {
+            IndexOverflow::Wrap12 => addr & 0xFFF,
+            IndexOverflow::Wrap16 => addr & 0xFFFF,
+            IndexOverflow::Fault | IndexOverflow::Ignore => addr,
+        }
+    }
+
+    // Adds `offset` to I, wrapping like an address unless the access itself
+    // should fault or be ignored later. I is only 16 bits, so it always
+    // wraps around there.
+    #[inline]
+    pub(crate) fn advance_index(&mut self, offset: u16) {
+        self.index = self.wrap_index(self.index.wrapping_add(offset) as usize) as u16;
+    }
+
+    #[inline]
+    pub(crate) fn read_index_byte(&self, offset: u16) -> Result<u8, Error> {
+        Ok(self
+            .index_address(offset)?
+            .map_or(0, |addr| self.memory[addr]))
+    }
+
+    #[inline]
+    pub(crate) fn write_index_byte(&mut self, offset: u16, byte: u8) -> Result<(), Error> {
+        if let Some(addr) = self.index_address(offset)? {
+            self.memory[addr] = byte;
+            self.effects.add_write(addr as u16, byte);
+        }
+        Ok(())
+    }
+
     #[inline]
     pub fn is_key_down(&self, key: u8) -> bool {
         if key > 0xF {
@@ -429,7 +847,12 @@ impl CPU {
 
     #[inline]
     pub(crate) fn await_key(&mut self, post_reg: Register) {
-        self.awaiting_key = Some(post_reg);
+        self.key_wait = Some(KeyWait::Press(post_reg));
+    }
+
+    #[inline]
+    pub fn key_wait(&self) -> Option<KeyWait> {
+        self.key_wait
     }
 
     #[inline]
@@ -437,7 +860,16 @@ impl CPU {
         if key > 0xF {
             // TODO raise error instead of no-op if out-of-bounds?
         } else {
+            let fresh = !self.input[key as usize];
             self.input[key as usize] = true;
+            if let (true, Some(KeyWait::Press(reg))) = (fresh, self.key_wait) {
+                if self.quirks.key_wait_on_press {
+                    self.registers[reg] = key;
+                    self.key_wait = None;
+                } else {
+                    self.key_wait = Some(KeyWait::Release(reg, key));
+                }
+            }
         }
     }
 
@@ -447,29 +879,210 @@ impl CPU {
             // TODO raise error instead of no-op if out-of-bounds?
         } else {
             self.input[key as usize] = false;
-            if let Some(reg) = self.awaiting_key {
-                self.registers[reg] = key;
-                self.awaiting_key = None;
+            if let Some(KeyWait::Release(reg, pressed)) = self.key_wait {
+                if pressed == key {
+                    self.registers[reg] = key;
+                    self.key_wait = None;
+                }
             }
         }
     }
 
+    // Queues a key press for `at` into the emulated time run by the next calls
+    // to `run`, rather than applying it straight away like `press_key`.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn press_key_at(&mut self, key: u8, at: Duration) {
+        self.queue_key(key, at, true);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn release_key_at(&mut self, key: u8, at: Duration) {
+        self.queue_key(key, at, false);
+    }
+
+    #[cfg(feature = "alloc")]
+    fn queue_key(&mut self, key: u8, at: Duration, pressed: bool) {
+        if key <= 0xF {
+            let offset = Budget::Time(at).clock_units();
+            self.key_queue.push(offset, key, pressed);
+        }
+    }
+
+    // Number of queued presses and releases that haven't happened yet.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn queued_key_events(&self) -> usize {
+        self.key_queue.len()
+    }
+
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn clear_key_events(&mut self) {
+        self.key_queue.clear();
+    }
+
+    #[cfg(feature = "alloc")]
+    fn apply_key_events(&mut self, observer: &mut dyn Chip8Observer) {
+        let sound_was_playing = self.is_sound_playing();
+        while let Some((key, pressed)) = self.key_queue.pop_due(self.hold_taps) {
+            if pressed {
+                self.press_key(key);
+            } else {
+                self.release_key(key);
+            }
+        }
+        self.notify_sound(sound_was_playing, observer);
+    }
+
     pub fn load_rom(&mut self, buf: &[u8]) -> Result<(), Error> {
         if buf.len() > (CHIP8_MEM_SIZE - 0x200) {
             return Err(Error::InvalidFile);
         }
         #[cfg(feature = "xo-chip")]
-        if buf.len() > 0x1000 && self.mode != Chip8Mode::XoChip {
+        let fresh = if buf.len() > 0x1000 && self.mode != Chip8Mode::XoChip {
             info!("Attempted to load a large ROM outside XO-CHIP mode; switching modes");
-            let _ = core::mem::replace(self, Self::new(Chip8Mode::XoChip));
-            self.memory[0x200..(buf.len() + 0x200)].copy_from_slice(buf);
-            return Ok(());
+            Self::new(Chip8Mode::XoChip)
+        } else {
+            Self::with_quirks(self.mode, self.quirks)
+        };
+        #[cfg(not(feature = "xo-chip"))]
+        let fresh = Self::with_quirks(self.mode, self.quirks);
+        let mut old = core::mem::replace(self, fresh);
+        old.screen.replace(core::mem::take(&mut self.screen));
+        self.screen = old.screen;
+        self.clock_speed = old.clock_speed;
+        self.font = old.font;
+        #[cfg(feature = "alloc")]
+        {
+            self.hold_taps = old.hold_taps;
+        }
+        self.clear_memory();
+        // the flags outlive the program even without storage attached
+        #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
+        {
+            self.persistent_registers = old.persistent_registers;
+        }
+        #[cfg(all(feature = "alloc", any(feature = "super-chip", feature = "xo-chip")))]
+        {
+            self.flag_storage = old.flag_storage;
+        }
+        // keep profiling, but the old counts are for a different program
+        #[cfg(feature = "alloc")]
+        if old.profile.is_some() {
+            self.enable_profiling();
         }
-        let _ = core::mem::replace(self, Self::new(self.mode));
         self.memory[0x200..(buf.len() + 0x200)].copy_from_slice(buf);
         Ok(())
     }
 
+    #[inline]
+    pub fn font(&self) -> &Font {
+        &self.font
+    }
+
+    // Replaces the font, blanking the old glyphs and loading the new ones into
+    // memory. It stays in place across `load_rom`.
+    pub fn set_font(&mut self, font: impl Into<Font>) -> Result<(), FontError> {
+        let font = font.into();
+        font.validate()?;
+        self.font.clear_from(&mut self.memory);
+        font.load_into(&mut self.memory);
+        self.font = font;
+        Ok(())
+    }
+
+    // Attaches storage for `FX75` to save the flag registers to and `FX85` to
+    // load them from, which stays attached across `load_rom`. The flags held
+    // so far are forgotten, since they may belong to another program.
+    #[cfg(all(feature = "alloc", any(feature = "super-chip", feature = "xo-chip")))]
+    #[inline]
+    pub fn set_flag_storage(&mut self, storage: Box<dyn FlagStorage>) {
+        self.flag_storage.0 = Some(storage);
+        self.persistent_registers = enum_map::enum_map! { _ => 0 };
+    }
+
+    // Detaches the storage, forgetting the flags loaded from it.
+    #[cfg(all(feature = "alloc", any(feature = "super-chip", feature = "xo-chip")))]
+    #[inline]
+    pub fn take_flag_storage(&mut self) -> Option<Box<dyn FlagStorage>> {
+        self.persistent_registers = enum_map::enum_map! { _ => 0 };
+        self.flag_storage.0.take()
+    }
+
+    #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
+    pub(crate) fn load_flags(&mut self) {
+        // storage that hasn't saved anything yet holds blank flags
+        #[cfg(feature = "alloc")]
+        if let Some(storage) = self.flag_storage.0.as_mut() {
+            let flags = storage.load().unwrap_or_default();
+            self.persistent_registers = enum_map::EnumMap::from_array(flags);
+        }
+    }
+
+    #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
+    pub(crate) fn save_flags(&mut self) {
+        #[cfg(feature = "alloc")]
+        if let Some(storage) = self.flag_storage.0.as_mut() {
+            storage.save(&self.persistent_registers.into_array());
+        }
+    }
+
+    // Starts counting executions and cycles per address and per call target,
+    // discarding any earlier profile.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn enable_profiling(&mut self) {
+        self.profile = Some(Box::new(Profile::new()));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_deref()
+    }
+
+    // Stops profiling and returns what was gathered.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        self.profile.take().map(|profile| *profile)
+    }
+
+    // Starts replaying `script` from frame 0, applying each frame's events at
+    // the timer tick that starts it, so replays are the same however `run`'s
+    // budgets divide up the time. Frame 0's events are applied right away.
+    // Loading a ROM stops the replay.
+    #[cfg(feature = "alloc")]
+    pub fn play_input(&mut self, script: InputScript) {
+        let mut replay = InputReplay::new(script);
+        replay.apply(self);
+        self.input_replay = Some(replay);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn input_replay(&self) -> Option<&InputReplay> {
+        self.input_replay.as_ref()
+    }
+
+    // Stops replaying input, leaving held keys as they are.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn stop_input(&mut self) -> Option<InputScript> {
+        self.input_replay.take().map(InputReplay::into_script)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn replay_next_frame(&mut self) {
+        if let Some(mut replay) = self.input_replay.take() {
+            replay.next_frame();
+            replay.apply(self);
+            self.input_replay = Some(replay);
+        }
+    }
+
     pub fn hotswap(&mut self, buf: &[u8]) -> Result<(), Error> {
         if buf.len() > (CHIP8_MEM_SIZE - 0x200) {
             return Err(Error::InvalidFile);
@@ -478,18 +1091,14 @@ impl CPU {
         Ok(())
     }
 
-    #[inline]
-    pub fn disassemble(&self, idx: u16) -> Option<&str> {
-        let word = self.read_memory_word(idx);
-        if let Ok(word) = word {
-            Instruction::lookup(word).map(|inst| inst.disassembly)
-        } else {
-            None
-        }
+    pub fn disassemble(&self, idx: u16) -> Option<DecodedInstruction> {
+        let start = (idx as usize).min(CHIP8_MEM_SIZE);
+        let end = (start + 4).min(CHIP8_MEM_SIZE);
+        DecodedInstruction::decode(&self.memory[start..end])
     }
 
     #[inline]
-    pub fn disassemble_next(&self) -> Option<&str> {
+    pub fn disassemble_next(&self) -> Option<DecodedInstruction> {
         self.disassemble(self.pc)
     }
 
@@ -498,9 +1107,6 @@ impl CPU {
         if self.exited {
             return Err(Error::Exited);
         }
-        let seed = self.random_state.rand();
-        // for consistency of random state in saved states
-        self.random_state.reseed(seed);
         Ok(SavedState {
             mode: self.mode,
             pc: self.pc,
@@ -511,8 +1117,13 @@ impl CPU {
             call_stack: self.call_stack.clone(),
             delay_timer: self.delay_timer,
             sound_timer: self.sound_timer,
-            awaiting_key: self.awaiting_key,
-            seed,
+            key_wait: self.key_wait,
+            rng_state: self.random_state.state(),
+            audio: self.audio,
+            input: self.input,
+            cycles_pending: self.cycles_pending,
+            timers_pending: self.timers_pending,
+            vblank_wait: self.vblank_wait,
             #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
             persistent_registers: self.persistent_registers,
         })
@@ -527,34 +1138,44 @@ impl CPU {
                 self.persistent_registers = state.persistent_registers;
             }
         }
-        self.cycles_pending = 0.0;
-        self.timers_pending = 0.0;
-        cfg_if::cfg_if! {
-            if #[cfg(feature = "cosmac")] {
-                self.vblank_wait = false;
-            }
-        }
+        self.cycles_pending = state.cycles_pending;
+        self.timers_pending = state.timers_pending;
+        self.vblank_wait = state.vblank_wait;
 
         self.pc = state.pc;
         self.index = state.index;
         self.registers = state.registers;
         self.memory = state.memory;
-        self.screen = state.screen;
+        self.screen.replace(state.screen);
         self.call_stack = state.call_stack;
         self.delay_timer = state.delay_timer;
         self.sound_timer = state.sound_timer;
-        self.awaiting_key = state.awaiting_key;
-        self.random_state.reseed(state.seed);
+        self.key_wait = state.key_wait;
+        self.audio = state.audio;
+        self.input = state.input;
+        self.random_state.get().set_state(state.rng_state);
+        // queued keys were meant for the state being replaced
+        #[cfg(feature = "alloc")]
+        self.key_queue.clear();
     }
 
     #[inline]
     pub fn random(&mut self) -> u8 {
-        self.random_state.generate()
+        self.random_state.get().next_byte()
     }
 
+    // Sets the state of the random number generator, which for the default
+    // `WyRand` is its seed.
     #[inline]
     pub fn reseed(&mut self, seed: u64) {
-        self.random_state = WyRand::new_seed(seed);
+        self.random_state.get().set_state(seed);
+    }
+
+    // Replaces the generator `CXNN` draws from.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn set_rng(&mut self, rng: Box<dyn Rng>) {
+        self.random_state = RngSlot::Custom(rng);
     }
 }
 
@@ -0,0 +1,440 @@
+use crate::instruction::Instruction;
+use crate::{Chip8Mode, Register};
+
+use core::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DecodedInstruction {
+    // 0000
+    Nop,
+    // 00CN
+    ScrollDown(u8),
+    // 00DN
+    ScrollUp(u8),
+    // 00E0
+    Clear,
+    // 00EE
+    Return,
+    // 00FB
+    ScrollRight,
+    // 00FC
+    ScrollLeft,
+    // 00FD
+    Exit,
+    // 00FE
+    LowRes,
+    // 00FF
+    HighRes,
+    // 1NNN
+    Goto(u16),
+    // 2NNN
+    Call(u16),
+    // 3XNN
+    SkipIfEqual(Register, u8),
+    // 4XNN
+    SkipIfNotEqual(Register, u8),
+    // 5XY0
+    SkipIfEqualRegister(Register, Register),
+    // 5XY2
+    RegDumpRange(Register, Register),
+    // 5XY3
+    RegLoadRange(Register, Register),
+    // 6XNN
+    SetRegister(Register, u8),
+    // 7XNN
+    AddImmediate(Register, u8),
+    // 8XY0
+    Move(Register, Register),
+    // 8XY1
+    Or(Register, Register),
+    // 8XY2
+    And(Register, Register),
+    // 8XY3
+    Xor(Register, Register),
+    // 8XY4
+    Add(Register, Register),
+    // 8XY5
+    Sub(Register, Register),
+    // 8XY6
+    ShiftRight(Register, Register),
+    // 8XY7
+    SubReverse(Register, Register),
+    // 8XYE
+    ShiftLeft(Register, Register),
+    // 9XYN, where N is normally 0 but any last nibble behaves the same; it's
+    // kept so the instruction encodes back to the same bytes
+    SkipIfNotEqualRegister(Register, Register, u8),
+    // ANNN
+    SetIndex(u16),
+    // BNNN, shown as a jump from V0 even though the `jump` quirk makes it
+    // BXNN, since decoding doesn't know the quirks in use
+    JumpOffset(u16),
+    // CXNN
+    Random(Register, u8),
+    // DXYN
+    Draw(Register, Register, u8),
+    // EX9E
+    SkipIfKey(Register),
+    // EXA1
+    SkipIfNotKey(Register),
+    // F000 NNNN
+    SetLongIndex(u16),
+    // FN01
+    SetPlane(u8),
+    // FX02, where X is normally 0 but is ignored, and kept like 9XYN's N
+    LoadAudioPattern(u8),
+    // FX07
+    GetDelay(Register),
+    // FX0A
+    GetKey(Register),
+    // FX15
+    SetDelay(Register),
+    // FX18
+    SetSound(Register),
+    // FX1E
+    AddToIndex(Register),
+    // FX29
+    SpriteAddress(Register),
+    // FX30
+    BigSpriteAddress(Register),
+    // FX33
+    Bcd(Register),
+    // FX3A
+    SetPitch(Register),
+    // FX55
+    RegDump(Register),
+    // FX65
+    RegLoad(Register),
+    // FX75
+    PersistDump(Register),
+    // FX85
+    PersistLoad(Register),
+}
+
+#[inline]
+fn nibble(opcode: u16, shift: u16) -> u8 {
+    ((opcode >> shift) & 0xF) as u8
+}
+
+#[inline]
+fn reg(opcode: u16, shift: u16) -> Register {
+    nibble(opcode, shift).try_into().unwrap()
+}
+
+impl DecodedInstruction {
+    // Decodes the instruction at the start of `bytes`, which needs to hold
+    // four bytes for the double-word `F000 NNNN`.
+    pub fn decode(bytes: &[u8]) -> Option<DecodedInstruction> {
+        use DecodedInstruction::*;
+
+        let opcode = (*bytes.first()? as u16) << 8 | *bytes.get(1)? as u16;
+        let x = reg(opcode, 8);
+        let y = reg(opcode, 4);
+        let n = nibble(opcode, 0);
+        let nn = (opcode & 0xFF) as u8;
+        let nnn = opcode & 0xFFF;
+        let nibbles = (nibble(opcode, 12), nibble(opcode, 8), nibble(opcode, 4), n);
+        Some(match nibbles {
+            (0x0, 0x0, 0x0, 0x0) => Nop,
+            (0x0, 0x0, 0xC, _) => ScrollDown(n),
+            (0x0, 0x0, 0xD, _) => ScrollUp(n),
+            (0x0, 0x0, 0xE, 0x0) => Clear,
+            (0x0, 0x0, 0xE, 0xE) => Return,
+            (0x0, 0x0, 0xF, 0xB) => ScrollRight,
+            (0x0, 0x0, 0xF, 0xC) => ScrollLeft,
+            (0x0, 0x0, 0xF, 0xD) => Exit,
+            (0x0, 0x0, 0xF, 0xE) => LowRes,
+            (0x0, 0x0, 0xF, 0xF) => HighRes,
+            (0x1, _, _, _) => Goto(nnn),
+            (0x2, _, _, _) => Call(nnn),
+            (0x3, _, _, _) => SkipIfEqual(x, nn),
+            (0x4, _, _, _) => SkipIfNotEqual(x, nn),
+            (0x5, _, _, 0x0) => SkipIfEqualRegister(x, y),
+            (0x5, _, _, 0x2) => RegDumpRange(x, y),
+            (0x5, _, _, 0x3) => RegLoadRange(x, y),
+            (0x6, _, _, _) => SetRegister(x, nn),
+            (0x7, _, _, _) => AddImmediate(x, nn),
+            (0x8, _, _, 0x0) => Move(x, y),
+            (0x8, _, _, 0x1) => Or(x, y),
+            (0x8, _, _, 0x2) => And(x, y),
+            (0x8, _, _, 0x3) => Xor(x, y),
+            (0x8, _, _, 0x4) => Add(x, y),
+            (0x8, _, _, 0x5) => Sub(x, y),
+            (0x8, _, _, 0x6) => ShiftRight(x, y),
+            (0x8, _, _, 0x7) => SubReverse(x, y),
+            (0x8, _, _, 0xE) => ShiftLeft(x, y),
+            (0x9, _, _, _) => SkipIfNotEqualRegister(x, y, n),
+            (0xA, _, _, _) => SetIndex(nnn),
+            (0xB, _, _, _) => JumpOffset(nnn),
+            (0xC, _, _, _) => Random(x, nn),
+            (0xD, _, _, _) => Draw(x, y, n),
+            (0xE, _, 0x9, 0xE) => SkipIfKey(x),
+            (0xE, _, 0xA, 0x1) => SkipIfNotKey(x),
+            (0xF, 0x0, 0x0, 0x0) => {
+                SetLongIndex((*bytes.get(2)? as u16) << 8 | *bytes.get(3)? as u16)
+            }
+            (0xF, _, 0x0, 0x1) => SetPlane(nibble(opcode, 8)),
+            (0xF, _, 0x0, 0x2) => LoadAudioPattern(nibble(opcode, 8)),
+            (0xF, _, 0x0, 0x7) => GetDelay(x),
+            (0xF, _, 0x0, 0xA) => GetKey(x),
+            (0xF, _, 0x1, 0x5) => SetDelay(x),
+            (0xF, _, 0x1, 0x8) => SetSound(x),
+            (0xF, _, 0x1, 0xE) => AddToIndex(x),
+            (0xF, _, 0x2, 0x9) => SpriteAddress(x),
+            (0xF, _, 0x3, 0x0) => BigSpriteAddress(x),
+            (0xF, _, 0x3, 0x3) => Bcd(x),
+            (0xF, _, 0x3, 0xA) => SetPitch(x),
+            (0xF, _, 0x5, 0x5) => RegDump(x),
+            (0xF, _, 0x6, 0x5) => RegLoad(x),
+            (0xF, _, 0x7, 0x5) => PersistDump(x),
+            (0xF, _, 0x8, 0x5) => PersistLoad(x),
+            _ => return None,
+        })
+    }
+
+    // Size of the encoded instruction in bytes.
+    #[inline]
+    pub fn size(&self) -> usize {
+        if let Self::SetLongIndex(_) = self {
+            4
+        } else {
+            2
+        }
+    }
+
+    // The first (or only) word of the encoded instruction.
+    pub fn opcode(&self) -> u16 {
+        use DecodedInstruction::*;
+
+        #[inline]
+        fn xy(base: u16, x: Register, y: Register) -> u16 {
+            base | (x as u16) << 8 | (y as u16) << 4
+        }
+        #[inline]
+        fn xnn(base: u16, x: Register, nn: u8) -> u16 {
+            base | (x as u16) << 8 | nn as u16
+        }
+
+        match *self {
+            Nop => 0x0000,
+            ScrollDown(n) => 0x00C0 | (n & 0xF) as u16,
+            ScrollUp(n) => 0x00D0 | (n & 0xF) as u16,
+            Clear => 0x00E0,
+            Return => 0x00EE,
+            ScrollRight => 0x00FB,
+            ScrollLeft => 0x00FC,
+            Exit => 0x00FD,
+            LowRes => 0x00FE,
+            HighRes => 0x00FF,
+            Goto(nnn) => 0x1000 | (nnn & 0xFFF),
+            Call(nnn) => 0x2000 | (nnn & 0xFFF),
+            SkipIfEqual(x, nn) => xnn(0x3000, x, nn),
+            SkipIfNotEqual(x, nn) => xnn(0x4000, x, nn),
+            SkipIfEqualRegister(x, y) => xy(0x5000, x, y),
+            RegDumpRange(x, y) => xy(0x5002, x, y),
+            RegLoadRange(x, y) => xy(0x5003, x, y),
+            SetRegister(x, nn) => xnn(0x6000, x, nn),
+            AddImmediate(x, nn) => xnn(0x7000, x, nn),
+            Move(x, y) => xy(0x8000, x, y),
+            Or(x, y) => xy(0x8001, x, y),
+            And(x, y) => xy(0x8002, x, y),
+            Xor(x, y) => xy(0x8003, x, y),
+            Add(x, y) => xy(0x8004, x, y),
+            Sub(x, y) => xy(0x8005, x, y),
+            ShiftRight(x, y) => xy(0x8006, x, y),
+            SubReverse(x, y) => xy(0x8007, x, y),
+            ShiftLeft(x, y) => xy(0x800E, x, y),
+            SkipIfNotEqualRegister(x, y, n) => xy(0x9000, x, y) | (n & 0xF) as u16,
+            SetIndex(nnn) => 0xA000 | (nnn & 0xFFF),
+            JumpOffset(nnn) => 0xB000 | (nnn & 0xFFF),
+            Random(x, nn) => xnn(0xC000, x, nn),
+            Draw(x, y, n) => xy(0xD000, x, y) | (n & 0xF) as u16,
+            SkipIfKey(x) => xnn(0xE09E, x, 0),
+            SkipIfNotKey(x) => xnn(0xE0A1, x, 0),
+            SetLongIndex(_) => 0xF000,
+            SetPlane(n) => 0xF001 | ((n & 0xF) as u16) << 8,
+            LoadAudioPattern(n) => 0xF002 | ((n & 0xF) as u16) << 8,
+            GetDelay(x) => xnn(0xF007, x, 0),
+            GetKey(x) => xnn(0xF00A, x, 0),
+            SetDelay(x) => xnn(0xF015, x, 0),
+            SetSound(x) => xnn(0xF018, x, 0),
+            AddToIndex(x) => xnn(0xF01E, x, 0),
+            SpriteAddress(x) => xnn(0xF029, x, 0),
+            BigSpriteAddress(x) => xnn(0xF030, x, 0),
+            Bcd(x) => xnn(0xF033, x, 0),
+            SetPitch(x) => xnn(0xF03A, x, 0),
+            RegDump(x) => xnn(0xF055, x, 0),
+            RegLoad(x) => xnn(0xF065, x, 0),
+            PersistDump(x) => xnn(0xF075, x, 0),
+            PersistLoad(x) => xnn(0xF085, x, 0),
+        }
+    }
+
+    // Writes the encoded instruction to the start of `buf`, returning the
+    // number of bytes written, or `None` if `buf` is too small.
+    pub fn encode(&self, buf: &mut [u8]) -> Option<usize> {
+        let size = self.size();
+        let buf = buf.get_mut(..size)?;
+        buf[..2].copy_from_slice(&self.opcode().to_be_bytes());
+        if let Self::SetLongIndex(nnnn) = self {
+            buf[2..].copy_from_slice(&nnnn.to_be_bytes());
+        }
+        Some(size)
+    }
+
+    #[inline]
+    pub fn is_defined_for(&self, mode: Chip8Mode) -> bool {
+        Instruction::lookup(self.opcode()).map_or(false, |inst| inst.for_mode(mode).is_some())
+    }
+
+    #[inline]
+    pub fn octo(&self) -> Octo<'_> {
+        Octo(self)
+    }
+}
+
+impl fmt::Display for DecodedInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use DecodedInstruction::*;
+
+        match *self {
+            Nop => write!(f, "(void) 0;"),
+            ScrollDown(n) => write!(f, "scroll_down({});", n),
+            ScrollUp(n) => write!(f, "scroll_up({});", n),
+            Clear => write!(f, "display_clear();"),
+            Return => write!(f, "return;"),
+            ScrollRight => write!(f, "scroll_right();"),
+            ScrollLeft => write!(f, "scroll_left();"),
+            Exit => write!(f, "exit();"),
+            LowRes => write!(f, "low_res();"),
+            HighRes => write!(f, "high_res();"),
+            Goto(nnn) => write!(f, "goto 0x{:03X};", nnn),
+            Call(nnn) => write!(f, "*(0x{:03X})();", nnn),
+            SkipIfEqual(x, nn) => write!(f, "if ({} == 0x{:02X}) goto next;", x, nn),
+            SkipIfNotEqual(x, nn) => write!(f, "if ({} != 0x{:02X}) goto next;", x, nn),
+            SkipIfEqualRegister(x, y) => write!(f, "if ({} == {}) goto next;", x, y),
+            RegDumpRange(x, y) => write!(f, "reg_dump({}, {}, &I);", x, y),
+            RegLoadRange(x, y) => write!(f, "reg_load({}, {}, &I);", x, y),
+            SetRegister(x, nn) => write!(f, "{} = 0x{:02X};", x, nn),
+            AddImmediate(x, nn) => write!(f, "{} += 0x{:02X};", x, nn),
+            Move(x, y) => write!(f, "{} = {};", x, y),
+            Or(x, y) => write!(f, "{} |= {};", x, y),
+            And(x, y) => write!(f, "{} &= {};", x, y),
+            Xor(x, y) => write!(f, "{} ^= {};", x, y),
+            Add(x, y) => write!(f, "{} += {};", x, y),
+            Sub(x, y) => write!(f, "{} -= {};", x, y),
+            ShiftRight(x, y) => write!(f, "{} = {} >> 1;", x, y),
+            SubReverse(x, y) => write!(f, "{} = {} - {};", x, y, x),
+            ShiftLeft(x, y) => write!(f, "{} = {} << 1;", x, y),
+            SkipIfNotEqualRegister(x, y, _) => write!(f, "if ({} != {}) goto next;", x, y),
+            SetIndex(nnn) => write!(f, "I = 0x{:03X};", nnn),
+            JumpOffset(nnn) => write!(f, "PC = V0 + 0x{:03X};", nnn),
+            Random(x, nn) => write!(f, "{} = rand() & 0x{:02X};", x, nn),
+            Draw(x, y, n) => write!(f, "draw({}, {}, {});", x, y, n),
+            SkipIfKey(x) => write!(f, "if (key() == {}) goto next;", x),
+            SkipIfNotKey(x) => write!(f, "if (key() != {}) goto next;", x),
+            SetLongIndex(nnnn) => write!(f, "I = 0x{:04X};", nnnn),
+            SetPlane(n) => write!(f, "set_drawing_plane({});", n),
+            LoadAudioPattern(_) => write!(f, "load_audio_pattern(I);"),
+            GetDelay(x) => write!(f, "{} = get_delay();", x),
+            GetKey(x) => write!(f, "{} = get_key();", x),
+            SetDelay(x) => write!(f, "delay_timer({});", x),
+            SetSound(x) => write!(f, "sound_timer({});", x),
+            AddToIndex(x) => write!(f, "I += {};", x),
+            SpriteAddress(x) => write!(f, "I = sprite_addr[{}];", x),
+            BigSpriteAddress(x) => write!(f, "I = digit_addr[{}];", x),
+            Bcd(x) => write!(f, "set_bcd(I, {});", x),
+            SetPitch(x) => write!(f, "set_audio_hertz({});", x),
+            RegDump(x) => write!(f, "reg_dump(V0, {}, &I);", x),
+            RegLoad(x) => write!(f, "reg_load(V0, {}, &I);", x),
+            PersistDump(x) => write!(f, "persist_dump({});", x),
+            PersistLoad(x) => write!(f, "persist_load({});", x),
+        }
+    }
+}
+
+// Formats a `DecodedInstruction` using Octo assembly syntax.
+#[derive(Clone, Copy, Debug)]
+pub struct Octo<'a>(&'a DecodedInstruction);
+
+// Octo spells registers in lowercase, as in `va`
+struct OctoRegister(Register);
+impl fmt::Display for OctoRegister {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{:x}", self.0 as u8)
+    }
+}
+
+impl fmt::Display for Octo<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use DecodedInstruction::*;
+        use OctoRegister as R;
+
+        match *self.0 {
+            Nop => write!(f, "0x00 0x00"),
+            ScrollDown(n) => write!(f, "scroll-down {}", n),
+            ScrollUp(n) => write!(f, "scroll-up {}", n),
+            Clear => write!(f, "clear"),
+            Return => write!(f, "return"),
+            ScrollRight => write!(f, "scroll-right"),
+            ScrollLeft => write!(f, "scroll-left"),
+            Exit => write!(f, "exit"),
+            LowRes => write!(f, "lores"),
+            HighRes => write!(f, "hires"),
+            Goto(nnn) => write!(f, "jump 0x{:03X}", nnn),
+            Call(nnn) => write!(f, ":call 0x{:03X}", nnn),
+            // Octo's conditionals are written in terms of the next
+            // instruction running, the opposite of when it's skipped
+            SkipIfEqual(x, nn) => write!(f, "if {} != 0x{:02X} then", R(x), nn),
+            SkipIfNotEqual(x, nn) => write!(f, "if {} == 0x{:02X} then", R(x), nn),
+            SkipIfEqualRegister(x, y) => write!(f, "if {} != {} then", R(x), R(y)),
+            RegDumpRange(x, y) => write!(f, "save {} - {}", R(x), R(y)),
+            RegLoadRange(x, y) => write!(f, "load {} - {}", R(x), R(y)),
+            SetRegister(x, nn) => write!(f, "{} := 0x{:02X}", R(x), nn),
+            AddImmediate(x, nn) => write!(f, "{} += 0x{:02X}", R(x), nn),
+            Move(x, y) => write!(f, "{} := {}", R(x), R(y)),
+            Or(x, y) => write!(f, "{} |= {}", R(x), R(y)),
+            And(x, y) => write!(f, "{} &= {}", R(x), R(y)),
+            Xor(x, y) => write!(f, "{} ^= {}", R(x), R(y)),
+            Add(x, y) => write!(f, "{} += {}", R(x), R(y)),
+            Sub(x, y) => write!(f, "{} -= {}", R(x), R(y)),
+            ShiftRight(x, y) => write!(f, "{} >>= {}", R(x), R(y)),
+            SubReverse(x, y) => write!(f, "{} =- {}", R(x), R(y)),
+            ShiftLeft(x, y) => write!(f, "{} <<= {}", R(x), R(y)),
+            SkipIfNotEqualRegister(x, y, 0) => write!(f, "if {} == {} then", R(x), R(y)),
+            // Octo only assembles 9XY0, so any other last nibble is written
+            // out as data, and likewise FX02 below
+            SkipIfNotEqualRegister(x, y, n) => {
+                write!(f, "0x{:02X} 0x{:02X}", 0x90 | x as u8, (y as u8) << 4 | n)
+            }
+            SetIndex(nnn) => write!(f, "i := 0x{:03X}", nnn),
+            JumpOffset(nnn) => write!(f, "jump0 0x{:03X}", nnn),
+            Random(x, nn) => write!(f, "{} := random 0x{:02X}", R(x), nn),
+            Draw(x, y, n) => write!(f, "sprite {} {} {}", R(x), R(y), n),
+            SkipIfKey(x) => write!(f, "if {} -key then", R(x)),
+            SkipIfNotKey(x) => write!(f, "if {} key then", R(x)),
+            SetLongIndex(nnnn) => write!(f, "i := long 0x{:04X}", nnnn),
+            SetPlane(n) => write!(f, "plane {}", n),
+            LoadAudioPattern(0) => write!(f, "audio"),
+            LoadAudioPattern(n) => write!(f, "0x{:02X} 0x02", 0xF0 | n),
+            GetDelay(x) => write!(f, "{} := delay", R(x)),
+            GetKey(x) => write!(f, "{} := key", R(x)),
+            SetDelay(x) => write!(f, "delay := {}", R(x)),
+            SetSound(x) => write!(f, "buzzer := {}", R(x)),
+            AddToIndex(x) => write!(f, "i += {}", R(x)),
+            SpriteAddress(x) => write!(f, "i := hex {}", R(x)),
+            BigSpriteAddress(x) => write!(f, "i := bighex {}", R(x)),
+            Bcd(x) => write!(f, "bcd {}", R(x)),
+            SetPitch(x) => write!(f, "pitch := {}", R(x)),
+            RegDump(x) => write!(f, "save {}", R(x)),
+            RegLoad(x) => write!(f, "load {}", R(x)),
+            PersistDump(x) => write!(f, "saveflags {}", R(x)),
+            PersistLoad(x) => write!(f, "loadflags {}", R(x)),
+        }
+    }
+}
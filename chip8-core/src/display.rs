@@ -1,6 +1,11 @@
 #[cfg(feature = "alloc")]
 use alloc::{vec, vec::Vec};
+use core::fmt;
+#[cfg(feature = "alloc")]
+use core::iter;
 
+#[cfg(feature = "alloc")]
+use crate::common::take;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "serde")]
@@ -29,7 +34,43 @@ cfg_if::cfg_if! {
     }
 }
 
-#[derive(Clone, Debug)]
+// A region of the screen, in the pixels of the resolution it was drawn in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ScreenRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl ScreenRect {
+    #[inline]
+    pub fn full((width, height): (usize, usize)) -> ScreenRect {
+        ScreenRect {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        }
+    }
+
+    // The smallest rectangle containing both `self` and `other`.
+    pub fn union(&self, other: ScreenRect) -> ScreenRect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        ScreenRect {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        }
+    }
+}
+
+#[derive(Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Display {
     #[cfg_attr(feature = "serde", serde(with = "BigArray"))]
@@ -41,6 +82,14 @@ pub struct Display {
     pub(crate) write_mask: u8,
     #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
     pub(crate) high_res: bool,
+    // what changed since the last `take_dirty`, in the pixels of the buffer
+    #[cfg_attr(feature = "serde", serde(skip, default = "Display::all_dirty"))]
+    dirty: Option<ScreenRect>,
+    // whether the drawing operation in progress wrote anything yet
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pending: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    generation: u64,
 }
 
 impl Display {
@@ -54,20 +103,128 @@ impl Display {
             write_mask: 1,
             #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
             high_res: false,
+            dirty: Self::all_dirty(),
+            pending: false,
+            generation: 0,
+        }
+    }
+
+    #[inline]
+    fn all_dirty() -> Option<ScreenRect> {
+        Some(ScreenRect::full(SCREEN_DIMENSIONS))
+    }
+
+    // Records a write to `rect` as part of the drawing operation in progress.
+    #[inline]
+    fn mark(&mut self, rect: ScreenRect) {
+        self.dirty = Some(self.dirty.map_or(rect, |dirty| dirty.union(rect)));
+        self.pending = true;
+    }
+
+    // Ends the drawing operation in progress, starting a new generation if it
+    // wrote anything.
+    #[inline]
+    pub(crate) fn finish_drawing(&mut self) {
+        if self.pending {
+            self.pending = false;
+            self.generation = self.generation.wrapping_add(1);
+        }
+    }
+
+    // A write to `rect` that is a whole drawing operation by itself.
+    #[inline]
+    fn touch(&mut self, rect: ScreenRect) {
+        self.mark(rect);
+        self.finish_drawing();
+    }
+
+    // Whether anything was drawn since the last `take_dirty`. A new display
+    // starts out dirty, so that it gets drawn at least once.
+    #[inline]
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.is_some()
+    }
+
+    // The area drawn to since the last `take_dirty`, in the pixels of the
+    // buffer (`SCREEN_DIMENSIONS`) rather than of the current resolution.
+    #[inline]
+    pub fn dirty_rect(&self) -> Option<ScreenRect> {
+        self.dirty
+    }
+
+    #[inline]
+    pub fn take_dirty(&mut self) -> Option<ScreenRect> {
+        self.dirty.take()
+    }
+
+    // Takes over the contents of `other` as one more change, so the
+    // generation carries on from this display's rather than `other`'s.
+    #[inline]
+    pub fn replace(&mut self, other: Display) {
+        let generation = self.generation;
+        *self = other;
+        self.generation = generation;
+        self.touch(ScreenRect::full(SCREEN_DIMENSIONS));
+    }
+
+    // Marks the whole screen to be drawn again, e.g. after the frontend lost
+    // its copy, without counting as a change to the contents.
+    #[inline]
+    pub fn invalidate(&mut self) {
+        self.dirty = Self::all_dirty();
+    }
+
+    // Counts the drawing operations that wrote to the screen, so that several
+    // observers can each tell whether it changed since they last looked. One
+    // generation is one sprite drawn, clear, scroll, switch of resolution,
+    // `replace` or call to `write_pixel` or `write_to_screen`, however many
+    // pixels it wrote. Their contents aren't compared, so drawing a sprite and
+    // then erasing it with the same sprite makes two generations even though
+    // the screen looks as before: the same generation means the same screen,
+    // but not the other way around. It only ever goes up, also when the
+    // display is `replace`d, like on loading a state.
+    #[inline]
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    #[inline]
+    pub fn dimensions(&self) -> (usize, usize) {
+        if self.is_high_res() {
+            HIGHRES_SCREEN_DIMENSIONS
+        } else {
+            LOWRES_SCREEN_DIMENSIONS
+        }
+    }
+
+    #[inline]
+    pub fn is_high_res(&self) -> bool {
+        cfg_if::cfg_if! {
+            if #[cfg(any(feature = "super-chip", feature = "xo-chip"))] {
+                self.high_res
+            } else {
+                false
+            }
         }
     }
 
     #[cfg(not(feature = "xo-chip"))]
-    pub fn write_pixel_unchecked(&mut self, x: u8, y: u8) -> bool {
+    fn toggle_pixel(&mut self, x: u8, y: u8) -> bool {
         let x = x as usize;
         let y = y as usize;
         let pos = x + y * SCREEN_WIDTH;
         let toggle = self.buffer[pos];
         self.buffer[pos] = !self.buffer[pos];
+        self.mark(ScreenRect {
+            x,
+            y,
+            width: 1,
+            height: 1,
+        });
         toggle
     }
     #[cfg(feature = "xo-chip")]
-    pub fn write_pixel_unchecked(&mut self, x: u8, y: u8) -> bool {
+    fn toggle_pixel(&mut self, x: u8, y: u8) -> bool {
         let x = x as usize;
         let y = y as usize;
         let pos = x + y * SCREEN_WIDTH;
@@ -80,6 +237,21 @@ impl Display {
             toggle |= self.secondary[pos];
             self.buffer[pos] = !self.buffer[pos];
         }
+        if self.write_mask != 0 {
+            self.mark(ScreenRect {
+                x,
+                y,
+                width: 1,
+                height: 1,
+            });
+        }
+        toggle
+    }
+
+    #[inline]
+    pub fn write_pixel_unchecked(&mut self, x: u8, y: u8) -> bool {
+        let toggle = self.toggle_pixel(x, y);
+        self.finish_drawing();
         toggle
     }
 
@@ -92,14 +264,23 @@ impl Display {
         }
     }
 
+    #[inline]
+    pub fn write_to_screen(&mut self, x: u8, y: u8) -> bool {
+        let toggle = self.draw_pixel(x, y);
+        self.finish_drawing();
+        toggle
+    }
+
+    // Writes a pixel in the coordinates of the current resolution as part of
+    // a bigger drawing operation, which `finish_drawing` ends.
     cfg_if::cfg_if! {
         if #[cfg(any(feature = "super-chip", feature = "xo-chip"))] {
-            pub fn write_to_screen(&mut self, x: u8, y: u8) -> bool {
+            pub(crate) fn draw_pixel(&mut self, x: u8, y: u8) -> bool {
                 if self.high_res {
                     if x >= SCREEN_WIDTH as u8 || y >= SCREEN_HEIGHT as u8 {
                         return false;
                     }
-                    self.write_pixel_unchecked(x, y)
+                    self.toggle_pixel(x, y)
                 } else {
                     let mut toggle = false;
                     let x = x << 1;
@@ -107,17 +288,21 @@ impl Display {
                     if x >= SCREEN_WIDTH as u8 || y >= SCREEN_HEIGHT as u8 {
                         return false;
                     }
-                    toggle |= self.write_pixel_unchecked(x, y);
-                    toggle |= self.write_pixel_unchecked(x + 1, y);
-                    toggle |= self.write_pixel_unchecked(x, y + 1);
-                    toggle |= self.write_pixel_unchecked(x + 1, y + 1);
+                    toggle |= self.toggle_pixel(x, y);
+                    toggle |= self.toggle_pixel(x + 1, y);
+                    toggle |= self.toggle_pixel(x, y + 1);
+                    toggle |= self.toggle_pixel(x + 1, y + 1);
                     toggle
                 }
             }
         } else {
             #[inline]
-            pub fn write_to_screen(&mut self, x: u8, y: u8) -> bool {
-                self.write_pixel(x, y)
+            pub(crate) fn draw_pixel(&mut self, x: u8, y: u8) -> bool {
+                if x >= SCREEN_WIDTH as u8 || y >= SCREEN_HEIGHT as u8 {
+                    false
+                } else {
+                    self.toggle_pixel(x, y)
+                }
             }
         }
     }
@@ -148,46 +333,26 @@ impl Display {
     #[inline]
     pub fn clear(&mut self) {
         self.buffer.fill(false);
+        self.touch(ScreenRect::full(SCREEN_DIMENSIONS));
     }
 
-    #[cfg(all(feature = "alloc", not(feature = "xo-chip")))]
+    // The whole buffer as 0RGB colors from `COLOR_SET`, row by row. See
+    // `render::Renderer` for other formats, or to render without allocating.
+    #[cfg(feature = "alloc")]
     pub fn to_buffer(&self, scale_x: usize, scale_y: usize) -> Vec<u32> {
         if scale_x == 0 || scale_y == 0 {
             return vec![];
         }
         let mut out = Vec::with_capacity(scale_x * scale_y * SCREEN_WIDTH * SCREEN_HEIGHT);
-        for row in self.buffer.chunks(SCREEN_WIDTH) {
-            for _ in 0..scale_y {
-                out.extend(
-                    row.iter()
-                        .flat_map(|&color| vec![COLOR_SET[color as usize]; scale_x].into_iter()),
-                );
+        for y in 0..SCREEN_HEIGHT {
+            let row_start = out.len();
+            for x in 0..SCREEN_WIDTH {
+                let color = COLOR_SET[self.read_pixel_unchecked(x as u8, y as u8)];
+                out.extend(iter::repeat(color).take(scale_x));
             }
-        }
-        out
-    }
-    #[cfg(all(feature = "alloc", feature = "xo-chip"))]
-    pub fn to_buffer(&self, scale_x: usize, scale_y: usize) -> Vec<u32> {
-        if scale_x == 0 || scale_y == 0 {
-            return vec![];
-        }
-        let mut out = Vec::with_capacity(scale_x * scale_y * SCREEN_WIDTH * SCREEN_HEIGHT);
-        for row in self
-            .buffer
-            .chunks(SCREEN_WIDTH)
-            .zip(self.secondary.chunks(SCREEN_WIDTH))
-        {
-            let colors = row
-                .0
-                .iter()
-                .enumerate()
-                .map(|(i, &x)| x as usize + (row.1[i] as usize) * 2);
-            for _ in 0..scale_y {
-                out.extend(
-                    colors
-                        .clone()
-                        .flat_map(|color| vec![COLOR_SET[color]; scale_x].into_iter()),
-                );
+            let row_end = out.len();
+            for _ in 1..scale_y {
+                out.extend_from_within(row_start..row_end);
             }
         }
         out
@@ -201,19 +366,22 @@ impl Display {
         let y_neg = scroll_y < 0;
         let scroll_x = scroll_x.unsigned_abs() as usize % SCREEN_WIDTH;
         let scroll_y = scroll_y.unsigned_abs() as usize % SCREEN_HEIGHT;
+        if scroll_x != 0 || scroll_y != 0 {
+            self.touch(ScreenRect::full(SCREEN_DIMENSIONS));
+        }
         if scroll_x != 0 {
             for i in 0..SCREEN_HEIGHT {
                 let row_start = i * SCREEN_WIDTH;
                 if x_neg {
                     self.buffer.copy_within(
-                        (row_start - scroll_x)..(row_start + SCREEN_WIDTH),
+                        (row_start + scroll_x)..(row_start + SCREEN_WIDTH),
                         row_start,
                     );
                     self.buffer[(row_start + SCREEN_WIDTH - scroll_x)..(row_start + SCREEN_WIDTH)]
                         .fill(false);
                     #[cfg(feature = "xo-chip")]
                     self.secondary.copy_within(
-                        (row_start - scroll_x)..(row_start + SCREEN_WIDTH),
+                        (row_start + scroll_x)..(row_start + SCREEN_WIDTH),
                         row_start,
                     );
                     #[cfg(feature = "xo-chip")]
@@ -280,9 +448,137 @@ impl Display {
     }
 }
 
+// Accessors for the save-state format, which stores pixels in the current
+// resolution rather than in the layout of the buffer.
+impl Display {
+    // Bitplanes set for the pixel at (`x`, `y`).
+    pub(crate) fn logical_pixel(&self, x: usize, y: usize) -> u8 {
+        let scale = SCREEN_WIDTH / self.dimensions().0;
+        self.read_pixel_unchecked((x * scale) as u8, (y * scale) as u8) as u8
+    }
+
+    // Like `draw_pixel`, part of an operation that `finish_drawing` ends.
+    pub(crate) fn set_logical_pixel(&mut self, x: usize, y: usize, planes: u8) {
+        let scale = SCREEN_WIDTH / self.dimensions().0;
+        self.mark(ScreenRect {
+            x: x * scale,
+            y: y * scale,
+            width: scale,
+            height: scale,
+        });
+        for row in (y * scale)..((y + 1) * scale) {
+            for col in (x * scale)..((x + 1) * scale) {
+                let pos = col + row * SCREEN_WIDTH;
+                self.buffer[pos] = planes & 1 != 0;
+                #[cfg(feature = "xo-chip")]
+                {
+                    self.secondary[pos] = planes & 2 != 0;
+                }
+            }
+        }
+    }
+
+    // Returns false if high resolution isn't supported by this build.
+    pub(crate) fn set_high_res(&mut self, high_res: bool) -> bool {
+        cfg_if::cfg_if! {
+            if #[cfg(any(feature = "super-chip", feature = "xo-chip"))] {
+                if self.high_res != high_res {
+                    self.high_res = high_res;
+                    self.touch(ScreenRect::full(SCREEN_DIMENSIONS));
+                }
+                true
+            } else {
+                !high_res
+            }
+        }
+    }
+
+    #[inline]
+    pub(crate) fn planes(&self) -> u8 {
+        if cfg!(feature = "xo-chip") {
+            2
+        } else {
+            1
+        }
+    }
+
+    #[inline]
+    pub(crate) fn write_mask(&self) -> u8 {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "xo-chip")] {
+                self.write_mask
+            } else {
+                1
+            }
+        }
+    }
+
+    #[inline]
+    pub(crate) fn set_write_mask(&mut self, _mask: u8) {
+        #[cfg(feature = "xo-chip")]
+        {
+            self.write_mask = _mask & 0x3;
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Display {
+    // Unversioned encoding for in-memory history, one byte per pixel so
+    // consecutive frames line up byte-for-byte.
+    pub(crate) fn write_raw(&self, out: &mut Vec<u8>) {
+        out.push(self.is_high_res() as u8);
+        #[cfg(feature = "xo-chip")]
+        out.push(self.write_mask);
+        out.extend(self.buffer.iter().map(|&px| px as u8));
+        #[cfg(feature = "xo-chip")]
+        out.extend(self.secondary.iter().map(|&px| px as u8));
+    }
+
+    pub(crate) fn read_raw(bytes: &mut &[u8]) -> Option<Display> {
+        let mut display = Display::new();
+        let _high_res = take(bytes, 1)?[0] != 0;
+        #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
+        {
+            display.high_res = _high_res;
+        }
+        #[cfg(feature = "xo-chip")]
+        {
+            display.write_mask = take(bytes, 1)?[0];
+        }
+        let pixels = take(bytes, SCREEN_WIDTH * SCREEN_HEIGHT)?;
+        for (px, &byte) in display.buffer.iter_mut().zip(pixels) {
+            *px = byte != 0;
+        }
+        #[cfg(feature = "xo-chip")]
+        {
+            let pixels = take(bytes, SCREEN_WIDTH * SCREEN_HEIGHT)?;
+            for (px, &byte) in display.secondary.iter_mut().zip(pixels) {
+                *px = byte != 0;
+            }
+        }
+        Some(display)
+    }
+}
+
 impl Default for Display {
     #[inline]
     fn default() -> Self {
         Self::new()
     }
 }
+
+// Leaves out the dirty area and generation, which track how the contents
+// changed rather than being part of them.
+impl fmt::Debug for Display {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("Display");
+        s.field("buffer", &self.buffer);
+        #[cfg(feature = "xo-chip")]
+        s.field("secondary", &self.secondary)
+            .field("write_mask", &self.write_mask);
+        #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
+        s.field("high_res", &self.high_res);
+        s.finish_non_exhaustive()
+    }
+}
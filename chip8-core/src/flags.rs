@@ -0,0 +1,39 @@
+#[cfg(all(feature = "alloc", any(feature = "super-chip", feature = "xo-chip")))]
+use alloc::boxed::Box;
+#[cfg(all(feature = "alloc", any(feature = "super-chip", feature = "xo-chip")))]
+use core::fmt;
+
+// XO-CHIP has sixteen flag registers. SUPER-CHIP only has the HP-48's eight
+// RPL user flags, so only the first eight are used in that mode.
+pub const FLAG_COUNT: usize = 16;
+
+// Where `FX75` saves the flag registers and `FX85` loads them from, so that
+// they outlive the CPU the way the HP-48's flags survive between programs.
+// Games use them for things like high scores.
+pub trait FlagStorage: Send {
+    // The last flags saved, or None if nothing has been saved yet.
+    fn load(&mut self) -> Option<[u8; FLAG_COUNT]>;
+    fn save(&mut self, flags: &[u8; FLAG_COUNT]);
+}
+
+// The storage attached to a CPU. Clones of the CPU start without one and
+// keep their flags in memory only, so they can't overwrite what the original
+// saved.
+#[cfg(all(feature = "alloc", any(feature = "super-chip", feature = "xo-chip")))]
+#[derive(Default)]
+pub(crate) struct FlagStorageSlot(pub(crate) Option<Box<dyn FlagStorage>>);
+
+#[cfg(all(feature = "alloc", any(feature = "super-chip", feature = "xo-chip")))]
+impl Clone for FlagStorageSlot {
+    #[inline]
+    fn clone(&self) -> Self {
+        FlagStorageSlot(None)
+    }
+}
+
+#[cfg(all(feature = "alloc", any(feature = "super-chip", feature = "xo-chip")))]
+impl fmt::Debug for FlagStorageSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.0.is_some() { "attached" } else { "none" })
+    }
+}
@@ -1,7 +1,218 @@
+use core::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// 16 glyphs, 5 bytes each
+pub const SMALL_FONT_LEN: usize = 80;
+// 16 glyphs, 10 bytes each
+pub const BIG_FONT_LEN: usize = 160;
+
+// Fonts shipped with historical interpreters. Those that had no big font use
+// Octo's.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FontPreset {
+    // the Jaxe emulator's, used by default
+    Jaxe,
+    CosmacVip,
+    Dream6800,
+    Eti660,
+    Chip48,
+    // only decimal digits in the big font
+    SuperChip11,
+    Octo,
+}
+
+impl FontPreset {
+    pub const ALL: [FontPreset; 7] = [
+        Self::Jaxe,
+        Self::CosmacVip,
+        Self::Dream6800,
+        Self::Eti660,
+        Self::Chip48,
+        Self::SuperChip11,
+        Self::Octo,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Jaxe => "jaxe",
+            Self::CosmacVip => "vip",
+            Self::Dream6800 => "dream6800",
+            Self::Eti660 => "eti660",
+            Self::Chip48 => "chip48",
+            Self::SuperChip11 => "schip",
+            Self::Octo => "octo",
+        }
+    }
+}
+
+impl Default for FontPreset {
+    #[inline]
+    fn default() -> Self {
+        Self::Jaxe
+    }
+}
+
+impl fmt::Display for FontPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FontPresetParseError;
+impl fmt::Display for FontPresetParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected one of ")?;
+        for (idx, preset) in FontPreset::ALL.iter().enumerate() {
+            let sep = if idx == 0 { "" } else { ", " };
+            write!(f, "{}`{}`", sep, preset)?;
+        }
+        Ok(())
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for FontPresetParseError {}
+
+impl core::str::FromStr for FontPreset {
+    type Err = FontPresetParseError;
+    fn from_str(s: &str) -> Result<Self, FontPresetParseError> {
+        let s = s.trim();
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|preset| preset.name().eq_ignore_ascii_case(s))
+            .ok_or(FontPresetParseError)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FontError {
+    // a font doesn't fit below 0x200, where programs are loaded
+    OutOfRange,
+    // the small and big fonts overlap
+    Overlap,
+}
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfRange => write!(f, "fonts must fit below address 0x200"),
+            Self::Overlap => write!(f, "the small and big fonts overlap"),
+        }
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for FontError {}
+
+// The glyphs `FX29` and `FX30` point to and where they're loaded in memory.
+// Unused glyphs are left blank.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Font {
+    pub small: [u8; SMALL_FONT_LEN],
+    pub big: [u8; BIG_FONT_LEN],
+    pub small_addr: u16,
+    pub big_addr: u16,
+}
+
+impl Font {
+    pub const DEFAULT_SMALL_ADDR: u16 = 0x50;
+    pub const DEFAULT_BIG_ADDR: u16 = 0xA0;
+
+    pub fn preset(preset: FontPreset) -> Font {
+        let (small, big) = match preset {
+            FontPreset::Jaxe => (JAXE_SMALL, JAXE_BIG),
+            FontPreset::CosmacVip => (COSMAC_SMALL, OCTO_BIG),
+            FontPreset::Dream6800 => (DREAM_6800_SMALL, OCTO_BIG),
+            FontPreset::Eti660 => (ETI_660_SMALL, OCTO_BIG),
+            FontPreset::Chip48 => (CHIP_48_SMALL, OCTO_BIG),
+            FontPreset::SuperChip11 => (CHIP_48_SMALL, SUPER_CHIP_BIG),
+            FontPreset::Octo => (JAXE_SMALL, OCTO_BIG),
+        };
+        Font {
+            small,
+            big,
+            small_addr: Self::DEFAULT_SMALL_ADDR,
+            big_addr: Self::DEFAULT_BIG_ADDR,
+        }
+    }
+
+    // Address of the small glyph for `digit`, as set by `FX29`.
+    #[inline]
+    pub fn small_glyph(&self, digit: u8) -> u16 {
+        self.small_addr + (digit & 0xF) as u16 * 5
+    }
+
+    // Address of the big glyph for `digit`, as set by `FX30`.
+    #[inline]
+    pub fn big_glyph(&self, digit: u8) -> u16 {
+        self.big_addr + (digit & 0xF) as u16 * 10
+    }
+
+    pub fn validate(&self) -> Result<(), FontError> {
+        let small = self.small_addr as usize..self.small_addr as usize + SMALL_FONT_LEN;
+        let big = self.big_addr as usize..self.big_addr as usize + BIG_FONT_LEN;
+        if small.end > 0x200 || (HAS_BIG_FONT && big.end > 0x200) {
+            Err(FontError::OutOfRange)
+        } else if HAS_BIG_FONT && small.start < big.end && big.start < small.end {
+            Err(FontError::Overlap)
+        } else {
+            Ok(())
+        }
+    }
+
+    // Copies the glyphs into memory at their addresses.
+    pub(crate) fn load_into(&self, memory: &mut [u8]) {
+        let small = self.small_addr as usize;
+        memory[small..small + SMALL_FONT_LEN].copy_from_slice(&self.small);
+        if HAS_BIG_FONT {
+            let big = self.big_addr as usize;
+            memory[big..big + BIG_FONT_LEN].copy_from_slice(&self.big);
+        }
+    }
+
+    pub(crate) fn clear_from(&self, memory: &mut [u8]) {
+        let small = self.small_addr as usize;
+        memory[small..small + SMALL_FONT_LEN].fill(0);
+        if HAS_BIG_FONT {
+            let big = self.big_addr as usize;
+            memory[big..big + BIG_FONT_LEN].fill(0);
+        }
+    }
+}
+
+// only SUPER-CHIP and XO-CHIP have `FX30`, so other builds don't load a big
+// font
+const HAS_BIG_FONT: bool = cfg!(any(feature = "super-chip", feature = "xo-chip"));
+
+impl Default for Font {
+    #[inline]
+    fn default() -> Self {
+        Self::preset(FontPreset::default())
+    }
+}
+
+impl From<FontPreset> for Font {
+    #[inline]
+    fn from(preset: FontPreset) -> Self {
+        Self::preset(preset)
+    }
+}
+
+// The glyphs are left out to keep CPU dumps readable.
+impl fmt::Debug for Font {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Font")
+            .field("small_addr", &self.small_addr)
+            .field("big_addr", &self.big_addr)
+            .finish_non_exhaustive()
+    }
+}
+
 // fonts taken from the Jaxe emulator, released under the MIT license:
 // https://github.com/kurtjd/jaxe/
-
-pub(crate) const FONT_SET: [u8; 80] = [
+const JAXE_SMALL: [u8; SMALL_FONT_LEN] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
     0x20, 0x60, 0x20, 0x20, 0x70, // 1
     0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
@@ -19,12 +230,8 @@ pub(crate) const FONT_SET: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
-pub(crate) const SPRITE_ADDR: [u16; 16] = [
-    0x50, 0x55, 0x5A, 0x5F, 0x64, 0x69, 0x6E, 0x73, 0x78, 0x7D, 0x82, 0x87, 0x8C, 0x91, 0x96, 0x9B,
-];
 
-#[cfg(any(feature = "super-chip", feature = "xo-chip"))]
-pub(crate) const BIG_FONT_SET: [u8; 160] = [
+const JAXE_BIG: [u8; BIG_FONT_LEN] = [
     0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
     0x0C, 0x0C, 0x3C, 0x3C, 0x0C, 0x0C, 0x0C, 0x0C, 0x3F, 0x3F, // 1
     0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
@@ -44,8 +251,121 @@ pub(crate) const BIG_FONT_SET: [u8; 160] = [
     0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
     0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
 ];
-#[cfg(any(feature = "super-chip", feature = "xo-chip"))]
-pub(crate) const BIG_SPRITE_ADDR: [u16; 16] = [
-    0xA0, 0xAA, 0xB4, 0xBE, 0xC8, 0xD2, 0xDC, 0xE6, 0xF0, 0xFA, 0x104, 0x10E, 0x118, 0x122, 0x12C,
-    0x136,
+
+// the rest are transcribed from the font sets in Octo, released under the MIT
+// license: https://github.com/JohnEarnest/Octo/
+const COSMAC_SMALL: [u8; SMALL_FONT_LEN] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+const DREAM_6800_SMALL: [u8; SMALL_FONT_LEN] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x40, 0x40, 0x40, 0x40, 0x40, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+const ETI_660_SMALL: [u8; SMALL_FONT_LEN] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x20, 0x20, 0x20, 0x20, 0x20, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0xA0, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0x80, 0x80, 0xE0, 0xA0, 0xE0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0x20, 0x20, 0xE0, 0xA0, 0xE0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+// CHIP-48 and SUPER-CHIP's three pixel wide digits
+const CHIP_48_SMALL: [u8; SMALL_FONT_LEN] = [
+    0x60, 0xA0, 0xA0, 0xA0, 0xC0, // 0
+    0x40, 0xC0, 0x40, 0x40, 0xE0, // 1
+    0xC0, 0x20, 0x40, 0x80, 0xE0, // 2
+    0xC0, 0x20, 0x40, 0x20, 0xC0, // 3
+    0x20, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xC0, 0x20, 0xC0, // 5
+    0x40, 0x80, 0xC0, 0xA0, 0x40, // 6
+    0xE0, 0x20, 0x60, 0x40, 0x40, // 7
+    0x40, 0xA0, 0x40, 0xA0, 0x40, // 8
+    0x40, 0xA0, 0x60, 0x20, 0x40, // 9
+    0x40, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xC0, 0xA0, 0xC0, // B
+    0x60, 0x80, 0x80, 0x80, 0x60, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xC0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+// SUPER-CHIP 1.1 only has the decimal digits
+const SUPER_CHIP_BIG: [u8; BIG_FONT_LEN] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // A
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // B
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // C
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // D
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // E
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // F
+];
+
+const OCTO_BIG: [u8; BIG_FONT_LEN] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
 ];
@@ -0,0 +1,92 @@
+use crate::timing::NANOS_PER_SEC;
+
+use alloc::collections::VecDeque;
+
+#[derive(Clone, Copy, Debug)]
+struct QueuedKey {
+    at: u64,
+    key: u8,
+    pressed: bool,
+    // a release put off to keep its press visible for a frame
+    deferred: bool,
+}
+
+// Key presses and releases waiting for their time to come. Times are in the
+// clock units `Budget` counts in, since the queue was created.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct KeyQueue {
+    now: u64,
+    // ordered by time, and otherwise by when they were queued
+    events: VecDeque<QueuedKey>,
+    // when each key was last pressed through the queue
+    pressed_at: [Option<u64>; 16],
+}
+
+impl KeyQueue {
+    pub(crate) fn push(&mut self, offset: u64, key: u8, pressed: bool) {
+        self.insert(QueuedKey {
+            at: self.now.saturating_add(offset),
+            key,
+            pressed,
+            deferred: false,
+        });
+    }
+
+    fn insert(&mut self, event: QueuedKey) {
+        let idx = self.events.partition_point(|e| e.at <= event.at);
+        self.events.insert(idx, event);
+    }
+
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    #[inline]
+    pub(crate) fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    // Clock units until the next event, which is never 0 once the due events
+    // have been taken with `pop_due`.
+    #[inline]
+    pub(crate) fn until_next(&self) -> Option<u64> {
+        self.events
+            .front()
+            .map(|event| event.at.saturating_sub(self.now))
+    }
+
+    #[inline]
+    pub(crate) fn advance(&mut self, units: u64) {
+        self.now = self.now.saturating_add(units);
+    }
+
+    // Takes the next event that's due, as a key and whether it was pressed.
+    // With `hold_taps`, a release less than a frame after its press is put off
+    // until a frame has passed, unless the key is pressed again first.
+    pub(crate) fn pop_due(&mut self, hold_taps: bool) -> Option<(u8, bool)> {
+        loop {
+            let event = *self.events.front().filter(|event| event.at <= self.now)?;
+            self.events.pop_front();
+            let key = event.key as usize;
+            if event.pressed {
+                self.pressed_at[key] = Some(self.now);
+                self.events
+                    .retain(|other| !(other.deferred && other.key == event.key));
+                return Some((event.key, true));
+            }
+            let held_until = self.pressed_at[key].map(|at| at.saturating_add(NANOS_PER_SEC));
+            match held_until {
+                Some(until) if hold_taps && until > self.now => self.insert(QueuedKey {
+                    at: until,
+                    deferred: true,
+                    ..event
+                }),
+                _ => {
+                    self.pressed_at[key] = None;
+                    return Some((event.key, false));
+                }
+            }
+        }
+    }
+}
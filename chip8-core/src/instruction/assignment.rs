@@ -1,130 +1,137 @@
-use crate::CPU;
+use crate::quirks::IndexIncrement;
+#[cfg(feature = "super-chip")]
+use crate::Chip8Mode;
+use crate::{Error, CPU};
+
+#[inline]
+fn increment_index(cpu: &mut CPU, count: u8) {
+    let offset = match cpu.quirks.load_store {
+        IndexIncrement::Unchanged => return,
+        IndexIncrement::ByX => count - 1,
+        IndexIncrement::ByXPlusOne => count,
+    };
+    cpu.advance_index(offset as u16);
+}
 
-pub(super) fn inst_set_register(cpu: &mut CPU, inst: u16) -> u32 {
+pub(super) fn inst_set_register(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
     let reg = (((inst >> 8) & 0xF) as u8).try_into().unwrap();
     let value = (inst & 0xFF) as u8;
     cpu.registers[reg] = value;
-    0
+    Ok(0)
 }
 
-pub(super) fn inst_set_index(cpu: &mut CPU, inst: u16) -> u32 {
+pub(super) fn inst_set_index(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
     cpu.index = inst & 0xFFF;
-    0
+    Ok(0)
 }
 
 #[cfg(feature = "xo-chip")]
-pub(super) fn inst_set_long_index(cpu: &mut CPU, _: u16) -> u32 {
+pub(super) fn inst_set_long_index(cpu: &mut CPU, _: u16) -> Result<u32, Error> {
     // Currently, PC is bumped before calling the instruction.
-    cpu.index = cpu.read_memory_word(cpu.pc).unwrap();
+    cpu.index = cpu
+        .read_memory_word(cpu.pc)
+        .map_err(|_| Error::MemoryFault(cpu.pc - 2, cpu.pc as u32))?;
     cpu.pc += 2;
-    0
-}
-
-#[cfg(any(feature = "cosmac", feature = "xo-chip"))]
-pub(super) fn inst_reg_dump(cpu: &mut CPU, inst: u16) -> u32 {
-    let max = (((inst >> 8) & 0xF) + 1) as u8;
-    for i in 0..max {
-        let reg = i.try_into().unwrap();
-        cpu.write_memory_byte(cpu.index + i as u16, cpu.registers[reg])
-            .unwrap();
-    }
-    cpu.index = (cpu.index + max as u16) & 0xFFF;
-    0
+    Ok(0)
 }
 
-#[cfg(feature = "super-chip")]
-pub(super) fn inst_reg_dump_schip(cpu: &mut CPU, inst: u16) -> u32 {
+pub(super) fn inst_reg_dump(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
     let max = (((inst >> 8) & 0xF) + 1) as u8;
     for i in 0..max {
         let reg = i.try_into().unwrap();
-        cpu.write_memory_byte(cpu.index + i as u16, cpu.registers[reg])
-            .unwrap();
+        cpu.write_index_byte(i as u16, cpu.registers[reg])?;
     }
-    0
+    increment_index(cpu, max);
+    Ok(0)
 }
 
-#[cfg(any(feature = "cosmac", feature = "xo-chip"))]
-pub(super) fn inst_reg_load(cpu: &mut CPU, inst: u16) -> u32 {
+pub(super) fn inst_reg_load(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
     let max = (((inst >> 8) & 0xF) + 1) as u8;
     for i in 0..max {
         let reg = i.try_into().unwrap();
-        cpu.registers[reg] = cpu.read_memory_byte(cpu.index + i as u16).unwrap();
+        cpu.registers[reg] = cpu.read_index_byte(i as u16)?;
     }
-    cpu.index = (cpu.index + max as u16) & 0xFFF;
-    0
+    increment_index(cpu, max);
+    Ok(0)
 }
 
-#[cfg(feature = "super-chip")]
-pub(super) fn inst_reg_load_schip(cpu: &mut CPU, inst: u16) -> u32 {
-    let max = (((inst >> 8) & 0xF) + 1) as u8;
-    for i in 0..max {
-        let reg = i.try_into().unwrap();
-        cpu.registers[reg] = cpu.read_memory_byte(cpu.index + i as u16).unwrap();
-    }
-    0
-}
-
-pub(super) fn inst_move_register(cpu: &mut CPU, inst: u16) -> u32 {
+pub(super) fn inst_move_register(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
     let reg_x = (((inst >> 8) & 0xF) as u8).try_into().unwrap();
     let reg_y = (((inst >> 4) & 0xF) as u8).try_into().unwrap();
     cpu.registers[reg_x] = cpu.registers[reg_y];
-    0
+    Ok(0)
 }
 
-pub(super) fn inst_sprite_addr_index(cpu: &mut CPU, inst: u16) -> u32 {
+pub(super) fn inst_sprite_addr_index(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
     let reg = (((inst >> 8) & 0xF) as u8).try_into().unwrap();
     let value = cpu.registers[reg] & 0xF;
-    cpu.index = crate::font::SPRITE_ADDR[value as usize];
-    0
+    cpu.index = cpu.font().small_glyph(value);
+    Ok(0)
 }
 
 #[cfg(feature = "xo-chip")]
-pub(super) fn inst_reg_dump_xy(cpu: &mut CPU, inst: u16) -> u32 {
+pub(super) fn inst_reg_dump_xy(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
     let min = (((inst >> 8) & 0xF) + 1) as u8;
     let max = (((inst >> 4) & 0xF) + 1) as u8;
     for i in min..max {
         let reg = i.try_into().unwrap();
-        cpu.write_memory_byte(cpu.index + i as u16, cpu.registers[reg])
-            .unwrap();
+        cpu.write_index_byte(i as u16, cpu.registers[reg])?;
     }
-    0
+    Ok(0)
 }
 
 #[cfg(feature = "xo-chip")]
-pub(super) fn inst_reg_load_xy(cpu: &mut CPU, inst: u16) -> u32 {
+pub(super) fn inst_reg_load_xy(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
     let min = (((inst >> 8) & 0xF) + 1) as u8;
     let max = (((inst >> 4) & 0xF) + 1) as u8;
     for i in min..max {
         let reg = i.try_into().unwrap();
-        cpu.registers[reg] = cpu.read_memory_byte(cpu.index + i as u16).unwrap();
+        cpu.registers[reg] = cpu.read_index_byte(i as u16)?;
     }
-    0
+    Ok(0)
 }
 
+// SUPER-CHIP only has eight flag registers, so `FX75` and `FX85` never go
+// past V7 in that mode.
 #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
-pub(super) fn inst_persist_dump(cpu: &mut CPU, inst: u16) -> u32 {
-    let max = (((inst >> 8) & 0xF) + 1) as u8;
+#[cfg_attr(not(feature = "super-chip"), allow(unused_variables))]
+fn flag_count(cpu: &CPU, inst: u16) -> u8 {
+    let count = (((inst >> 8) & 0xF) + 1) as u8;
+    #[cfg(feature = "super-chip")]
+    if cpu.mode == Chip8Mode::SuperChip {
+        return count.min(8);
+    }
+    count
+}
+
+#[cfg(any(feature = "super-chip", feature = "xo-chip"))]
+pub(super) fn inst_persist_dump(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
+    let max = flag_count(cpu, inst);
+    // flags past Vx keep their stored values
+    cpu.load_flags();
     for i in 0..max {
         let reg = i.try_into().unwrap();
         cpu.persistent_registers[reg] = cpu.registers[reg];
     }
-    0
+    cpu.save_flags();
+    Ok(0)
 }
 
 #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
-pub(super) fn inst_persist_load(cpu: &mut CPU, inst: u16) -> u32 {
-    let max = (((inst >> 8) & 0xF) + 1) as u8;
+pub(super) fn inst_persist_load(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
+    let max = flag_count(cpu, inst);
+    cpu.load_flags();
     for i in 0..max {
         let reg = i.try_into().unwrap();
         cpu.registers[reg] = cpu.persistent_registers[reg];
     }
-    0
+    Ok(0)
 }
 
 #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
-pub(super) fn inst_big_sprite_addr_index(cpu: &mut CPU, inst: u16) -> u32 {
+pub(super) fn inst_big_sprite_addr_index(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
     let reg = (((inst >> 8) & 0xF) as u8).try_into().unwrap();
     let value = cpu.registers[reg] & 0xF;
-    cpu.index = crate::font::BIG_SPRITE_ADDR[value as usize];
-    0
+    cpu.index = cpu.font().big_glyph(value);
+    Ok(0)
 }
@@ -1,4 +1,4 @@
-use crate::CPU;
+use crate::{Error, CPU};
 
 #[cfg(feature = "xo-chip")]
 #[inline]
@@ -15,38 +15,38 @@ fn skip_instruction(cpu: &mut CPU) {
     cpu.pc += 2;
 }
 
-pub(super) fn inst_if_equal(cpu: &mut CPU, inst: u16) -> u32 {
+pub(super) fn inst_if_equal(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
     let reg = (((inst >> 8) & 0xF) as u8).try_into().unwrap();
     let value = (inst & 0xFF) as u8;
     if cpu.registers[reg] == value {
         skip_instruction(cpu);
     }
-    0
+    Ok(0)
 }
 
-pub(super) fn inst_if_inequal(cpu: &mut CPU, inst: u16) -> u32 {
+pub(super) fn inst_if_inequal(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
     let reg = (((inst >> 8) & 0xF) as u8).try_into().unwrap();
     let value = (inst & 0xFF) as u8;
     if cpu.registers[reg] != value {
         skip_instruction(cpu);
     }
-    0
+    Ok(0)
 }
 
-pub(super) fn inst_if_equal_register(cpu: &mut CPU, inst: u16) -> u32 {
+pub(super) fn inst_if_equal_register(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
     let reg_x = (((inst >> 8) & 0xF) as u8).try_into().unwrap();
     let reg_y = (((inst >> 4) & 0xF) as u8).try_into().unwrap();
     if cpu.registers[reg_x] == cpu.registers[reg_y] {
         skip_instruction(cpu);
     }
-    0
+    Ok(0)
 }
 
-pub(super) fn inst_if_inequal_register(cpu: &mut CPU, inst: u16) -> u32 {
+pub(super) fn inst_if_inequal_register(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
     let reg_x = (((inst >> 8) & 0xF) as u8).try_into().unwrap();
     let reg_y = (((inst >> 4) & 0xF) as u8).try_into().unwrap();
     if cpu.registers[reg_x] != cpu.registers[reg_y] {
         skip_instruction(cpu);
     }
-    0
+    Ok(0)
 }
@@ -1,120 +1,142 @@
-use crate::display;
+use crate::display::ScreenRect;
 use crate::Register;
-use crate::CPU;
+use crate::{Error, CPU};
 
-#[cfg(feature = "cosmac")]
-pub(super) fn inst_draw_cosmac(cpu: &mut CPU, inst: u16) -> u32 {
-    cpu.vblank_wait = true;
-    let reg_x = (((inst >> 8) & 0xF) as u8).try_into().unwrap();
-    let reg_y = (((inst >> 4) & 0xF) as u8).try_into().unwrap();
-    let x = cpu.registers[reg_x] % display::LOWRES_SCREEN_WIDTH as u8;
-    let y = cpu.registers[reg_y] % display::LOWRES_SCREEN_HEIGHT as u8;
-    let n = (inst & 0xF) as u8;
-    let mut flag = false;
-    for (i, idx) in (0..n).zip(cpu.index..) {
-        let byte = cpu.read_memory_byte(idx).unwrap();
-        for bit in 0..8 {
-            if (byte << bit) & 128 == 128 {
-                flag |= cpu.screen.write_to_screen(x + bit, y + i);
-            }
-        }
+fn draw_sprite(cpu: &mut CPU, inst: u16, rows: u8, wide: bool) -> Result<(), Error> {
+    if cpu.quirks.display_wait {
+        cpu.vblank_wait = true;
     }
-    cpu.registers[Register::VF] = if flag { 1 } else { 0 };
-    0
-}
-#[cfg(feature = "super-chip")]
-pub(super) fn inst_draw_schip(cpu: &mut CPU, inst: u16) -> u32 {
-    let dimensions = if cpu.screen.high_res {
-        display::HIGHRES_SCREEN_DIMENSIONS
-    } else {
-        display::LOWRES_SCREEN_DIMENSIONS
-    };
+    let (width, height) = cpu.screen.dimensions();
     let reg_x = (((inst >> 8) & 0xF) as u8).try_into().unwrap();
     let reg_y = (((inst >> 4) & 0xF) as u8).try_into().unwrap();
-    let x = cpu.registers[reg_x] % dimensions.0 as u8;
-    let y = cpu.registers[reg_y] % dimensions.1 as u8;
-    let n = (inst & 0xF) as u8;
-    let mut flag = false;
-    for (i, idx) in (0..n).zip(cpu.index..) {
-        let byte = cpu.read_memory_byte(idx).unwrap();
-        for bit in 0..8 {
-            if (byte << bit) & 128 == 128 {
-                flag |= cpu.screen.write_to_screen(x + bit, y + i);
+    let x = cpu.registers[reg_x] % width as u8;
+    let y = cpu.registers[reg_y] % height as u8;
+    let row_bytes = if wide { 2 } else { 1 };
+    // a sprite that wraps around an edge may touch that whole axis
+    let span = |start: usize, len: usize, size: usize| match start + len {
+        end if end <= size => (start, len),
+        _ if cpu.quirks.wrap => (0, size),
+        _ => (start, size - start),
+    };
+    let (rect_x, rect_width) = span(x as usize, row_bytes * 8, width);
+    let (rect_y, rect_height) = span(y as usize, rows as usize, height);
+    if rows > 0 {
+        cpu.effects.add_screen(ScreenRect {
+            x: rect_x,
+            y: rect_y,
+            width: rect_width,
+            height: rect_height,
+        });
+    }
+    let drawn = (|| -> Result<(u8, u8), Error> {
+        let mut collided_rows = 0;
+        let mut clipped_rows = 0;
+        for (i, offset) in (0..rows).zip((0..).step_by(row_bytes)) {
+            // left-align the row so both sprite widths are drawn the same way
+            let row = if wide {
+                (cpu.read_index_byte(offset)? as u16) << 8 | cpu.read_index_byte(offset + 1)? as u16
+            } else {
+                (cpu.read_index_byte(offset)? as u16) << 8
+            };
+            if !cpu.quirks.wrap && (y + i) as usize >= height {
+                clipped_rows += 1;
+                continue;
+            }
+            let mut collided = false;
+            for bit in 0..(row_bytes as u8 * 8) {
+                if (row << bit) & 0x8000 == 0x8000 {
+                    collided |= if cpu.quirks.wrap {
+                        cpu.screen
+                            .draw_pixel((x + bit) % width as u8, (y + i) % height as u8)
+                    } else {
+                        cpu.screen.draw_pixel(x + bit, y + i)
+                    };
+                }
+            }
+            if collided {
+                collided_rows += 1;
             }
         }
-    }
-    cpu.registers[Register::VF] = if flag { 1 } else { 0 };
-    0
-}
-#[cfg(feature = "xo-chip")]
-pub(super) fn inst_draw_xochip(cpu: &mut CPU, inst: u16) -> u32 {
-    let dimensions = if cpu.screen.high_res {
-        display::HIGHRES_SCREEN_DIMENSIONS
+        Ok((collided_rows, clipped_rows))
+    })();
+    // the whole sprite is one change to the screen, even the rows drawn
+    // before a fault
+    cpu.screen.finish_drawing();
+    let (collided_rows, clipped_rows) = drawn?;
+    cpu.registers[Register::VF] = if cpu.quirks.collision_rows && cpu.screen.is_high_res() {
+        collided_rows + clipped_rows
+    } else if collided_rows > 0 {
+        1
     } else {
-        display::LOWRES_SCREEN_DIMENSIONS
+        0
     };
-    let reg_x = (((inst >> 8) & 0xF) as u8).try_into().unwrap();
-    let reg_y = (((inst >> 4) & 0xF) as u8).try_into().unwrap();
-    let x = cpu.registers[reg_x] % dimensions.0 as u8;
-    let y = cpu.registers[reg_y] % dimensions.1 as u8;
-    let n = (inst & 0xF) as u8;
-    let mut flag = false;
-    for (i, idx) in (0..n).zip(cpu.index..) {
-        let byte = cpu.read_memory_byte(idx).unwrap();
-        for bit in 0..8 {
-            if (byte << bit) & 128 == 128 {
-                flag |= cpu
-                    .screen
-                    .write_to_screen((x + bit) % dimensions.0 as u8, (y + i) % dimensions.1 as u8);
-            }
-        }
-    }
-    cpu.registers[Register::VF] = if flag { 1 } else { 0 };
-    0
+    Ok(())
+}
+
+pub(super) fn inst_draw(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
+    draw_sprite(cpu, inst, (inst & 0xF) as u8, false)?;
+    Ok(0)
+}
+
+#[cfg(any(feature = "super-chip", feature = "xo-chip"))]
+pub(super) fn inst_draw_big(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
+    // SUPER-CHIP 1.1 draws an 8x16 sprite in low resolution
+    let wide = cpu.screen.is_high_res() || !cpu.quirks.lores_tall_sprite;
+    draw_sprite(cpu, inst, 16, wide)?;
+    Ok(0)
 }
 
-pub(super) fn inst_clear(cpu: &mut CPU, _: u16) -> u32 {
+pub(super) fn inst_clear(cpu: &mut CPU, _: u16) -> Result<u32, Error> {
     cpu.screen.clear();
-    0
+    cpu.effects
+        .add_screen(ScreenRect::full(cpu.screen.dimensions()));
+    Ok(0)
 }
 
 cfg_if::cfg_if! {
     if #[cfg(any(feature = "super-chip", feature = "xo-chip"))] {
-        pub(super) fn inst_low_res(cpu: &mut CPU, _: u16) -> u32 {
-            cpu.screen.high_res = false;
-            0
+        pub(super) fn inst_low_res(cpu: &mut CPU, _: u16) -> Result<u32, Error> {
+            cpu.screen.set_high_res(false);
+            cpu.effects.add_screen(ScreenRect::full(cpu.screen.dimensions()));
+            Ok(0)
         }
-        pub(super) fn inst_high_res(cpu: &mut CPU, _: u16) -> u32 {
-            cpu.screen.high_res = true;
-            0
+        pub(super) fn inst_high_res(cpu: &mut CPU, _: u16) -> Result<u32, Error> {
+            cpu.screen.set_high_res(true);
+            cpu.effects.add_screen(ScreenRect::full(cpu.screen.dimensions()));
+            Ok(0)
         }
 
-        pub(super) fn inst_scroll_down(cpu: &mut CPU, inst: u16) -> u32 {
+        pub(super) fn inst_scroll_down(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
             let n = (inst & 0xF) as i8;
             cpu.screen.scroll(0, -if cpu.screen.high_res { n } else { n / 2 });
-            0
+            cpu.effects.add_screen(ScreenRect::full(cpu.screen.dimensions()));
+            Ok(0)
         }
-        pub(super) fn inst_scroll_right(cpu: &mut CPU, _: u16) -> u32 {
+        pub(super) fn inst_scroll_right(cpu: &mut CPU, _: u16) -> Result<u32, Error> {
             cpu.screen.scroll(if cpu.screen.high_res { 4 } else { 2 }, 0);
-            0
+            cpu.effects.add_screen(ScreenRect::full(cpu.screen.dimensions()));
+            Ok(0)
         }
-        pub(super) fn inst_scroll_left(cpu: &mut CPU, _: u16) -> u32 {
+        pub(super) fn inst_scroll_left(cpu: &mut CPU, _: u16) -> Result<u32, Error> {
             cpu.screen.scroll(if cpu.screen.high_res { -4 } else { -2 }, 0);
-            0
+            cpu.effects.add_screen(ScreenRect::full(cpu.screen.dimensions()));
+            Ok(0)
         }
     }
 }
 
 #[cfg(feature = "xo-chip")]
-pub(super) fn inst_scroll_up(cpu: &mut CPU, inst: u16) -> u32 {
+pub(super) fn inst_scroll_up(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
     let n = (inst & 0xF) as i8;
     cpu.screen
         .scroll(0, if cpu.screen.high_res { n } else { n / 2 });
-    0
+    cpu.effects
+        .add_screen(ScreenRect::full(cpu.screen.dimensions()));
+    Ok(0)
 }
 
 #[cfg(feature = "xo-chip")]
-pub(super) fn inst_set_bitplane(cpu: &mut CPU, inst: u16) -> u32 {
+pub(super) fn inst_set_bitplane(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
     cpu.screen.write_mask = ((inst >> 8) & 0x3) as u8;
-    0
+    Ok(0)
 }
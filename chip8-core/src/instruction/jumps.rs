@@ -1,31 +1,27 @@
-use crate::CPU;
+use crate::{Error, CPU};
 
-pub(super) fn inst_goto(cpu: &mut CPU, inst: u16) -> u32 {
+pub(super) fn inst_goto(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
     cpu.pc = inst & 0xFFF;
-    0
+    Ok(0)
 }
 
-pub(super) fn inst_call(cpu: &mut CPU, inst: u16) -> u32 {
-    cpu.call_routine(inst & 0xFFF);
-    0
+pub(super) fn inst_call(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
+    cpu.call_routine(inst & 0xFFF)?;
+    Ok(0)
 }
 
-pub(super) fn inst_return(cpu: &mut CPU, _: u16) -> u32 {
-    cpu.return_routine();
-    0
+pub(super) fn inst_return(cpu: &mut CPU, _: u16) -> Result<u32, Error> {
+    cpu.return_routine()?;
+    Ok(0)
 }
 
-#[cfg(any(feature = "cosmac", feature = "xo-chip"))]
-pub(super) fn inst_jump_v0(cpu: &mut CPU, inst: u16) -> u32 {
+pub(super) fn inst_jump_v0(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
     let base = inst & 0xFFF;
-    cpu.pc = base + cpu.registers[crate::Register::V0] as u16;
-    0
-}
-
-#[cfg(feature = "super-chip")]
-pub(super) fn inst_jump_v0_schip(cpu: &mut CPU, inst: u16) -> u32 {
-    let base = inst & 0xFFF;
-    let reg = (((inst >> 8) & 0xF) as u8).try_into().unwrap();
+    let reg = if cpu.quirks.jump {
+        (((inst >> 8) & 0xF) as u8).try_into().unwrap()
+    } else {
+        crate::Register::V0
+    };
     cpu.pc = base + cpu.registers[reg] as u16;
-    0
+    Ok(0)
 }
@@ -1,43 +1,45 @@
-use crate::CPU;
+use crate::{Error, CPU};
 
-pub(super) fn inst_bcd(cpu: &mut CPU, inst: u16) -> u32 {
+pub(super) fn inst_bcd(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
     let reg = (((inst >> 8) & 0xF) as u8).try_into().unwrap();
     let value = cpu.registers[reg];
-    cpu.write_memory_byte(cpu.index, value / 100).unwrap();
-    cpu.write_memory_byte(cpu.index + 1, (value / 10) % 10)
-        .unwrap();
-    cpu.write_memory_byte(cpu.index + 2, value % 10).unwrap();
-    0
+    cpu.write_index_byte(0, value / 100)?;
+    cpu.write_index_byte(1, (value / 10) % 10)?;
+    cpu.write_index_byte(2, value % 10)?;
+    Ok(0)
 }
 
-pub(super) fn inst_random(cpu: &mut CPU, inst: u16) -> u32 {
+pub(super) fn inst_random(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
     let reg = (((inst >> 8) & 0xF) as u8).try_into().unwrap();
     let and = (inst & 0xFF) as u8;
     cpu.registers[reg] = cpu.random() & and;
-    0
+    Ok(0)
 }
 
-pub(super) fn inst_nop(_: &mut CPU, _: u16) -> u32 {
-    0
+pub(super) fn inst_nop(_: &mut CPU, _: u16) -> Result<u32, Error> {
+    Ok(0)
 }
 
 #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
-pub(super) fn inst_exit(cpu: &mut CPU, _: u16) -> u32 {
+pub(super) fn inst_exit(cpu: &mut CPU, _: u16) -> Result<u32, Error> {
     cpu.exited = true;
-    0
+    Ok(0)
 }
 
 #[cfg(feature = "xo-chip")]
-pub(super) fn inst_set_audio_buffer(cpu: &mut CPU, _: u16) -> u32 {
-    let buf = &cpu.memory[(cpu.index as usize)..];
-    cpu.audio.write_pattern(buf);
-    0
+pub(super) fn inst_set_audio_buffer(cpu: &mut CPU, _: u16) -> Result<u32, Error> {
+    let mut buf = [0; 16];
+    for (offset, byte) in (0..).zip(buf.iter_mut()) {
+        *byte = cpu.read_index_byte(offset)?;
+    }
+    cpu.audio.write_pattern(&buf);
+    Ok(0)
 }
 
 #[cfg(feature = "xo-chip")]
-pub(super) fn inst_set_audio_pitch(cpu: &mut CPU, inst: u16) -> u32 {
+pub(super) fn inst_set_audio_pitch(cpu: &mut CPU, inst: u16) -> Result<u32, Error> {
     let reg = (((inst >> 8) & 0xF) as u8).try_into().unwrap();
     let pitch = cpu.registers[reg] as f32;
     cpu.audio.set_pitch(pitch);
-    0
+    Ok(0)
 }
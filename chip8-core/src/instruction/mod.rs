@@ -5,7 +5,7 @@
     allow(unused_imports)
 )]
 
-use crate::CPU;
+use crate::{Chip8Mode, Error, CPU};
 
 mod arithmetic;
 use arithmetic::*;
@@ -26,7 +26,7 @@ use misc::*;
 mod timers;
 use timers::*;
 
-pub(crate) type OpcodeExecute = fn(&mut CPU, u16) -> u32;
+pub(crate) type OpcodeExecute = fn(&mut CPU, u16) -> Result<u32, Error>;
 
 // #[derive(Copy, Clone, Debug)]
 pub(crate) struct Instruction {
@@ -56,6 +56,18 @@ macro_rules! make_instruction {
 }
 
 impl Instruction {
+    #[inline]
+    pub(crate) fn for_mode(&self, mode: Chip8Mode) -> Option<OpcodeExecute> {
+        match mode {
+            #[cfg(feature = "cosmac")]
+            Chip8Mode::Cosmac => self.cosmac,
+            #[cfg(feature = "super-chip")]
+            Chip8Mode::SuperChip => self.schip,
+            #[cfg(feature = "xo-chip")]
+            Chip8Mode::XoChip => self.xochip,
+        }
+    }
+
     pub(crate) fn lookup(opcode: u16) -> Option<Instruction> {
         match (
             (opcode >> 12) & 0xF,
@@ -206,21 +218,21 @@ impl Instruction {
                 "Vx = Vy;",
             ),
             (0x8, _, _, 0x1) => make_instruction!(
-                Some(inst_oreq_register_cosmac),
+                Some(inst_oreq_register),
                 Some(inst_oreq_register),
                 Some(inst_oreq_register),
                 1,
                 "Vx |= Vy;",
             ),
             (0x8, _, _, 0x2) => make_instruction!(
-                Some(inst_andeq_register_cosmac),
+                Some(inst_andeq_register),
                 Some(inst_andeq_register),
                 Some(inst_andeq_register),
                 1,
                 "Vx &= Vy;",
             ),
             (0x8, _, _, 0x3) => make_instruction!(
-                Some(inst_xoreq_register_cosmac),
+                Some(inst_xoreq_register),
                 Some(inst_xoreq_register),
                 Some(inst_xoreq_register),
                 1,
@@ -242,7 +254,7 @@ impl Instruction {
             ),
             (0x8, _, _, 0x6) => make_instruction!(
                 Some(inst_shift_right),
-                Some(inst_shift_right_schip),
+                Some(inst_shift_right),
                 Some(inst_shift_right),
                 1,
                 "Vx = Vy >> 1;",
@@ -256,7 +268,7 @@ impl Instruction {
             ),
             (0x8, _, _, 0xE) => make_instruction!(
                 Some(inst_shift_left),
-                Some(inst_shift_left_schip),
+                Some(inst_shift_left),
                 Some(inst_shift_left),
                 1,
                 "Vx = Vy << 1;",
@@ -277,7 +289,7 @@ impl Instruction {
             ),
             (0xB, _, _, _) => make_instruction!(
                 Some(inst_jump_v0),
-                Some(inst_jump_v0_schip),
+                Some(inst_jump_v0),
                 Some(inst_jump_v0),
                 1,
                 "PC = V0 + NNN;",
@@ -289,10 +301,17 @@ impl Instruction {
                 1,
                 "Vx = rand() & NN;",
             ),
+            (0xD, _, _, 0x0) => make_instruction!(
+                Some(inst_draw),
+                Some(inst_draw_big),
+                Some(inst_draw_big),
+                1,
+                "draw(Vx, Vy, N);",
+            ),
             (0xD, _, _, _) => make_instruction!(
-                Some(inst_draw_cosmac),
-                Some(inst_draw_schip),
-                Some(inst_draw_xochip),
+                Some(inst_draw),
+                Some(inst_draw),
+                Some(inst_draw),
                 1,
                 "draw(Vx, Vy, N);",
             ),
@@ -402,14 +421,14 @@ impl Instruction {
             }
             (0xF, _, 0x5, 0x5) => make_instruction!(
                 Some(inst_reg_dump),
-                Some(inst_reg_dump_schip),
+                Some(inst_reg_dump),
                 Some(inst_reg_dump),
                 1,
                 "reg_dump(V0, Vx, &I);",
             ),
             (0xF, _, 0x6, 0x5) => make_instruction!(
                 Some(inst_reg_load),
-                Some(inst_reg_load_schip),
+                Some(inst_reg_load),
                 Some(inst_reg_load),
                 1,
                 "reg_load(V0, Vx, &I);",
@@ -5,7 +5,7 @@
 extern crate alloc;
 extern crate cfg_if;
 extern crate enum_map;
-extern crate nanorand;
+extern crate getrandom;
 #[cfg(feature = "serde")]
 extern crate serde;
 #[cfg(feature = "serde")]
@@ -18,11 +18,45 @@ extern crate tracing;
 pub mod audio;
 mod common;
 pub mod cpu;
+pub mod disassembly;
 pub mod display;
-mod font;
+pub mod flags;
+pub mod font;
+#[cfg(feature = "alloc")]
+mod input;
 mod instruction;
+#[cfg(feature = "alloc")]
+pub mod movie;
+pub mod observer;
+#[cfg(feature = "alloc")]
+pub mod profile;
+pub mod quirks;
 pub mod register;
+pub mod render;
+#[cfg(feature = "alloc")]
+pub mod rewind;
+pub mod rng;
+pub mod savestate;
+#[cfg(feature = "alloc")]
+pub mod script;
+pub mod timing;
+pub mod trace;
 
 pub use common::{Chip8Mode, Error};
 pub use cpu::CPU;
+pub use disassembly::DecodedInstruction;
+pub use flags::FlagStorage;
+pub use font::{Font, FontPreset};
+#[cfg(feature = "alloc")]
+pub use movie::{Movie, MoviePlayer, MovieRecorder};
+pub use observer::Chip8Observer;
+pub use quirks::Quirks;
 pub use register::Register;
+#[cfg(feature = "alloc")]
+pub use rewind::Rewind;
+pub use rng::Rng;
+pub use savestate::SaveStateError;
+#[cfg(feature = "alloc")]
+pub use script::InputScript;
+pub use timing::{Budget, ClockSpeed, FrameReport, StopConditions, StopReason};
+pub use trace::{TraceRecord, Tracer};
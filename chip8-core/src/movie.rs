@@ -0,0 +1,569 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::common::take;
+use crate::cpu::SavedState;
+use crate::quirks::{IndexIncrement, IndexOverflow};
+use crate::savestate::SaveStateError;
+use crate::script::{InputReplay, InputScript, KeyAction, ScriptEvent};
+use crate::timing::{ClockSpeed, FrameReport};
+use crate::{Error, Quirks, CPU};
+
+// Movies are laid out as
+//
+//     magic     "CH8M"
+//     version   u16
+//     sections  tag: [u8; 4], length: u32, then `length` bytes
+//
+// with every integer little-endian, in the same way as save states: readers
+// skip sections they don't know and ignore bytes past the fields they do.
+pub const MAGIC: [u8; 4] = *b"CH8M";
+pub const VERSION: u16 = 2;
+
+// clock speed (0 = Hz, 1 = instructions per frame) and its value as a u32,
+// then the quirks: a byte of flags (shift, jump, VF reset, wrap, display wait,
+// low resolution tall sprites and collision rows from the lowest bit), the
+// `FX55`/`FX65` increment, index overflow, stack limit (0xFFFF for none) and
+// since version 2, a second byte of flags (key wait on press, key wait beep)
+const SECTION_SETTINGS: [u8; 4] = *b"SETT";
+// the save state the movie starts from
+const SECTION_START: [u8; 4] = *b"STAT";
+// frame count and event count as u32s, then each event as its frame (u32),
+// action (0 = release, 1 = press) and key
+const SECTION_INPUT: [u8; 4] = *b"INPT";
+// checksum interval in frames, then a u64 checksum for each interval
+const SECTION_CHECKSUMS: [u8; 4] = *b"SUMS";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MovieError {
+    // the data doesn't start with `MAGIC`
+    NotAMovie,
+    // written by a newer version of the format
+    UnsupportedVersion(u16),
+    // the starting state couldn't be read
+    SaveState(SaveStateError),
+    // the data ends early, is missing a section or holds invalid values
+    Corrupt,
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAMovie => write!(f, "not a movie"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "movie version {} is newer than supported ({})",
+                version, VERSION
+            ),
+            Self::SaveState(err) => write!(f, "movie's starting state: {}", err),
+            Self::Corrupt => write!(f, "movie is corrupt"),
+        }
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for MovieError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlaybackError {
+    Emulation(Error),
+    // the state after `frame` frames doesn't match the movie's checksum
+    Desync {
+        frame: u32,
+        expected: u64,
+        actual: u64,
+    },
+}
+
+impl fmt::Display for PlaybackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Emulation(err) => err.fmt(f),
+            Self::Desync {
+                frame,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "desynced by frame {}: checksum {:016x}, expected {:016x}",
+                frame, actual, expected
+            ),
+        }
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for PlaybackError {}
+
+impl From<Error> for PlaybackError {
+    #[inline]
+    fn from(err: Error) -> Self {
+        Self::Emulation(err)
+    }
+}
+
+// A recorded session: the state, quirks and clock speed it started with, and
+// the keys pressed and released in each frame after. Playing it back on any
+// build reproduces the session exactly, as long as the `CPU` uses the same
+// kind of random number generator. Checksums of the state taken every
+// `checksum_interval` frames while recording show where a playback diverges.
+#[derive(Clone, Debug)]
+pub struct Movie {
+    // boxed since XO-CHIP's memory makes it large
+    start: Box<SavedState>,
+    quirks: Quirks,
+    clock_speed: ClockSpeed,
+    input: InputScript,
+    frames: u32,
+    checksum_interval: u32,
+    // of the state after every `checksum_interval` frames
+    checksums: Vec<u64>,
+}
+
+impl Movie {
+    #[inline]
+    pub fn start(&self) -> &SavedState {
+        &self.start
+    }
+
+    #[inline]
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    #[inline]
+    pub fn clock_speed(&self) -> ClockSpeed {
+        self.clock_speed
+    }
+
+    #[inline]
+    pub fn input(&self) -> &InputScript {
+        &self.input
+    }
+
+    // Number of frames recorded.
+    #[inline]
+    pub fn len(&self) -> u32 {
+        self.frames
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.frames == 0
+    }
+
+    #[inline]
+    pub fn checksum_interval(&self) -> u32 {
+        self.checksum_interval
+    }
+
+    #[inline]
+    pub fn checksums(&self) -> &[u64] {
+        &self.checksums
+    }
+
+    // Drops everything from `frame` on.
+    fn truncate(&mut self, frame: u32) {
+        self.frames = self.frames.min(frame);
+        self.input.truncate(self.frames);
+        self.checksums
+            .truncate((self.frames / self.checksum_interval) as usize);
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend(MAGIC);
+        out.extend(VERSION.to_le_bytes());
+
+        section(&mut out, SECTION_SETTINGS, |out| {
+            let (kind, value) = match self.clock_speed {
+                ClockSpeed::Hertz(hz) => (0, hz),
+                ClockSpeed::InstructionsPerFrame(ipf) => (1, ipf),
+            };
+            out.push(kind);
+            out.extend(value.to_le_bytes());
+            let quirks = &self.quirks;
+            let flags = [
+                quirks.shift,
+                quirks.jump,
+                quirks.vf_reset,
+                quirks.wrap,
+                quirks.display_wait,
+                quirks.lores_tall_sprite,
+                quirks.collision_rows,
+            ];
+            out.push(
+                flags
+                    .iter()
+                    .rev()
+                    .fold(0, |bits, &flag| bits << 1 | flag as u8),
+            );
+            out.push(match quirks.load_store {
+                IndexIncrement::Unchanged => 0,
+                IndexIncrement::ByX => 1,
+                IndexIncrement::ByXPlusOne => 2,
+            });
+            out.push(match quirks.index_overflow {
+                IndexOverflow::Fault => 0,
+                IndexOverflow::Wrap12 => 1,
+                IndexOverflow::Wrap16 => 2,
+                IndexOverflow::Ignore => 3,
+            });
+            let stack_limit = quirks
+                .stack_limit
+                .map_or(0xFFFF, |limit| limit.min(0xFFFE) as u16);
+            out.extend(stack_limit.to_le_bytes());
+            out.push(quirks.key_wait_on_press as u8 | (quirks.key_wait_beep as u8) << 1);
+        });
+
+        section(&mut out, SECTION_START, |out| {
+            out.extend(self.start.to_bytes());
+        });
+
+        section(&mut out, SECTION_INPUT, |out| {
+            let events = self.input.events();
+            out.extend(self.frames.to_le_bytes());
+            out.extend((events.len() as u32).to_le_bytes());
+            for event in events {
+                out.extend(event.frame.to_le_bytes());
+                out.push(match event.action {
+                    KeyAction::Release => 0,
+                    KeyAction::Press => 1,
+                });
+                out.push(event.key);
+            }
+        });
+
+        section(&mut out, SECTION_CHECKSUMS, |out| {
+            out.extend(self.checksum_interval.to_le_bytes());
+            for checksum in &self.checksums {
+                out.extend(checksum.to_le_bytes());
+            }
+        });
+        out
+    }
+
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Movie, MovieError> {
+        let bytes = &mut bytes;
+        if take(bytes, MAGIC.len()) != Some(&MAGIC[..]) {
+            return Err(MovieError::NotAMovie);
+        }
+        let version = read_u16(bytes)?;
+        if version == 0 || version > VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+
+        let (mut settings, mut start, mut input) = (None, None, None);
+        let mut checksums = (0, Vec::new());
+        while !bytes.is_empty() {
+            let tag = take(bytes, 4).ok_or(MovieError::Corrupt)?;
+            let len = read_u32(bytes)? as usize;
+            let section = &mut take(bytes, len).ok_or(MovieError::Corrupt)?;
+            match [tag[0], tag[1], tag[2], tag[3]] {
+                SECTION_SETTINGS => settings = Some(read_settings(section, version)?),
+                SECTION_START => {
+                    start = Some(Box::new(
+                        SavedState::decode(section).map_err(MovieError::SaveState)?,
+                    ))
+                }
+                SECTION_INPUT => input = Some(read_input(section)?),
+                SECTION_CHECKSUMS => {
+                    let interval = read_u32(section)?;
+                    let count = section.len() / 8;
+                    let sums = (0..count)
+                        .map(|_| read_u64(section))
+                        .collect::<Result<_, _>>()?;
+                    checksums = (interval, sums);
+                }
+                _ => {}
+            }
+        }
+
+        let ((clock_speed, quirks), start, (frames, input)) = match (settings, start, input) {
+            (Some(settings), Some(start), Some(input)) => (settings, start, input),
+            _ => return Err(MovieError::Corrupt),
+        };
+        let (checksum_interval, checksums) = checksums;
+        if checksum_interval == 0 && !checksums.is_empty() {
+            return Err(MovieError::Corrupt);
+        }
+        Ok(Movie {
+            start,
+            quirks,
+            clock_speed,
+            input,
+            frames,
+            checksum_interval: checksum_interval.max(1),
+            checksums,
+        })
+    }
+}
+
+fn section(out: &mut Vec<u8>, tag: [u8; 4], contents: impl FnOnce(&mut Vec<u8>)) {
+    out.extend(tag);
+    let len_pos = out.len();
+    out.extend([0; 4]);
+    contents(out);
+    let len = (out.len() - len_pos - 4) as u32;
+    out[len_pos..len_pos + 4].copy_from_slice(&len.to_le_bytes());
+}
+
+fn read_u8(bytes: &mut &[u8]) -> Result<u8, MovieError> {
+    Ok(take(bytes, 1).ok_or(MovieError::Corrupt)?[0])
+}
+
+fn read_u16(bytes: &mut &[u8]) -> Result<u16, MovieError> {
+    let bytes = take(bytes, 2).ok_or(MovieError::Corrupt)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(bytes: &mut &[u8]) -> Result<u32, MovieError> {
+    let bytes = take(bytes, 4).ok_or(MovieError::Corrupt)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(bytes: &mut &[u8]) -> Result<u64, MovieError> {
+    let bytes = take(bytes, 8).ok_or(MovieError::Corrupt)?;
+    let mut array = [0; 8];
+    array.copy_from_slice(bytes);
+    Ok(u64::from_le_bytes(array))
+}
+
+fn read_settings(bytes: &mut &[u8], version: u16) -> Result<(ClockSpeed, Quirks), MovieError> {
+    let clock_speed = match (read_u8(bytes)?, read_u32(bytes)?) {
+        (0, hz) => ClockSpeed::Hertz(hz),
+        (1, ipf) => ClockSpeed::InstructionsPerFrame(ipf),
+        _ => return Err(MovieError::Corrupt),
+    };
+    let flags = read_u8(bytes)?;
+    let flag = |bit: u8| flags & 1 << bit != 0;
+    let load_store = match read_u8(bytes)? {
+        0 => IndexIncrement::Unchanged,
+        1 => IndexIncrement::ByX,
+        2 => IndexIncrement::ByXPlusOne,
+        _ => return Err(MovieError::Corrupt),
+    };
+    let index_overflow = match read_u8(bytes)? {
+        0 => IndexOverflow::Fault,
+        1 => IndexOverflow::Wrap12,
+        2 => IndexOverflow::Wrap16,
+        3 => IndexOverflow::Ignore,
+        _ => return Err(MovieError::Corrupt),
+    };
+    let stack_limit = match read_u16(bytes)? {
+        0xFFFF => None,
+        limit => Some(limit as usize),
+    };
+    // movies from before FX0A waited for a fresh press were recorded without
+    // either quirk
+    let more_flags = if version >= 2 { read_u8(bytes)? } else { 0 };
+    let quirks = Quirks {
+        shift: flag(0),
+        load_store,
+        jump: flag(1),
+        vf_reset: flag(2),
+        wrap: flag(3),
+        display_wait: flag(4),
+        lores_tall_sprite: flag(5),
+        collision_rows: flag(6),
+        index_overflow,
+        stack_limit,
+        key_wait_on_press: more_flags & 1 != 0,
+        key_wait_beep: more_flags & 2 != 0,
+    };
+    Ok((clock_speed, quirks))
+}
+
+fn read_input(bytes: &mut &[u8]) -> Result<(u32, InputScript), MovieError> {
+    let frames = read_u32(bytes)?;
+    let count = read_u32(bytes)?;
+    let mut input = InputScript::new();
+    for _ in 0..count {
+        let frame = read_u32(bytes)?;
+        let action = match read_u8(bytes)? {
+            0 => KeyAction::Release,
+            1 => KeyAction::Press,
+            _ => return Err(MovieError::Corrupt),
+        };
+        let key = read_u8(bytes)?;
+        if key > 0xF || frame > frames {
+            return Err(MovieError::Corrupt);
+        }
+        input.push(ScriptEvent { frame, action, key });
+    }
+    Ok((frames, input))
+}
+
+// 64-bit FNV-1a of the encoded state, which doesn't depend on the features of
+// the build. A program that has exited has no state to save, and nothing more
+// can happen to it, so it always checksums to 0.
+fn checksum(cpu: &mut CPU) -> u64 {
+    match cpu.save_state() {
+        Ok(state) => state
+            .to_bytes()
+            .iter()
+            .fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+                (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+            }),
+        Err(_) => 0,
+    }
+}
+
+// Records a session on a `CPU` into a `Movie`. Keys have to be pressed and
+// released through the recorder, and frames run through it, for them to be
+// recorded; changing the quirks or clock speed while recording desyncs the
+// movie.
+#[derive(Clone, Debug)]
+pub struct MovieRecorder {
+    movie: Movie,
+}
+
+impl MovieRecorder {
+    pub const DEFAULT_CHECKSUM_INTERVAL: u32 = 60;
+
+    // Starts recording from the current state of `cpu`, which can be a fresh
+    // ROM or a loaded save state.
+    #[inline]
+    pub fn new(cpu: &mut CPU) -> Result<MovieRecorder, Error> {
+        Self::with_checksum_interval(cpu, Self::DEFAULT_CHECKSUM_INTERVAL)
+    }
+
+    // Shorter intervals catch desyncs closer to where they happen, at the cost
+    // of a bigger movie and slower recording and playback.
+    pub fn with_checksum_interval(cpu: &mut CPU, interval: u32) -> Result<MovieRecorder, Error> {
+        Ok(MovieRecorder {
+            movie: Movie {
+                start: Box::new(cpu.save_state()?),
+                quirks: cpu.quirks,
+                clock_speed: cpu.clock_speed,
+                input: InputScript::new(),
+                frames: 0,
+                checksum_interval: interval.max(1),
+                checksums: Vec::new(),
+            },
+        })
+    }
+
+    // Frames recorded so far.
+    #[inline]
+    pub fn frame(&self) -> u32 {
+        self.movie.frames
+    }
+
+    #[inline]
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+
+    pub fn press_key(&mut self, cpu: &mut CPU, key: u8) {
+        self.record(cpu, KeyAction::Press, key);
+    }
+
+    pub fn release_key(&mut self, cpu: &mut CPU, key: u8) {
+        self.record(cpu, KeyAction::Release, key);
+    }
+
+    fn record(&mut self, cpu: &mut CPU, action: KeyAction, key: u8) {
+        match action {
+            KeyAction::Press => cpu.press_key(key),
+            KeyAction::Release => cpu.release_key(key),
+        }
+        self.movie.input.push(ScriptEvent {
+            frame: self.movie.frames,
+            action,
+            key,
+        });
+    }
+
+    pub fn run_frame(&mut self, cpu: &mut CPU) -> Result<FrameReport, Error> {
+        let report = cpu.run_frame()?;
+        self.movie.frames += 1;
+        if self.movie.frames % self.movie.checksum_interval == 0 {
+            self.movie.checksums.push(checksum(cpu));
+        }
+        Ok(report)
+    }
+
+    #[inline]
+    pub fn finish(self) -> Movie {
+        self.movie
+    }
+}
+
+// Plays a `Movie` back on a `CPU`, checking the state against the movie's
+// checksums as it goes.
+#[derive(Clone, Debug)]
+pub struct MoviePlayer {
+    movie: Movie,
+    replay: InputReplay,
+}
+
+impl MoviePlayer {
+    // Puts `cpu` in the state, quirks and clock speed the movie starts with.
+    pub fn new(movie: Movie, cpu: &mut CPU) -> MoviePlayer {
+        cpu.load_state((*movie.start).clone());
+        cpu.quirks = movie.quirks;
+        cpu.clock_speed = movie.clock_speed;
+        let replay = InputReplay::new(movie.input.clone());
+        MoviePlayer { movie, replay }
+    }
+
+    // Frames played so far.
+    #[inline]
+    pub fn frame(&self) -> u32 {
+        self.replay.frame()
+    }
+
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.frame() >= self.movie.frames
+    }
+
+    #[inline]
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+
+    // Plays the next frame, or returns None once the movie is over. A desync
+    // is reported after the frame it was found in, and playback can carry on
+    // from there.
+    pub fn run_frame(&mut self, cpu: &mut CPU) -> Result<Option<FrameReport>, PlaybackError> {
+        if self.is_finished() {
+            return Ok(None);
+        }
+        self.replay.apply(cpu);
+        let report = cpu.run_frame()?;
+        self.replay.next_frame();
+
+        let frame = self.frame();
+        let interval = self.movie.checksum_interval;
+        if frame % interval == 0 {
+            let idx = (frame / interval - 1) as usize;
+            if let Some(&expected) = self.movie.checksums.get(idx) {
+                let actual = checksum(cpu);
+                if actual != expected {
+                    return Err(PlaybackError::Desync {
+                        frame,
+                        expected,
+                        actual,
+                    });
+                }
+            }
+        }
+        Ok(Some(report))
+    }
+
+    // Plays the rest of the movie, stopping at the first desync.
+    pub fn play(&mut self, cpu: &mut CPU) -> Result<(), PlaybackError> {
+        while self.run_frame(cpu)?.is_some() {}
+        Ok(())
+    }
+
+    // Branches off the movie at the current frame: everything up to here is
+    // kept and recording carries on from the state of `cpu`.
+    pub fn into_recorder(self) -> MovieRecorder {
+        let frame = self.frame();
+        let mut movie = self.movie;
+        movie.truncate(frame);
+        MovieRecorder { movie }
+    }
+}
@@ -0,0 +1,73 @@
+use crate::display::ScreenRect;
+use crate::{Register, CPU};
+
+// Receives events from `CPU::run_observed` and `CPU::step_observed`. Every
+// method does nothing by default, so implementors only override the events
+// they care about.
+pub trait Chip8Observer {
+    // called after the instruction at `pc` finished executing
+    fn instruction_executed(&mut self, _cpu: &CPU, _pc: u16, _opcode: u16) {}
+    // a clear, draw, scroll or resolution change touched the pixels in `rect`
+    fn screen_modified(&mut self, _cpu: &CPU, _rect: ScreenRect) {}
+    // the sound started or stopped playing, see `CPU::is_sound_playing`
+    fn sound_changed(&mut self, _cpu: &CPU, _playing: bool) {}
+    // `FX0A` started waiting for a key to store in `register`
+    fn key_wait(&mut self, _cpu: &CPU, _register: Register) {}
+    // `00FD` exited the interpreter
+    fn exited(&mut self, _cpu: &CPU) {}
+    // an instruction stored `value` at `addr`
+    fn memory_written(&mut self, _cpu: &CPU, _addr: u16, _value: u8) {}
+}
+
+// For running without an observer.
+impl Chip8Observer for () {}
+
+// Most memory any single instruction writes (`FX55` with X = F).
+const MAX_WRITES: usize = 16;
+
+// What the instruction being executed changed, recorded by the instruction
+// handlers and reported to the observer once it completes.
+#[derive(Clone, Default)]
+pub(crate) struct StepEffects {
+    pub(crate) screen: Option<ScreenRect>,
+    writes: [(u16, u8); MAX_WRITES],
+    write_count: usize,
+}
+
+impl StepEffects {
+    #[inline]
+    pub(crate) fn clear(&mut self) {
+        self.screen = None;
+        self.write_count = 0;
+    }
+
+    #[inline]
+    pub(crate) fn add_screen(&mut self, rect: ScreenRect) {
+        self.screen = Some(match self.screen {
+            Some(prev) => prev.union(rect),
+            None => rect,
+        });
+    }
+
+    #[inline]
+    pub(crate) fn add_write(&mut self, addr: u16, value: u8) {
+        if self.write_count < MAX_WRITES {
+            self.writes[self.write_count] = (addr, value);
+            self.write_count += 1;
+        }
+    }
+
+    #[inline]
+    pub(crate) fn writes(&self) -> &[(u16, u8)] {
+        &self.writes[..self.write_count]
+    }
+}
+
+impl core::fmt::Debug for StepEffects {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("StepEffects")
+            .field("screen", &self.screen)
+            .field("writes", &self.writes())
+            .finish()
+    }
+}
@@ -0,0 +1,298 @@
+use crate::cpu::{CallStack, CHIP8_MEM_SIZE};
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+// Totals for one address.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AddressStats {
+    pub addr: u16,
+    pub executions: u64,
+    pub cycles: u64,
+}
+
+// Totals for the subroutine starting at a call target.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RoutineStats {
+    pub addr: u16,
+    pub calls: u64,
+    // instructions and cycles spent in the routine itself
+    pub instructions: u64,
+    pub self_cycles: u64,
+    // cycles spent in the routine and everything it called
+    pub total_cycles: u64,
+}
+
+// Execution counts and cycle costs gathered while profiling is enabled on a
+// `CPU`. Cycles are the instruction costs the timing model charges, not
+// wall-clock time.
+#[derive(Clone)]
+pub struct Profile {
+    executions: Vec<u64>,
+    cycles: Vec<u64>,
+    routines: BTreeMap<u16, RoutineStats>,
+    instructions: u64,
+    total_cycles: u64,
+    // call targets of the frames active for the instruction being executed,
+    // innermost last
+    frames: Vec<u16>,
+}
+
+impl Profile {
+    pub fn new() -> Profile {
+        Profile {
+            executions: vec![0; CHIP8_MEM_SIZE],
+            cycles: vec![0; CHIP8_MEM_SIZE],
+            routines: BTreeMap::new(),
+            instructions: 0,
+            total_cycles: 0,
+            frames: Vec::new(),
+        }
+    }
+
+    // Called before an instruction runs, so that calls and returns are
+    // charged to the routine they're in. Each frame's target is the operand
+    // of the `2NNN` just before its return address.
+    pub(crate) fn enter(&mut self, call_stack: &CallStack, memory: &[u8]) {
+        self.frames.clear();
+        for &ret in call_stack {
+            let call = ret.wrapping_sub(2) as usize;
+            let target = match (memory.get(call), memory.get(call + 1)) {
+                (Some(&high), Some(&low)) => u16::from_be_bytes([high, low]) & 0xFFF,
+                _ => continue,
+            };
+            self.frames.push(target);
+        }
+    }
+
+    pub(crate) fn record(&mut self, pc: u16, opcode: u16, cycles: u32) {
+        let cycles = cycles as u64;
+        let addr = pc as usize % CHIP8_MEM_SIZE;
+        self.executions[addr] += 1;
+        self.cycles[addr] += cycles;
+        self.instructions += 1;
+        self.total_cycles += cycles;
+
+        let routines = &mut self.routines;
+        if let Some((&innermost, outer)) = self.frames.split_last() {
+            let current = routine(routines, innermost);
+            current.instructions += 1;
+            current.self_cycles += cycles;
+            current.total_cycles += cycles;
+            // recursive routines are only charged once per instruction
+            for (idx, &target) in outer.iter().enumerate() {
+                if target != innermost && !outer[idx + 1..].contains(&target) {
+                    routine(routines, target).total_cycles += cycles;
+                }
+            }
+        }
+        if opcode & 0xF000 == 0x2000 {
+            routine(routines, opcode & 0xFFF).calls += 1;
+        }
+    }
+
+    #[inline]
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    #[inline]
+    pub fn total_cycles(&self) -> u64 {
+        self.total_cycles
+    }
+
+    #[inline]
+    pub fn address(&self, addr: u16) -> AddressStats {
+        let idx = addr as usize % CHIP8_MEM_SIZE;
+        AddressStats {
+            addr,
+            executions: self.executions[idx],
+            cycles: self.cycles[idx],
+        }
+    }
+
+    #[inline]
+    pub fn routine_stats(&self, addr: u16) -> Option<RoutineStats> {
+        self.routines.get(&addr).copied()
+    }
+
+    // Every address that ran, the most cycles first.
+    pub fn hot_spots(&self) -> Vec<AddressStats> {
+        let mut spots: Vec<AddressStats> = (0..CHIP8_MEM_SIZE)
+            .filter(|&addr| self.executions[addr] > 0)
+            .map(|addr| self.address(addr as u16))
+            .collect();
+        spots.sort_by(|a, b| {
+            (b.cycles, b.executions, a.addr).cmp(&(a.cycles, a.executions, b.addr))
+        });
+        spots
+    }
+
+    // Every routine that was called or ran, the most total cycles first.
+    pub fn routines(&self) -> Vec<RoutineStats> {
+        let mut routines: Vec<RoutineStats> = self.routines.values().copied().collect();
+        routines.sort_by(|a, b| {
+            (b.total_cycles, b.self_cycles, a.addr).cmp(&(a.total_cycles, a.self_cycles, b.addr))
+        });
+        routines
+    }
+
+    // A table of the `limit` hottest routines and addresses.
+    #[inline]
+    pub fn report(&self, limit: usize) -> ProfileReport<'_> {
+        ProfileReport {
+            profile: self,
+            limit,
+        }
+    }
+
+    // One intensity per address of memory: 0 for addresses that never ran, and
+    // 1 to 255 for the rest, on a logarithmic scale of their cycles up to the
+    // hottest address.
+    pub fn heatmap(&self) -> Vec<u8> {
+        let max = log2_fixed(self.cycles.iter().copied().max().unwrap_or(0));
+        self.cycles
+            .iter()
+            .zip(&self.executions)
+            .map(|(&cycles, &executions)| {
+                if executions == 0 {
+                    0
+                } else if max == 0 {
+                    255
+                } else {
+                    (1 + log2_fixed(cycles) as u64 * 254 / max as u64) as u8
+                }
+            })
+            .collect()
+    }
+
+    // The heatmap as text, `width` addresses to a row, skipping rows where
+    // nothing ran.
+    #[inline]
+    pub fn heatmap_text(&self, width: usize) -> HeatmapText {
+        HeatmapText {
+            heatmap: self.heatmap(),
+            width: width.max(1),
+        }
+    }
+}
+
+impl Default for Profile {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The per-address tables would drown out everything else in a `CPU`'s debug
+// output, so only the totals are shown.
+impl fmt::Debug for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Profile")
+            .field("instructions", &self.instructions)
+            .field("total_cycles", &self.total_cycles)
+            .finish_non_exhaustive()
+    }
+}
+
+#[inline]
+fn routine(routines: &mut BTreeMap<u16, RoutineStats>, addr: u16) -> &mut RoutineStats {
+    routines.entry(addr).or_insert(RoutineStats {
+        addr,
+        ..RoutineStats::default()
+    })
+}
+
+// log2(value) with four fractional bits, so that the heatmap has more than a
+// dozen shades.
+fn log2_fixed(value: u64) -> u32 {
+    if value == 0 {
+        return 0;
+    }
+    let exp = 63 - value.leading_zeros();
+    let fraction = if exp >= 4 {
+        (value >> (exp - 4)) & 0xF
+    } else {
+        (value << (4 - exp)) & 0xF
+    };
+    exp * 16 + fraction as u32
+}
+
+pub struct ProfileReport<'a> {
+    profile: &'a Profile,
+    limit: usize,
+}
+
+impl fmt::Display for ProfileReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let profile = self.profile;
+        // hundredths of a percent of all cycles
+        let share = |cycles: u64| cycles * 10_000 / profile.total_cycles.max(1);
+        writeln!(
+            f,
+            "{} instructions, {} cycles",
+            profile.instructions, profile.total_cycles
+        )?;
+
+        writeln!(f)?;
+        writeln!(f, "routine  calls       self cycles  total cycles       %")?;
+        for routine in profile.routines().iter().take(self.limit) {
+            let pct = share(routine.total_cycles);
+            writeln!(
+                f,
+                "0x{:03X}    {:<10}  {:<11}  {:<12}  {:>3}.{:02}",
+                routine.addr,
+                routine.calls,
+                routine.self_cycles,
+                routine.total_cycles,
+                pct / 100,
+                pct % 100
+            )?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "address  executions  cycles       %")?;
+        for spot in profile.hot_spots().iter().take(self.limit) {
+            let pct = share(spot.cycles);
+            writeln!(
+                f,
+                "0x{:04X}   {:<10}  {:<11}  {:>3}.{:02}",
+                spot.addr,
+                spot.executions,
+                spot.cycles,
+                pct / 100,
+                pct % 100
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub struct HeatmapText {
+    heatmap: Vec<u8>,
+    width: usize,
+}
+
+impl fmt::Display for HeatmapText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const SHADES: &[u8] = b" .:-=+*#%@";
+        for (row, values) in self.heatmap.chunks(self.width).enumerate() {
+            if values.iter().all(|&value| value == 0) {
+                continue;
+            }
+            write!(f, "0x{:04X} |", row * self.width)?;
+            for &value in values {
+                let shade = if value == 0 {
+                    0
+                } else {
+                    1 + (value as usize - 1) * (SHADES.len() - 1) / 255
+                };
+                write!(f, "{}", SHADES[shade] as char)?;
+            }
+            writeln!(f, "|")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+use crate::Chip8Mode;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// How `FX55` and `FX65` leave the address register after accessing memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum IndexIncrement {
+    // SUPER-CHIP 1.1
+    Unchanged,
+    // CHIP-48 and SUPER-CHIP 1.0
+    ByX,
+    // COSMAC VIP and XO-CHIP
+    ByXPlusOne,
+}
+
+// What happens when an instruction accesses memory through I past the end of
+// the address space.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum IndexOverflow {
+    // stop execution with `Error::MemoryFault`
+    Fault,
+    // wrap the address around to 12 bits (4 KiB)
+    Wrap12,
+    // wrap the address around to 16 bits (64 KiB)
+    Wrap16,
+    // reads return zero and writes are discarded
+    Ignore,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Quirks {
+    // `8XY6` and `8XYE` shift Vx in place instead of reading from Vy
+    pub shift: bool,
+    pub load_store: IndexIncrement,
+    // `BXNN` jumps to `XNN + Vx` instead of `XNN + V0`
+    pub jump: bool,
+    // `8XY1`, `8XY2` and `8XY3` reset VF to zero
+    pub vf_reset: bool,
+    // sprites wrap around the screen edges instead of being clipped
+    pub wrap: bool,
+    // `DXYN` waits for the next timer tick before execution continues
+    pub display_wait: bool,
+    // `DXY0` draws an 8x16 sprite rather than a 16x16 one in low resolution
+    pub lores_tall_sprite: bool,
+    // in high resolution, VF is set to the number of sprite rows that collided
+    // or were clipped off the bottom of the screen
+    pub collision_rows: bool,
+    pub index_overflow: IndexOverflow,
+    // maximum call stack depth before `2NNN` fails with `Error::StackOverflow`
+    pub stack_limit: Option<usize>,
+    // `FX0A` finishes as soon as a key is pressed rather than once it's
+    // released again
+    pub key_wait_on_press: bool,
+    // a tone plays while `FX0A` waits for the pressed key to be released
+    pub key_wait_beep: bool,
+}
+
+impl Quirks {
+    pub const COSMAC: Quirks = Quirks {
+        shift: false,
+        load_store: IndexIncrement::ByXPlusOne,
+        jump: false,
+        vf_reset: true,
+        wrap: false,
+        display_wait: true,
+        lores_tall_sprite: false,
+        collision_rows: false,
+        index_overflow: IndexOverflow::Fault,
+        stack_limit: Some(12),
+        key_wait_on_press: false,
+        key_wait_beep: true,
+    };
+    pub const CHIP48: Quirks = Quirks {
+        shift: true,
+        load_store: IndexIncrement::ByX,
+        jump: true,
+        vf_reset: false,
+        wrap: false,
+        display_wait: false,
+        lores_tall_sprite: false,
+        collision_rows: false,
+        index_overflow: IndexOverflow::Fault,
+        stack_limit: Some(16),
+        key_wait_on_press: false,
+        key_wait_beep: false,
+    };
+    pub const SUPER_CHIP: Quirks = Quirks {
+        shift: true,
+        load_store: IndexIncrement::Unchanged,
+        jump: true,
+        vf_reset: false,
+        wrap: false,
+        display_wait: false,
+        lores_tall_sprite: true,
+        collision_rows: true,
+        index_overflow: IndexOverflow::Fault,
+        stack_limit: Some(16),
+        key_wait_on_press: false,
+        key_wait_beep: false,
+    };
+    pub const XO_CHIP: Quirks = Quirks {
+        shift: false,
+        load_store: IndexIncrement::ByXPlusOne,
+        jump: false,
+        vf_reset: false,
+        wrap: true,
+        display_wait: false,
+        lores_tall_sprite: false,
+        collision_rows: false,
+        index_overflow: IndexOverflow::Fault,
+        stack_limit: None,
+        key_wait_on_press: false,
+        key_wait_beep: false,
+    };
+
+    #[inline]
+    pub fn for_mode(mode: Chip8Mode) -> Quirks {
+        match mode {
+            #[cfg(feature = "cosmac")]
+            Chip8Mode::Cosmac => Self::COSMAC,
+            #[cfg(feature = "super-chip")]
+            Chip8Mode::SuperChip => Self::SUPER_CHIP,
+            #[cfg(feature = "xo-chip")]
+            Chip8Mode::XoChip => Self::XO_CHIP,
+        }
+    }
+}
+
+impl From<Chip8Mode> for Quirks {
+    #[inline]
+    fn from(mode: Chip8Mode) -> Self {
+        Self::for_mode(mode)
+    }
+}
+
+#[cfg(any(feature = "cosmac", feature = "super-chip", feature = "xo-chip"))]
+impl Default for Quirks {
+    #[inline]
+    fn default() -> Self {
+        Self::for_mode(Chip8Mode::default())
+    }
+}
@@ -0,0 +1,180 @@
+use crate::display::{Display, COLOR_SET, HIGHRES_SCREEN_DIMENSIONS, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use core::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PixelFormat {
+    // 4 bytes per pixel, red first, alpha always 0xFF
+    Rgba8,
+    // 4 bytes per pixel, blue first, alpha always 0xFF
+    Bgra8,
+    // 2 bytes per pixel, little endian
+    Rgb565,
+    // 8 pixels per byte, most significant bit first, each row starting on a
+    // new byte; a bit is set for any pixel that isn't blank
+    Mono1,
+    // 1 byte per pixel, the luma of the palette color
+    Gray8,
+}
+
+impl PixelFormat {
+    #[inline]
+    pub fn bits_per_pixel(self) -> usize {
+        match self {
+            Self::Rgba8 | Self::Bgra8 => 32,
+            Self::Rgb565 => 16,
+            Self::Mono1 => 1,
+            Self::Gray8 => 8,
+        }
+    }
+
+    // The bytes of a 0RGB color, or None for `Mono1`, which has no colors.
+    fn encode(self, color: u32) -> Option<[u8; 4]> {
+        let [_, r, g, b] = color.to_be_bytes();
+        Some(match self {
+            Self::Rgba8 => [r, g, b, 0xFF],
+            Self::Bgra8 => [b, g, r, 0xFF],
+            Self::Rgb565 => {
+                let rgb = (r as u16 >> 3) << 11 | (g as u16 >> 2) << 5 | b as u16 >> 3;
+                let [lo, hi] = rgb.to_le_bytes();
+                [lo, hi, 0, 0]
+            }
+            Self::Gray8 => {
+                let luma = (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000;
+                [luma as u8, 0, 0, 0]
+            }
+            Self::Mono1 => return None,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Resolution {
+    // the display's current resolution, so 64x32 in low resolution
+    Native,
+    // always 128x64, with low resolution pixels doubled, so the output keeps
+    // its size when a program switches resolution
+    Fixed,
+}
+
+// Renders a `Display` into a buffer supplied by the caller, without
+// allocating. The buffer holds `height` rows of `stride` bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Renderer {
+    pub format: PixelFormat,
+    // every pixel becomes a `scale`x`scale` square; 0 renders nothing
+    pub scale: usize,
+    pub resolution: Resolution,
+    // 0RGB colors, indexed by the bitplanes set for a pixel
+    pub palette: [u32; 4],
+}
+
+// `Renderer::render` was given a buffer shorter than `Renderer::buffer_len`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BufferTooSmall {
+    pub needed: usize,
+}
+
+impl fmt::Display for BufferTooSmall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "render buffer too small, needs {} bytes", self.needed)
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for BufferTooSmall {}
+
+impl Renderer {
+    // Renders at native resolution without scaling, in the colors of
+    // `COLOR_SET`.
+    pub fn new(format: PixelFormat) -> Renderer {
+        let mut palette = [COLOR_SET[COLOR_SET.len() - 1]; 4];
+        palette[..COLOR_SET.len()].copy_from_slice(&COLOR_SET);
+        Renderer {
+            format,
+            scale: 1,
+            resolution: Resolution::Native,
+            palette,
+        }
+    }
+
+    // Pixels rendered from `display` before scaling.
+    #[inline]
+    fn source_dimensions(&self, display: &Display) -> (usize, usize) {
+        match self.resolution {
+            Resolution::Native => display.dimensions(),
+            Resolution::Fixed => HIGHRES_SCREEN_DIMENSIONS,
+        }
+    }
+
+    // Size of the rendered image in pixels.
+    #[inline]
+    pub fn dimensions(&self, display: &Display) -> (usize, usize) {
+        let (width, height) = self.source_dimensions(display);
+        (width * self.scale, height * self.scale)
+    }
+
+    // Bytes per row of the rendered image.
+    #[inline]
+    pub fn stride(&self, display: &Display) -> usize {
+        let (width, _) = self.dimensions(display);
+        (width * self.format.bits_per_pixel() + 7) / 8
+    }
+
+    #[inline]
+    pub fn buffer_len(&self, display: &Display) -> usize {
+        self.stride(display) * self.dimensions(display).1
+    }
+
+    // Renders into the start of `out`, leaving anything past `buffer_len`
+    // untouched, and returns the size of the image in pixels.
+    pub fn render(
+        &self,
+        display: &Display,
+        out: &mut [u8],
+    ) -> Result<(usize, usize), BufferTooSmall> {
+        let needed = self.buffer_len(display);
+        if out.len() < needed {
+            return Err(BufferTooSmall { needed });
+        }
+        let (src_width, src_height) = self.source_dimensions(display);
+        let stride = self.stride(display);
+        if stride == 0 {
+            return Ok(self.dimensions(display));
+        }
+        let colors = self.palette.map(|color| self.format.encode(color));
+        let bytes = self.format.bits_per_pixel() / 8;
+        let out = &mut out[..needed];
+        for (y, rows) in out.chunks_exact_mut(stride * self.scale).enumerate() {
+            let (row, rest) = rows.split_at_mut(stride);
+            let buf_y = y * SCREEN_HEIGHT / src_height;
+            if bytes == 0 {
+                row.fill(0);
+            }
+            for x in 0..src_width {
+                let buf_x = x * SCREEN_WIDTH / src_width;
+                let pixel = display.read_pixel_unchecked(buf_x as u8, buf_y as u8);
+                let start = x * self.scale;
+                match colors[pixel] {
+                    Some(color) => {
+                        for px in
+                            row[start * bytes..(start + self.scale) * bytes].chunks_exact_mut(bytes)
+                        {
+                            px.copy_from_slice(&color[..bytes]);
+                        }
+                    }
+                    None if pixel != 0 => {
+                        for bit in start..start + self.scale {
+                            row[bit / 8] |= 0x80 >> (bit % 8);
+                        }
+                    }
+                    None => {}
+                }
+            }
+            // the rest of a scaled row is the same as its first line
+            for line in rest.chunks_exact_mut(stride) {
+                line.copy_from_slice(row);
+            }
+        }
+        Ok(self.dimensions(display))
+    }
+}
@@ -0,0 +1,208 @@
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use crate::cpu::SavedState;
+use crate::{Error, CPU};
+
+// History of saved states for stepping backwards. Every state is stored as
+// the XOR of its encoding against the keyframe that starts its group, with
+// runs of unchanged bytes skipped, so a frame that only touched a few
+// registers and pixels costs a few dozen bytes rather than a whole copy of
+// memory and the screen.
+#[derive(Clone, Debug)]
+pub struct Rewind {
+    capacity: usize,
+    keyframe_interval: usize,
+    frames: VecDeque<Frame>,
+    // encoding of the newest keyframe, which new frames are compared against
+    keyframe: Vec<u8>,
+    since_keyframe: usize,
+}
+
+#[derive(Clone, Debug)]
+struct Frame {
+    keyframe: bool,
+    delta: Vec<u8>,
+}
+
+impl Rewind {
+    pub const DEFAULT_KEYFRAME_INTERVAL: usize = 60;
+
+    // Keeps up to `capacity` recorded frames.
+    #[inline]
+    pub fn new(capacity: usize) -> Self {
+        Self::with_keyframe_interval(capacity, Self::DEFAULT_KEYFRAME_INTERVAL)
+    }
+
+    // Shorter intervals make rewinding cheaper but history larger.
+    pub fn with_keyframe_interval(capacity: usize, keyframe_interval: usize) -> Self {
+        Rewind {
+            capacity,
+            keyframe_interval: keyframe_interval.max(1),
+            frames: VecDeque::new(),
+            keyframe: Vec::new(),
+            since_keyframe: 0,
+        }
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // Number of frames that can currently be stepped back to.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.frames.len().min(self.capacity)
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Total bytes of recorded history.
+    pub fn size_in_bytes(&self) -> usize {
+        self.frames.iter().map(|frame| frame.delta.len()).sum()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.keyframe.clear();
+        self.since_keyframe = 0;
+    }
+
+    // Adds the current state of `cpu` as the newest frame, typically called
+    // once per frame.
+    pub fn record(&mut self, cpu: &mut CPU) -> Result<(), Error> {
+        if self.capacity == 0 {
+            return Ok(());
+        }
+        let mut raw = Vec::new();
+        cpu.save_state()?.write_raw(&mut raw);
+        if self.frames.is_empty() || self.since_keyframe >= self.keyframe_interval {
+            self.frames.push_back(Frame {
+                keyframe: true,
+                delta: encode_delta(&raw, &[]),
+            });
+            self.keyframe = raw;
+            self.since_keyframe = 1;
+        } else {
+            self.frames.push_back(Frame {
+                keyframe: false,
+                delta: encode_delta(&raw, &self.keyframe),
+            });
+            self.since_keyframe += 1;
+        }
+
+        // frames depend on their keyframe, so whole groups are dropped once
+        // they're all older than the capacity
+        while let Some(next_group) = self.frames.iter().skip(1).position(|frame| frame.keyframe) {
+            let group_len = next_group + 1;
+            if self.frames.len() - group_len < self.capacity {
+                break;
+            }
+            self.frames.drain(..group_len);
+        }
+        Ok(())
+    }
+
+    // Restores the state from `frames` recordings before the newest one,
+    // discarding everything newer; `rewind(cpu, 0)` restores the newest frame.
+    // Recording again continues the history from the restored frame. Returns
+    // how many frames were actually stepped back, which is less than `frames`
+    // if the history doesn't go back that far.
+    pub fn rewind(&mut self, cpu: &mut CPU, frames: usize) -> usize {
+        if self.is_empty() {
+            return 0;
+        }
+        let newest = self.frames.len() - 1;
+        let steps = frames.min(self.len() - 1);
+        let target = newest - steps;
+        let keyframe_idx = (0..=target)
+            .rev()
+            .find(|&idx| self.frames[idx].keyframe)
+            .expect("history starts with a keyframe");
+
+        let keyframe = decode_delta(&self.frames[keyframe_idx].delta, &[]);
+        let raw = if target == keyframe_idx {
+            keyframe.clone()
+        } else {
+            decode_delta(&self.frames[target].delta, &keyframe)
+        };
+        let state = SavedState::read_raw(&raw).expect("history holds states from this build");
+        cpu.load_state(state);
+
+        self.frames.truncate(target + 1);
+        self.keyframe = keyframe;
+        self.since_keyframe = target - keyframe_idx + 1;
+        steps
+    }
+}
+
+// Deltas are the total length, followed by pairs of unchanged byte runs and
+// changed bytes: (LEB128 zero count, LEB128 literal count, literal bytes).
+fn encode_delta(raw: &[u8], base: &[u8]) -> Vec<u8> {
+    let xor = |idx: usize| raw[idx] ^ base.get(idx).copied().unwrap_or(0);
+    let mut out = Vec::new();
+    write_varint(&mut out, raw.len());
+    let mut idx = 0;
+    while idx < raw.len() {
+        let zeros_start = idx;
+        while idx < raw.len() && xor(idx) == 0 {
+            idx += 1;
+        }
+        if idx == raw.len() {
+            break;
+        }
+        let literal_start = idx;
+        while idx < raw.len() && xor(idx) != 0 {
+            idx += 1;
+        }
+        write_varint(&mut out, literal_start - zeros_start);
+        write_varint(&mut out, idx - literal_start);
+        out.extend((literal_start..idx).map(xor));
+    }
+    out
+}
+
+fn decode_delta(mut delta: &[u8], base: &[u8]) -> Vec<u8> {
+    let delta = &mut delta;
+    let len = read_varint(delta);
+    let mut out = Vec::with_capacity(len);
+    while !delta.is_empty() {
+        let zeros = read_varint(delta);
+        out.resize(out.len() + zeros, 0);
+        let literals = read_varint(delta);
+        let (bytes, rest) = delta.split_at(literals);
+        out.extend_from_slice(bytes);
+        *delta = rest;
+    }
+    out.resize(len, 0);
+    for (byte, &base) in out.iter_mut().zip(base) {
+        *byte ^= base;
+    }
+    out
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    while let Some((&byte, rest)) = bytes.split_first() {
+        *bytes = rest;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    value
+}
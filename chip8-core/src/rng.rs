@@ -0,0 +1,229 @@
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, vec::Vec};
+use core::fmt;
+
+// Source of the random bytes `CXNN` masks with NN.
+pub trait Rng: RngClone + Send + fmt::Debug {
+    fn next_byte(&mut self) -> u8;
+    // Everything needed to continue the same sequence, for save states.
+    fn state(&self) -> u64;
+    fn set_state(&mut self, state: u64);
+}
+
+// Lets a CPU with a boxed `Rng` be cloned. Every `Rng` that's `Clone`
+// implements it.
+pub trait RngClone {
+    #[cfg(feature = "alloc")]
+    fn clone_box(&self) -> Box<dyn Rng>;
+}
+
+impl<T: Rng + Clone + 'static> RngClone for T {
+    #[cfg(feature = "alloc")]
+    #[inline]
+    fn clone_box(&self) -> Box<dyn Rng> {
+        Box::new(self.clone())
+    }
+}
+
+// The default generator: fast, with a 64-bit state that's easy to save.
+#[derive(Clone)]
+pub struct WyRand {
+    seed: u64,
+}
+
+impl WyRand {
+    // Seeded from the system's source of entropy, if there is one.
+    pub fn new() -> WyRand {
+        let mut seed = [0; 8];
+        if getrandom::getrandom(&mut seed).is_err() {
+            // only happens on targets without an entropy source, where any
+            // fixed seed is as good as another
+            seed = 0x2545_f491_4f6c_dd1du64.to_le_bytes();
+        }
+        Self::with_seed(u64::from_le_bytes(seed))
+    }
+
+    #[inline]
+    pub const fn with_seed(seed: u64) -> WyRand {
+        WyRand { seed }
+    }
+}
+
+impl Default for WyRand {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rng for WyRand {
+    #[inline]
+    fn next_byte(&mut self) -> u8 {
+        self.seed = self.seed.wrapping_add(0xa076_1d64_78bd_642f);
+        let t = (self.seed as u128).wrapping_mul((self.seed ^ 0xe703_7ed1_a0b4_28db) as u128);
+        ((t >> 64) ^ t) as u8
+    }
+
+    #[inline]
+    fn state(&self) -> u64 {
+        self.seed
+    }
+
+    #[inline]
+    fn set_state(&mut self, state: u64) {
+        self.seed = state;
+    }
+}
+
+impl fmt::Debug for WyRand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WyRand")
+            .field("seed", &format_args!("0x{:x}", self.seed))
+            .finish()
+    }
+}
+
+// Plays back a fixed list of bytes, starting over after the last one, for
+// tests that need to know what `CXNN` will produce.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug)]
+pub struct SequenceRng {
+    values: Vec<u8>,
+    next: usize,
+}
+
+#[cfg(feature = "alloc")]
+impl SequenceRng {
+    // Panics if `values` is empty.
+    pub fn new(values: impl Into<Vec<u8>>) -> SequenceRng {
+        let values = values.into();
+        assert!(!values.is_empty(), "a sequence needs at least one value");
+        SequenceRng { values, next: 0 }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Rng for SequenceRng {
+    #[inline]
+    fn next_byte(&mut self) -> u8 {
+        let value = self.values[self.next];
+        self.next = (self.next + 1) % self.values.len();
+        value
+    }
+
+    #[inline]
+    fn state(&self) -> u64 {
+        self.next as u64
+    }
+
+    #[inline]
+    fn set_state(&mut self, state: u64) {
+        self.next = (state % self.values.len() as u64) as usize;
+    }
+}
+
+// The COSMAC VIP interpreter's generator. Its 16-bit seed lives in register
+// R9: each `CXNN` steps the low byte, then adds the byte of interpreter code
+// it now points at in page 0x01 to the high byte, which is the result. The
+// outcome depends on the interpreter's own code, which isn't included here,
+// so it has to be supplied by the caller: the 512-byte CHIP-8 interpreter
+// that the VIP loads at 0x0000, as listed in the VIP's manual (VIP-311) or
+// dumped from a VIP with CHIP-8 loaded.
+#[derive(Clone)]
+pub struct CosmacVipRng {
+    page: [u8; 256],
+    seed: u16,
+}
+
+impl CosmacVipRng {
+    // `interpreter_page` is the VIP's memory from 0x0100 to 0x01FF while the
+    // CHIP-8 interpreter is loaded.
+    #[inline]
+    pub fn new(interpreter_page: [u8; 256], seed: u16) -> CosmacVipRng {
+        CosmacVipRng {
+            page: interpreter_page,
+            seed,
+        }
+    }
+
+    // Takes the page from the whole interpreter as loaded at 0x0000, or
+    // returns None if `interpreter` is shorter than 512 bytes.
+    pub fn from_interpreter(interpreter: &[u8], seed: u16) -> Option<CosmacVipRng> {
+        let page = interpreter.get(0x100..0x200)?.try_into().ok()?;
+        Some(Self::new(page, seed))
+    }
+}
+
+impl Rng for CosmacVipRng {
+    fn next_byte(&mut self) -> u8 {
+        let [mut high, mut low] = self.seed.to_be_bytes();
+        low = low.wrapping_add(1);
+        high = high.wrapping_add(self.page[low as usize]);
+        self.seed = u16::from_be_bytes([high, low]);
+        high
+    }
+
+    #[inline]
+    fn state(&self) -> u64 {
+        self.seed as u64
+    }
+
+    #[inline]
+    fn set_state(&mut self, state: u64) {
+        self.seed = state as u16;
+    }
+}
+
+impl fmt::Debug for CosmacVipRng {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CosmacVipRng")
+            .field("seed", &format_args!("0x{:04x}", self.seed))
+            .finish_non_exhaustive()
+    }
+}
+
+// The generator a CPU uses: the built-in one unless another is attached.
+#[derive(Clone)]
+pub(crate) enum RngSlot {
+    WyRand(WyRand),
+    #[cfg(feature = "alloc")]
+    Custom(Box<dyn Rng>),
+}
+
+impl RngSlot {
+    #[inline]
+    pub(crate) fn get(&mut self) -> &mut dyn Rng {
+        match self {
+            Self::WyRand(rng) => rng,
+            #[cfg(feature = "alloc")]
+            Self::Custom(rng) => rng.as_mut(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn state(&self) -> u64 {
+        match self {
+            Self::WyRand(rng) => rng.state(),
+            #[cfg(feature = "alloc")]
+            Self::Custom(rng) => rng.state(),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Clone for Box<dyn Rng> {
+    #[inline]
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+impl fmt::Debug for RngSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WyRand(rng) => rng.fmt(f),
+            #[cfg(feature = "alloc")]
+            Self::Custom(rng) => rng.fmt(f),
+        }
+    }
+}
@@ -0,0 +1,384 @@
+use core::fmt;
+
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+
+use crate::audio::Audio;
+use crate::common::{take, Chip8ModeParseError};
+use crate::cpu::{bits_to_keys, keys_to_bits, CallStack, KeyWait, SavedState, CHIP8_MEM_SIZE};
+use crate::display::{Display, HIGHRES_SCREEN_DIMENSIONS, LOWRES_SCREEN_DIMENSIONS};
+use crate::Chip8Mode;
+
+// Save states are laid out as
+//
+//     magic     "CH8S"
+//     version   u16
+//     mode      u8 (0 = COSMAC, 1 = SUPER-CHIP, 2 = XO-CHIP)
+//     sections  tag: [u8; 4], length: u32, then `length` bytes
+//
+// with every integer little-endian. Nothing depends on the features of the
+// build that wrote a state, so one taken with every mode enabled loads into a
+// COSMAC-only build as long as it fits. Readers skip sections they don't know
+// and ignore bytes past the end of the fields they do, so later versions can
+// add to the format without breaking older states.
+//
+// Version 2 added the timing accumulators and display wait to "TIME", and the
+// "AUDI" and "KEYS" sections. Version 3 added the key `FX0A` is waiting to be
+// released to "CPU ".
+pub const MAGIC: [u8; 4] = *b"CH8S";
+pub const VERSION: u16 = 3;
+
+// PC, I, V0-VF, the key register `FX0A` is waiting on (0xFF if none), the
+// random number generator's state, the SUPER-CHIP flag registers, the call
+// stack depth and return addresses, then the key `FX0A` saw pressed and is
+// waiting to be released (0xFF if none)
+const SECTION_CPU: [u8; 4] = *b"CPU ";
+// delay and sound timers, then the cycles and clock time carried over to the
+// next `CPU::run` and whether `DXYN` is waiting for the next timer tick
+const SECTION_TIMERS: [u8; 4] = *b"TIME";
+// memory from address 0, with trailing zeroes left off
+const SECTION_MEMORY: [u8; 4] = *b"MEM ";
+// high resolution flag, XO-CHIP write mask and bitplane count, then each
+// bitplane packed eight pixels per byte, most significant bit first, in the
+// current resolution
+const SECTION_DISPLAY: [u8; 4] = *b"DISP";
+// sample position, XO-CHIP pitch and the 16-byte XO-CHIP audio pattern
+const SECTION_AUDIO: [u8; 4] = *b"AUDI";
+// held keys as a 16-bit mask, key 0 in the lowest bit
+const SECTION_KEYS: [u8; 4] = *b"KEYS";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaveStateError {
+    // the data doesn't start with `MAGIC`
+    NotASaveState,
+    // written by a newer version of the format
+    UnsupportedVersion(u16),
+    // the state's mode needs a feature this build doesn't have
+    ModeNotEnabled(&'static str),
+    // the state uses more memory, call stack or display than this build has
+    Incompatible,
+    // the data ends early, is missing a section or holds invalid values
+    Corrupt,
+    // `SavedState::encode` was given a buffer shorter than `encoded_len`
+    BufferTooSmall,
+}
+
+impl fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotASaveState => write!(f, "not a save state"),
+            Self::UnsupportedVersion(version) => {
+                write!(
+                    f,
+                    "save state version {} is newer than supported ({})",
+                    version, VERSION
+                )
+            }
+            Self::ModeNotEnabled(feature) => {
+                write!(
+                    f,
+                    "save state needs the `{}` feature to be enabled",
+                    feature
+                )
+            }
+            Self::Incompatible => write!(f, "save state doesn't fit in this build"),
+            Self::Corrupt => write!(f, "save state is corrupt"),
+            Self::BufferTooSmall => write!(f, "buffer too small for save state"),
+        }
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for SaveStateError {}
+
+// Writes into a fixed buffer, counting the space needed even once it's full
+// so that the same code computes `encoded_len`.
+struct Writer<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl Writer<'_> {
+    fn put(&mut self, bytes: &[u8]) {
+        if let Some(dest) = self.buf.get_mut(self.len..self.len + bytes.len()) {
+            dest.copy_from_slice(bytes);
+        }
+        self.len += bytes.len();
+    }
+
+    fn section(&mut self, tag: [u8; 4], contents: impl FnOnce(&mut Self)) {
+        self.put(&tag);
+        let len_pos = self.len;
+        self.put(&[0; 4]);
+        contents(self);
+        let len = (self.len - len_pos - 4) as u32;
+        if let Some(dest) = self.buf.get_mut(len_pos..len_pos + 4) {
+            dest.copy_from_slice(&len.to_le_bytes());
+        }
+    }
+}
+
+fn read_u8(bytes: &mut &[u8]) -> Result<u8, SaveStateError> {
+    Ok(take(bytes, 1).ok_or(SaveStateError::Corrupt)?[0])
+}
+
+fn read_u16(bytes: &mut &[u8]) -> Result<u16, SaveStateError> {
+    let bytes = take(bytes, 2).ok_or(SaveStateError::Corrupt)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(bytes: &mut &[u8]) -> Result<u32, SaveStateError> {
+    let bytes = take(bytes, 4).ok_or(SaveStateError::Corrupt)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_array<const N: usize>(bytes: &mut &[u8]) -> Result<[u8; N], SaveStateError> {
+    let mut array = [0; N];
+    array.copy_from_slice(take(bytes, N).ok_or(SaveStateError::Corrupt)?);
+    Ok(array)
+}
+
+impl SavedState {
+    // Size of the buffer `encode` needs.
+    pub fn encoded_len(&self) -> usize {
+        let mut writer = Writer {
+            buf: &mut [],
+            len: 0,
+        };
+        self.write(&mut writer);
+        writer.len
+    }
+
+    // Writes the state to the start of `buf`, returning how many bytes were
+    // used.
+    pub fn encode(&self, buf: &mut [u8]) -> Result<usize, SaveStateError> {
+        let mut writer = Writer { buf, len: 0 };
+        self.write(&mut writer);
+        if writer.len > writer.buf.len() {
+            return Err(SaveStateError::BufferTooSmall);
+        }
+        Ok(writer.len)
+    }
+
+    #[cfg(feature = "alloc")]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0; self.encoded_len()];
+        self.encode(&mut bytes)
+            .expect("buffer is sized by encoded_len");
+        bytes
+    }
+
+    fn write(&self, w: &mut Writer) {
+        w.put(&MAGIC);
+        w.put(&VERSION.to_le_bytes());
+        w.put(&[self.mode as u8]);
+
+        w.section(SECTION_CPU, |w| {
+            w.put(&self.pc.to_le_bytes());
+            w.put(&self.index.to_le_bytes());
+            w.put(self.registers.as_slice());
+            let [wait_reg, wait_key] = KeyWait::to_bytes(self.key_wait);
+            w.put(&[wait_reg]);
+            w.put(&self.rng_state.to_le_bytes());
+            cfg_if::cfg_if! {
+                if #[cfg(any(feature = "super-chip", feature = "xo-chip"))] {
+                    w.put(self.persistent_registers.as_slice());
+                } else {
+                    w.put(&[0; 16]);
+                }
+            }
+            w.put(&(self.call_stack.len() as u16).to_le_bytes());
+            for addr in &self.call_stack {
+                w.put(&addr.to_le_bytes());
+            }
+            w.put(&[wait_key]);
+        });
+
+        w.section(SECTION_TIMERS, |w| {
+            w.put(&[self.delay_timer, self.sound_timer]);
+            w.put(&self.cycles_pending.to_le_bytes());
+            w.put(&self.timers_pending.to_le_bytes());
+            w.put(&[self.vblank_wait as u8]);
+        });
+
+        w.section(SECTION_MEMORY, |w| {
+            let len = self
+                .memory
+                .iter()
+                .rposition(|&byte| byte != 0)
+                .map_or(0, |idx| idx + 1);
+            w.put(&self.memory[..len]);
+        });
+
+        w.section(SECTION_DISPLAY, |w| {
+            let screen = &self.screen;
+            let planes = screen.planes();
+            let high_res = screen.dimensions() == HIGHRES_SCREEN_DIMENSIONS;
+            w.put(&[high_res as u8, screen.write_mask(), planes]);
+            let (width, height) = screen.dimensions();
+            for plane in 0..planes {
+                let mut byte = 0;
+                for idx in 0..width * height {
+                    let pixel = screen.logical_pixel(idx % width, idx / width);
+                    byte = byte << 1 | (pixel >> plane) & 1;
+                    if idx % 8 == 7 {
+                        w.put(&[byte]);
+                        byte = 0;
+                    }
+                }
+            }
+        });
+
+        w.section(SECTION_AUDIO, |w| {
+            w.put(&(self.audio.position() as u16).to_le_bytes());
+            w.put(&[self.audio.pitch()]);
+            w.put(&self.audio.pattern());
+        });
+
+        w.section(SECTION_KEYS, |w| {
+            w.put(&keys_to_bits(&self.input).to_le_bytes());
+        });
+    }
+
+    // Reads a state written by `encode` in any build with the same or an
+    // older format version.
+    pub fn decode(mut bytes: &[u8]) -> Result<SavedState, SaveStateError> {
+        let bytes = &mut bytes;
+        if take(bytes, MAGIC.len()) != Some(&MAGIC[..]) {
+            return Err(SaveStateError::NotASaveState);
+        }
+        let version = read_u16(bytes)?;
+        if version == 0 || version > VERSION {
+            return Err(SaveStateError::UnsupportedVersion(version));
+        }
+        let mode = Chip8Mode::try_from(read_u8(bytes)?).map_err(|err| match err {
+            Chip8ModeParseError::NotEnabled(feature) => SaveStateError::ModeNotEnabled(feature),
+            Chip8ModeParseError::InvalidMode => SaveStateError::Corrupt,
+        })?;
+
+        let mut state = SavedState {
+            mode,
+            pc: 0x200,
+            index: 0,
+            registers: enum_map::enum_map! { _ => 0 },
+            memory: [0; CHIP8_MEM_SIZE],
+            screen: Display::new(),
+            call_stack: CallStack::new(),
+            delay_timer: 0,
+            sound_timer: 0,
+            key_wait: None,
+            rng_state: 0,
+            audio: Audio::new(),
+            input: [false; 16],
+            cycles_pending: 0,
+            timers_pending: 0,
+            vblank_wait: false,
+            #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
+            persistent_registers: enum_map::enum_map! { _ => 0 },
+        };
+        let (mut has_cpu, mut has_memory) = (false, false);
+        while !bytes.is_empty() {
+            let tag = read_array::<4>(bytes)?;
+            let len = read_u32(bytes)? as usize;
+            let section = &mut take(bytes, len).ok_or(SaveStateError::Corrupt)?;
+            match tag {
+                SECTION_CPU => {
+                    state.read_cpu(section, version)?;
+                    has_cpu = true;
+                }
+                SECTION_TIMERS => state.read_timers(section, version)?,
+                SECTION_MEMORY => {
+                    // anything past the end of memory is non-zero, since
+                    // trailing zeroes aren't stored
+                    if section.len() > CHIP8_MEM_SIZE {
+                        return Err(SaveStateError::Incompatible);
+                    }
+                    state.memory[..section.len()].copy_from_slice(section);
+                    has_memory = true;
+                }
+                SECTION_DISPLAY => state.read_display(section)?,
+                SECTION_AUDIO => {
+                    let position = read_u16(section)? as usize;
+                    let pitch = read_u8(section)?;
+                    let pattern = read_array::<16>(section)?;
+                    if !state.audio.restore(&pattern, pitch, position) {
+                        return Err(SaveStateError::Incompatible);
+                    }
+                }
+                SECTION_KEYS => state.input = bits_to_keys(read_u16(section)?),
+                _ => {}
+            }
+        }
+        if !(has_cpu && has_memory) {
+            return Err(SaveStateError::Corrupt);
+        }
+        Ok(state)
+    }
+
+    fn read_cpu(&mut self, bytes: &mut &[u8], version: u16) -> Result<(), SaveStateError> {
+        self.pc = read_u16(bytes)?;
+        self.index = read_u16(bytes)?;
+        self.registers = enum_map::EnumMap::from_array(read_array::<16>(bytes)?);
+        let wait_reg = read_u8(bytes)?;
+        self.rng_state = u64::from_le_bytes(read_array::<8>(bytes)?);
+        let _persistent_registers = read_array::<16>(bytes)?;
+        #[cfg(any(feature = "super-chip", feature = "xo-chip"))]
+        {
+            self.persistent_registers = enum_map::EnumMap::from_array(_persistent_registers);
+        }
+        let depth = read_u16(bytes)? as usize;
+        if CallStack::CAPACITY.map_or(false, |capacity| depth > capacity) {
+            return Err(SaveStateError::Incompatible);
+        }
+        for _ in 0..depth {
+            self.call_stack.push(read_u16(bytes)?);
+        }
+        let wait_key = if version >= 3 { read_u8(bytes)? } else { 0xFF };
+        self.key_wait = KeyWait::from_bytes([wait_reg, wait_key]).ok_or(SaveStateError::Corrupt)?;
+        Ok(())
+    }
+
+    fn read_timers(&mut self, bytes: &mut &[u8], version: u16) -> Result<(), SaveStateError> {
+        self.delay_timer = read_u8(bytes)?;
+        self.sound_timer = read_u8(bytes)?;
+        if version >= 2 {
+            self.cycles_pending = i64::from_le_bytes(read_array::<8>(bytes)?);
+            self.timers_pending = u64::from_le_bytes(read_array::<8>(bytes)?);
+            self.vblank_wait = read_u8(bytes)? != 0;
+        }
+        Ok(())
+    }
+
+    fn read_display(&mut self, bytes: &mut &[u8]) -> Result<(), SaveStateError> {
+        let [high_res, write_mask, planes] = read_array::<3>(bytes)?;
+        let screen = &mut self.screen;
+        if high_res > 1 || !screen.set_high_res(high_res == 1) {
+            return Err(SaveStateError::Incompatible);
+        }
+        screen.set_write_mask(write_mask);
+        let (width, height) = if high_res == 1 {
+            HIGHRES_SCREEN_DIMENSIONS
+        } else {
+            LOWRES_SCREEN_DIMENSIONS
+        };
+        let plane_len = width * height / 8;
+        for plane in 0..planes {
+            let packed = take(bytes, plane_len).ok_or(SaveStateError::Corrupt)?;
+            if plane >= screen.planes() {
+                // only blank bitplanes can be dropped without losing anything
+                if packed.iter().any(|&byte| byte != 0) {
+                    return Err(SaveStateError::Incompatible);
+                }
+                continue;
+            }
+            for idx in 0..width * height {
+                if packed[idx / 8] & (0x80 >> (idx % 8)) != 0 {
+                    let (x, y) = (idx % width, idx / width);
+                    let pixel = screen.logical_pixel(x, y) | 1 << plane;
+                    screen.set_logical_pixel(x, y, pixel);
+                }
+            }
+        }
+        screen.finish_drawing();
+        Ok(())
+    }
+}
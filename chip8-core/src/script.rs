@@ -0,0 +1,215 @@
+use crate::CPU;
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyAction {
+    Press,
+    Release,
+}
+
+// A key pressed or released at the start of a frame, counting the frame the
+// script started in as 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScriptEvent {
+    pub frame: u32,
+    pub action: KeyAction,
+    pub key: u8,
+}
+
+// Key presses and releases to replay on a `CPU` with `CPU::play_input`. As
+// text, one event per line:
+//
+//     # frame, action, hex key
+//     10 press 1
+//     12 release 1
+//
+// Blank lines and anything after a `#` are ignored.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InputScript {
+    // ordered by frame, and otherwise by when they were added
+    events: Vec<ScriptEvent>,
+}
+
+impl InputScript {
+    #[inline]
+    pub fn new() -> InputScript {
+        InputScript::default()
+    }
+
+    // Adds an event after any others in the same frame. Keys past 0xF are
+    // ignored, as by `CPU::press_key`.
+    pub fn push(&mut self, event: ScriptEvent) {
+        if event.key > 0xF {
+            return;
+        }
+        let idx = self.events.partition_point(|e| e.frame <= event.frame);
+        self.events.insert(idx, event);
+    }
+
+    #[inline]
+    pub fn press(mut self, frame: u32, key: u8) -> InputScript {
+        self.push(ScriptEvent {
+            frame,
+            action: KeyAction::Press,
+            key,
+        });
+        self
+    }
+
+    #[inline]
+    pub fn release(mut self, frame: u32, key: u8) -> InputScript {
+        self.push(ScriptEvent {
+            frame,
+            action: KeyAction::Release,
+            key,
+        });
+        self
+    }
+
+    // Holds `key` down for `frames` frames.
+    #[inline]
+    pub fn tap(self, frame: u32, key: u8, frames: u32) -> InputScript {
+        self.press(frame, key).release(frame + frames, key)
+    }
+
+    #[inline]
+    pub fn events(&self) -> &[ScriptEvent] {
+        &self.events
+    }
+
+    // Removes the events from `frame` on.
+    pub fn truncate(&mut self, frame: u32) {
+        let len = self.events.partition_point(|e| e.frame < frame);
+        self.events.truncate(len);
+    }
+
+    // The frame the last event happens in.
+    #[inline]
+    pub fn last_frame(&self) -> Option<u32> {
+        self.events.last().map(|event| event.frame)
+    }
+}
+
+impl fmt::Display for InputScript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for event in &self.events {
+            let action = match event.action {
+                KeyAction::Press => "press",
+                KeyAction::Release => "release",
+            };
+            writeln!(f, "{} {} {:X}", event.frame, action, event.key)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InputScriptParseError {
+    // counting from 1
+    pub line: usize,
+}
+impl fmt::Display for InputScriptParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}: expected `<frame> press|release <key>`",
+            self.line
+        )
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for InputScriptParseError {}
+
+impl FromStr for InputScript {
+    type Err = InputScriptParseError;
+    fn from_str(s: &str) -> Result<Self, InputScriptParseError> {
+        let mut script = InputScript::new();
+        for (idx, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut words = line.split_whitespace();
+            let first = match words.next() {
+                Some(first) => first,
+                None => continue,
+            };
+            let event = (|| {
+                let frame = first.parse().ok()?;
+                let action = match words.next()? {
+                    action if action.eq_ignore_ascii_case("press") => KeyAction::Press,
+                    action if action.eq_ignore_ascii_case("release") => KeyAction::Release,
+                    _ => return None,
+                };
+                let key = u8::from_str_radix(words.next()?, 16)
+                    .ok()
+                    .filter(|&key| key <= 0xF)?;
+                words
+                    .next()
+                    .is_none()
+                    .then(|| ScriptEvent { frame, action, key })
+            })();
+            script.push(event.ok_or(InputScriptParseError { line: idx + 1 })?);
+        }
+        Ok(script)
+    }
+}
+
+// A script being replayed by a `CPU`.
+#[derive(Clone, Debug)]
+pub struct InputReplay {
+    script: InputScript,
+    next: usize,
+    frame: u32,
+}
+
+impl InputReplay {
+    #[inline]
+    pub(crate) fn new(script: InputScript) -> InputReplay {
+        InputReplay {
+            script,
+            next: 0,
+            frame: 0,
+        }
+    }
+
+    // Frames started since the script did.
+    #[inline]
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.next == self.script.events.len()
+    }
+
+    #[inline]
+    pub fn script(&self) -> &InputScript {
+        &self.script
+    }
+
+    #[inline]
+    pub(crate) fn into_script(self) -> InputScript {
+        self.script
+    }
+
+    // Applies the events of the current frame that haven't been yet.
+    pub(crate) fn apply(&mut self, cpu: &mut CPU) {
+        while let Some(event) = self.script.events.get(self.next) {
+            if event.frame > self.frame {
+                break;
+            }
+            match event.action {
+                KeyAction::Press => cpu.press_key(event.key),
+                KeyAction::Release => cpu.release_key(event.key),
+            }
+            self.next += 1;
+        }
+    }
+
+    #[inline]
+    pub(crate) fn next_frame(&mut self) {
+        self.frame = self.frame.saturating_add(1);
+    }
+}
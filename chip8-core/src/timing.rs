@@ -0,0 +1,179 @@
+use crate::CPU;
+use core::fmt;
+use core::time::Duration;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Delay and sound timers count down at 60 Hz on every platform.
+pub const TIMER_SPEED: u32 = 60;
+
+pub(crate) const NANOS_PER_SEC: u64 = 1_000_000_000;
+// Pending cycles are counted in units of 1 / CYCLE of a cycle. Both one
+// nanosecond and one timer tick are a whole number of units at any integer
+// clock speed, so timing is exact no matter how the host splits up time.
+pub(crate) const CYCLE: i64 = NANOS_PER_SEC as i64 * TIMER_SPEED as i64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ClockSpeed {
+    // a fixed number of cycles per second, spread evenly between timer ticks
+    Hertz(u32),
+    // Octo's "tickrate": a fixed number of cycles run all at once on every
+    // timer tick
+    InstructionsPerFrame(u32),
+}
+
+impl ClockSpeed {
+    // Average number of cycles executed per second.
+    #[inline]
+    pub fn cycles_per_second(&self) -> u32 {
+        match *self {
+            Self::Hertz(hz) => hz,
+            Self::InstructionsPerFrame(ipf) => ipf.saturating_mul(TIMER_SPEED),
+        }
+    }
+}
+
+impl Default for ClockSpeed {
+    #[inline]
+    fn default() -> Self {
+        Self::Hertz(500)
+    }
+}
+
+impl fmt::Display for ClockSpeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Hertz(hz) => write!(f, "{} Hz", hz),
+            Self::InstructionsPerFrame(ipf) => write!(f, "{} instructions per frame", ipf),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClockSpeedParseError;
+impl fmt::Display for ClockSpeedParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected a clock speed like `500hz` or `15ipf`")
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for ClockSpeedParseError {}
+
+impl core::str::FromStr for ClockSpeed {
+    type Err = ClockSpeedParseError;
+    fn from_str(s: &str) -> Result<Self, ClockSpeedParseError> {
+        let s = s.trim();
+        let strip_suffix = |suffix: &str| {
+            let split = s.len().checked_sub(suffix.len())?;
+            if s.is_char_boundary(split) && s[split..].eq_ignore_ascii_case(suffix) {
+                s[..split].trim_end().parse().ok()
+            } else {
+                None
+            }
+        };
+        if let Some(ipf) = strip_suffix("ipf") {
+            Ok(Self::InstructionsPerFrame(ipf))
+        } else if let Some(hz) = strip_suffix("hz") {
+            Ok(Self::Hertz(hz))
+        } else {
+            s.parse().map(Self::Hertz).map_err(|_| ClockSpeedParseError)
+        }
+    }
+}
+
+// How long `CPU::run` may execute for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Budget {
+    // emulated time
+    Time(Duration),
+    // a number of 60 Hz timer ticks
+    Frames(u32),
+}
+
+impl Budget {
+    // The budget in nanoseconds times TIMER_SPEED, which makes a timer tick
+    // exactly NANOS_PER_SEC long.
+    pub(crate) fn clock_units(&self) -> u64 {
+        match *self {
+            Self::Time(dur) => {
+                let nanos = u64::try_from(dur.as_nanos()).unwrap_or(u64::MAX);
+                nanos.saturating_mul(TIMER_SPEED as u64)
+            }
+            Self::Frames(frames) => frames as u64 * NANOS_PER_SEC,
+        }
+    }
+}
+
+// Which events end `CPU::run` before its budget is used up. Exiting always
+// does. Otherwise, waiting for a key or the next timer tick just idles until
+// the budget runs out.
+#[derive(Clone, Copy, Default)]
+pub struct StopConditions<'a> {
+    pub key_wait: bool,
+    pub vblank: bool,
+    // stop on an instruction that jumps to itself
+    pub infinite_loop: bool,
+    // checked after every instruction, as for breakpoints
+    pub predicate: Option<&'a dyn Fn(&CPU) -> bool>,
+}
+
+impl<'a> StopConditions<'a> {
+    #[inline]
+    pub fn until(predicate: &'a dyn Fn(&CPU) -> bool) -> Self {
+        StopConditions {
+            predicate: Some(predicate),
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub(crate) fn stops_on(&self, reason: StopReason) -> bool {
+        match reason {
+            StopReason::BudgetExhausted => false,
+            StopReason::WaitingForKey => self.key_wait,
+            StopReason::WaitingForVBlank => self.vblank,
+            _ => true,
+        }
+    }
+}
+
+// Why execution stopped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum StopReason {
+    // every cycle allotted was used
+    BudgetExhausted,
+    // `FX0A` is waiting for a key press
+    WaitingForKey,
+    // `DXYN` is waiting for the next timer tick (the display wait quirk)
+    WaitingForVBlank,
+    // `00FD` exited the interpreter
+    Exited,
+    // the stop predicate returned true, with the PC at the time
+    PredicateHit(u16),
+    // the instruction at this address jumps to itself
+    InfiniteLoop(u16),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FrameReport {
+    pub instructions: u32,
+    // a clear, draw, scroll or resolution change was executed this frame
+    pub screen_changed: bool,
+    // the sound timer is still running at the end of the frame
+    pub sound_active: bool,
+    // why execution last stopped during the frame
+    pub stop_reason: StopReason,
+}
+
+impl FrameReport {
+    pub(crate) const EMPTY: FrameReport = FrameReport {
+        instructions: 0,
+        screen_changed: false,
+        sound_active: false,
+        stop_reason: StopReason::BudgetExhausted,
+    };
+}
@@ -0,0 +1,429 @@
+use crate::observer::Chip8Observer;
+use crate::{DecodedInstruction, Register, CPU};
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::io;
+
+// Binary traces start with the magic and version, followed by one
+// `RECORD_LEN`-byte record per instruction.
+pub const MAGIC: [u8; 4] = *b"CH8T";
+pub const VERSION: u16 = 1;
+pub const HEADER_LEN: usize = 6;
+pub const RECORD_LEN: usize = 27;
+
+// The machine state right after the instruction at `pc` ran. Recording the
+// state after each instruction means the first record that differs between
+// two traces names the instruction that went wrong.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub pc: u16,
+    pub opcode: u16,
+    // the word after the opcode, which only `F000 NNNN` uses; zero otherwise
+    pub operand: u16,
+    pub registers: [u8; 16],
+    pub index: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub stack_depth: u8,
+}
+
+impl TraceRecord {
+    // Records `cpu` just after it executed `opcode` from `pc`.
+    pub fn capture(cpu: &CPU, pc: u16, opcode: u16) -> TraceRecord {
+        let operand = if opcode == 0xF000 {
+            cpu.read_memory_word(pc.wrapping_add(2)).unwrap_or(0)
+        } else {
+            0
+        };
+        let mut registers = [0; 16];
+        for (value, (_, &reg)) in registers.iter_mut().zip(cpu.registers.iter()) {
+            *value = reg;
+        }
+        TraceRecord {
+            pc,
+            opcode,
+            operand,
+            registers,
+            index: cpu.index,
+            delay_timer: cpu.delay_timer,
+            sound_timer: cpu.sound_timer,
+            stack_depth: cpu.call_stack.len() as u8,
+        }
+    }
+
+    #[inline]
+    fn is_long(&self) -> bool {
+        self.opcode == 0xF000
+    }
+
+    #[inline]
+    pub fn instruction(&self) -> Option<DecodedInstruction> {
+        let [a, b] = self.opcode.to_be_bytes();
+        let [c, d] = self.operand.to_be_bytes();
+        DecodedInstruction::decode(&[a, b, c, d])
+    }
+
+    // Every field in a fixed layout, multi-byte values little-endian.
+    pub fn encode(&self) -> [u8; RECORD_LEN] {
+        let mut out = [0; RECORD_LEN];
+        out[0..2].copy_from_slice(&self.pc.to_le_bytes());
+        out[2..4].copy_from_slice(&self.opcode.to_le_bytes());
+        out[4..6].copy_from_slice(&self.operand.to_le_bytes());
+        out[6..22].copy_from_slice(&self.registers);
+        out[22..24].copy_from_slice(&self.index.to_le_bytes());
+        out[24] = self.delay_timer;
+        out[25] = self.sound_timer;
+        out[26] = self.stack_depth;
+        out
+    }
+
+    pub fn decode(bytes: &[u8; RECORD_LEN]) -> TraceRecord {
+        let word = |at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+        let mut registers = [0; 16];
+        registers.copy_from_slice(&bytes[6..22]);
+        TraceRecord {
+            pc: word(0),
+            opcode: word(2),
+            operand: word(4),
+            registers,
+            index: word(22),
+            delay_timer: bytes[24],
+            sound_timer: bytes[25],
+            stack_depth: bytes[26],
+        }
+    }
+
+    // The fields that differ from `other`, in the order they're printed.
+    pub fn differences<'a>(
+        &'a self,
+        other: &'a TraceRecord,
+    ) -> impl Iterator<Item = TraceField> + 'a {
+        let fixed = [
+            (TraceField::Pc, self.pc != other.pc),
+            (
+                TraceField::Opcode,
+                (self.opcode, self.operand) != (other.opcode, other.operand),
+            ),
+        ];
+        let registers = (0..16u8).filter_map(move |idx| {
+            let reg = Register::try_from(idx).ok()?;
+            (self.registers[idx as usize] != other.registers[idx as usize])
+                .then(|| TraceField::Register(reg))
+        });
+        let rest = [
+            (TraceField::Index, self.index != other.index),
+            (
+                TraceField::DelayTimer,
+                self.delay_timer != other.delay_timer,
+            ),
+            (
+                TraceField::SoundTimer,
+                self.sound_timer != other.sound_timer,
+            ),
+            (
+                TraceField::StackDepth,
+                self.stack_depth != other.stack_depth,
+            ),
+        ];
+        let differs = |&(field, differs): &(TraceField, bool)| differs.then(|| field);
+        fixed
+            .into_iter()
+            .filter_map(move |entry| differs(&entry))
+            .chain(registers)
+            .chain(rest.into_iter().filter_map(move |entry| differs(&entry)))
+    }
+}
+
+// One line per record:
+//
+//     PC:0200 OP:607B V:7B000000000000000000000000000000 I:0000 DT:00 ST:00 SP:00 ; v0 := 0x7B
+//
+// with the operand of `F000 NNNN` appended to its opcode, and the Octo
+// mnemonic after the semicolon for people to read. Parsing ignores it.
+impl fmt::Display for TraceRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PC:{:04X} OP:{:04X}", self.pc, self.opcode)?;
+        if self.is_long() {
+            write!(f, "{:04X}", self.operand)?;
+        }
+        write!(f, " V:")?;
+        for value in self.registers {
+            write!(f, "{:02X}", value)?;
+        }
+        write!(
+            f,
+            " I:{:04X} DT:{:02X} ST:{:02X} SP:{:02X} ; ",
+            self.index, self.delay_timer, self.sound_timer, self.stack_depth
+        )?;
+        match self.instruction() {
+            Some(inst) => write!(f, "{}", inst.octo()),
+            None => write!(f, "0x{:02X} 0x{:02X}", self.opcode >> 8, self.opcode & 0xFF),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceParseError {
+    // the line of a text trace, or the record of a binary one, counting from 1;
+    // 0 is the header of a binary trace
+    pub record: usize,
+}
+impl fmt::Display for TraceParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "malformed trace record {}", self.record)
+    }
+}
+#[cfg(feature = "std")]
+impl std::error::Error for TraceParseError {}
+
+impl FromStr for TraceRecord {
+    type Err = TraceParseError;
+    fn from_str(s: &str) -> Result<Self, TraceParseError> {
+        const ERR: TraceParseError = TraceParseError { record: 1 };
+        let fields = s.split(';').next().unwrap_or_default();
+        let mut fields = fields.split_whitespace();
+        let mut field = |name: &str, digits: &[usize]| {
+            let value = fields.next()?.strip_prefix(name)?.strip_prefix(':')?;
+            if !value.is_ascii() || !digits.contains(&value.len()) {
+                return None;
+            }
+            Some(value)
+        };
+        let hex = |value: &str| u32::from_str_radix(value, 16).ok();
+
+        let pc = hex(field("PC", &[4]).ok_or(ERR)?);
+        let op = field("OP", &[4, 8]).ok_or(ERR)?;
+        let (opcode, operand) = match op.split_at(4) {
+            (opcode, "") => (hex(opcode), Some(0)),
+            (opcode, operand) => (hex(opcode), hex(operand)),
+        };
+        let regs = field("V", &[32]).ok_or(ERR)?;
+        let mut registers = [0; 16];
+        for (idx, value) in registers.iter_mut().enumerate() {
+            let digits = regs.get(idx * 2..idx * 2 + 2).ok_or(ERR)?;
+            *value = hex(digits).ok_or(ERR)? as u8;
+        }
+        let index = hex(field("I", &[4]).ok_or(ERR)?);
+        let delay_timer = hex(field("DT", &[2]).ok_or(ERR)?);
+        let sound_timer = hex(field("ST", &[2]).ok_or(ERR)?);
+        let stack_depth = hex(field("SP", &[2]).ok_or(ERR)?);
+        if fields.next().is_some() {
+            return Err(ERR);
+        }
+
+        let record = (|| {
+            Some(TraceRecord {
+                pc: pc? as u16,
+                opcode: opcode? as u16,
+                operand: operand? as u16,
+                registers,
+                index: index? as u16,
+                delay_timer: delay_timer? as u8,
+                sound_timer: sound_timer? as u8,
+                stack_depth: stack_depth? as u8,
+            })
+        })();
+        match record {
+            // only `F000` carries an operand
+            Some(record) if record.is_long() == (op.len() == 8) => Ok(record),
+            _ => Err(ERR),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceField {
+    Pc,
+    Opcode,
+    Register(Register),
+    Index,
+    DelayTimer,
+    SoundTimer,
+    StackDepth,
+}
+impl fmt::Display for TraceField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pc => write!(f, "PC"),
+            Self::Opcode => write!(f, "OP"),
+            Self::Register(reg) => write!(f, "{}", reg),
+            Self::Index => write!(f, "I"),
+            Self::DelayTimer => write!(f, "DT"),
+            Self::SoundTimer => write!(f, "ST"),
+            Self::StackDepth => write!(f, "SP"),
+        }
+    }
+}
+
+// Where two traces stop agreeing. A record is None if that trace ended first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    // number of records both traces agree on before the divergence
+    pub index: usize,
+    pub left: Option<TraceRecord>,
+    pub right: Option<TraceRecord>,
+}
+
+// Finds the first record where `left` and `right` differ, or None if they're
+// identical.
+pub fn first_divergence<L, R>(left: L, right: R) -> Option<Divergence>
+where
+    L: IntoIterator<Item = TraceRecord>,
+    R: IntoIterator<Item = TraceRecord>,
+{
+    let (mut left, mut right) = (left.into_iter(), right.into_iter());
+    let mut index = 0;
+    loop {
+        match (left.next(), right.next()) {
+            (None, None) => return None,
+            (l, r) if l != r => {
+                return Some(Divergence {
+                    index,
+                    left: l,
+                    right: r,
+                })
+            }
+            _ => index += 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceFormat {
+    // one `TraceRecord` per line, as formatted by its `Display` impl
+    Text,
+    // the header followed by `TraceRecord::encode`d records
+    Binary,
+}
+
+// Reads a whole trace in either format, telling them apart by the binary
+// header. Blank lines and lines starting with `#` in text traces are skipped.
+#[cfg(feature = "alloc")]
+pub fn parse_trace(data: &[u8]) -> Result<Vec<TraceRecord>, TraceParseError> {
+    if let Some(records) = data.strip_prefix(&MAGIC[..]) {
+        let version = records.get(..2).map(|v| u16::from_le_bytes([v[0], v[1]]));
+        if version != Some(VERSION) {
+            return Err(TraceParseError { record: 0 });
+        }
+        let records = &records[2..];
+        if records.len() % RECORD_LEN != 0 {
+            return Err(TraceParseError {
+                record: records.len() / RECORD_LEN + 1,
+            });
+        }
+        return Ok(records
+            .chunks_exact(RECORD_LEN)
+            .map(|chunk| TraceRecord::decode(chunk.try_into().unwrap()))
+            .collect());
+    }
+
+    let text = core::str::from_utf8(data).map_err(|e| TraceParseError {
+        record: data[..e.valid_up_to()]
+            .iter()
+            .filter(|&&b| b == b'\n')
+            .count()
+            + 1,
+    })?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(idx, line)| {
+            line.parse()
+                .map_err(|_| TraceParseError { record: idx + 1 })
+        })
+        .collect()
+}
+
+// Observer that hands a `TraceRecord` for every executed instruction to a
+// callback.
+pub struct Tracer<F: FnMut(&TraceRecord)> {
+    callback: F,
+}
+
+impl<F: FnMut(&TraceRecord)> Tracer<F> {
+    #[inline]
+    pub fn new(callback: F) -> Tracer<F> {
+        Tracer { callback }
+    }
+}
+
+impl<F: FnMut(&TraceRecord)> Chip8Observer for Tracer<F> {
+    #[inline]
+    fn instruction_executed(&mut self, cpu: &CPU, pc: u16, opcode: u16) {
+        (self.callback)(&TraceRecord::capture(cpu, pc, opcode));
+    }
+}
+
+// Observer that emits every executed instruction as a `TRACE`-level `tracing`
+// event with target `chip8_core::trace`, carrying the record's fields and its
+// text line as the message.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TraceLogger;
+
+impl Chip8Observer for TraceLogger {
+    fn instruction_executed(&mut self, cpu: &CPU, pc: u16, opcode: u16) {
+        let record = TraceRecord::capture(cpu, pc, opcode);
+        tracing::trace!(
+            target: "chip8_core::trace",
+            pc = record.pc,
+            opcode = record.opcode,
+            index = record.index,
+            delay_timer = record.delay_timer,
+            sound_timer = record.sound_timer,
+            stack_depth = record.stack_depth,
+            "{}",
+            record
+        );
+    }
+}
+
+// Observer that writes a trace to `out`. The first error stops the trace, and
+// is returned by `finish`.
+#[cfg(feature = "std")]
+pub struct TraceWriter<W: io::Write> {
+    out: W,
+    format: TraceFormat,
+    error: Option<io::Error>,
+}
+
+#[cfg(feature = "std")]
+impl<W: io::Write> TraceWriter<W> {
+    pub fn new(mut out: W, format: TraceFormat) -> io::Result<TraceWriter<W>> {
+        if format == TraceFormat::Binary {
+            out.write_all(&MAGIC)?;
+            out.write_all(&VERSION.to_le_bytes())?;
+        }
+        Ok(TraceWriter {
+            out,
+            format,
+            error: None,
+        })
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: io::Write> Chip8Observer for TraceWriter<W> {
+    fn instruction_executed(&mut self, cpu: &CPU, pc: u16, opcode: u16) {
+        if self.error.is_some() {
+            return;
+        }
+        let record = TraceRecord::capture(cpu, pc, opcode);
+        let res = match self.format {
+            TraceFormat::Text => writeln!(self.out, "{}", record),
+            TraceFormat::Binary => self.out.write_all(&record.encode()),
+        };
+        self.error = res.err();
+    }
+}
@@ -0,0 +1,96 @@
+use chip8_core::*;
+
+const CORAX_ROM: &[u8] = include_bytes!("./timendus-tests/3-corax+.ch8");
+
+#[test]
+fn test_operands() {
+    let inst = DecodedInstruction::decode(&[0x6A, 0x2F]).unwrap();
+    assert_eq!(inst, DecodedInstruction::SetRegister(Register::VA, 0x2F));
+    assert_eq!(inst.to_string(), "VA = 0x2F;");
+    assert_eq!(inst.octo().to_string(), "va := 0x2F");
+
+    let inst = DecodedInstruction::decode(&[0xD1, 0x25]).unwrap();
+    assert_eq!(inst.to_string(), "draw(V1, V2, 5);");
+    assert_eq!(inst.octo().to_string(), "sprite v1 v2 5");
+
+    let inst = DecodedInstruction::decode(&[0xF0, 0x00, 0x12, 0x34]).unwrap();
+    assert_eq!(inst, DecodedInstruction::SetLongIndex(0x1234));
+    assert_eq!(inst.octo().to_string(), "i := long 0x1234");
+    // the long address is required
+    assert_eq!(DecodedInstruction::decode(&[0xF0, 0x00]), None);
+    assert_eq!(DecodedInstruction::decode(&[0xFF, 0xFF]), None);
+}
+
+#[test]
+fn test_availability() {
+    let mode = Chip8Mode::default();
+    assert!(DecodedInstruction::Clear.is_defined_for(mode));
+    // only the extensions to CHIP-8 have a high-resolution mode
+    #[cfg(feature = "cosmac")]
+    let cosmac = mode == Chip8Mode::Cosmac;
+    #[cfg(not(feature = "cosmac"))]
+    let cosmac = false;
+    assert_eq!(DecodedInstruction::HighRes.is_defined_for(mode), !cosmac);
+}
+
+// Every opcode the interpreter knows, in any mode, decodes, and nothing else
+// does.
+#[test]
+fn test_matches_interpreter() {
+    let mut emu = CPU::default();
+    for opcode in 0..=0xFFFFu16 {
+        let [hi, lo] = opcode.to_be_bytes();
+        emu.memory[0x200..0x204].copy_from_slice(&[hi, lo, 0, 0]);
+        emu.pc = 0x200;
+        let known = !matches!(emu.step(), Err(Error::UnknownOpcode(_)));
+        let decoded = DecodedInstruction::decode(&[hi, lo, 0, 0]);
+        assert_eq!(decoded.is_some(), known, "{:04X}", opcode);
+        if emu.key_wait().is_some() {
+            emu.press_key(0);
+            emu.release_key(0);
+        }
+        if opcode == 0x00FD {
+            emu = CPU::default();
+        }
+    }
+}
+
+#[test]
+fn test_reassemble() {
+    let mut pos = 0;
+    while pos + 1 < CORAX_ROM.len() {
+        let bytes = &CORAX_ROM[pos..];
+        if let Some(inst) = DecodedInstruction::decode(bytes) {
+            let mut buf = [0; 4];
+            let size = inst.encode(&mut buf).unwrap();
+            assert_eq!(buf[..size], bytes[..size], "{} at {:#X}", inst, pos);
+            pos += size;
+        } else {
+            pos += 2;
+        }
+    }
+}
+
+// Every opcode that decodes encodes back to the same bytes.
+#[test]
+fn test_round_trip() {
+    for opcode in 0..=0xFFFFu16 {
+        let [hi, lo] = opcode.to_be_bytes();
+        let bytes = [hi, lo, 0x12, 0x34];
+        if let Some(inst) = DecodedInstruction::decode(&bytes) {
+            let mut buf = [0; 4];
+            let size = inst.encode(&mut buf).unwrap();
+            assert_eq!(buf[..size], bytes[..size], "{:04X} {:?}", opcode, inst);
+        }
+    }
+
+    // Octo has no syntax for 9XYN or FX02 with a nibble other than 0
+    let inst = DecodedInstruction::decode(&[0x91, 0x23]).unwrap();
+    assert_eq!(inst.to_string(), "if (V1 != V2) goto next;");
+    assert_eq!(inst.octo().to_string(), "0x91 0x23");
+    let inst = DecodedInstruction::decode(&[0x91, 0x20]).unwrap();
+    assert_eq!(inst.octo().to_string(), "if v1 == v2 then");
+    let inst = DecodedInstruction::decode(&[0xF3, 0x02]).unwrap();
+    assert_eq!(inst, DecodedInstruction::LoadAudioPattern(3));
+    assert_eq!(inst.octo().to_string(), "0xF3 0x02");
+}
@@ -0,0 +1,164 @@
+use chip8_core::display::{Display, ScreenRect, SCREEN_DIMENSIONS};
+use chip8_core::*;
+
+#[test]
+fn test_dirty_tracking() {
+    let mut display = Display::new();
+    assert!(display.is_dirty());
+    assert_eq!(
+        display.take_dirty(),
+        Some(ScreenRect::full(SCREEN_DIMENSIONS))
+    );
+    assert_eq!(display.take_dirty(), None);
+    assert_eq!(display.generation(), 0);
+
+    display.write_pixel(3, 4);
+    display.write_pixel(10, 2);
+    assert_eq!(
+        display.dirty_rect(),
+        Some(ScreenRect {
+            x: 3,
+            y: 2,
+            width: 8,
+            height: 3,
+        })
+    );
+    assert_eq!(display.generation(), 2);
+    // off-screen writes don't draw anything
+    display.write_pixel(200, 200);
+    assert_eq!(display.generation(), 2);
+    display.take_dirty();
+
+    display.invalidate();
+    assert_eq!(
+        display.take_dirty(),
+        Some(ScreenRect::full(SCREEN_DIMENSIONS))
+    );
+    assert_eq!(display.generation(), 2);
+    display.clear();
+    assert_eq!(
+        display.take_dirty(),
+        Some(ScreenRect::full(SCREEN_DIMENSIONS))
+    );
+    assert_eq!(display.generation(), 3);
+}
+
+#[test]
+fn test_generation_per_sprite() {
+    const ROM: &[u8] = &[
+        0xF0, 0x29, // 0x200: I = font sprite for V0
+        0xD0, 0x15, // 0x202: draw it at (V0, V1)
+        0xD0, 0x15, // 0x204: erase it again
+        0x00, 0xE0, // 0x206: clear the screen
+    ];
+    let mut emu = CPU::default();
+    emu.load_rom(ROM).unwrap();
+    emu.step().unwrap();
+    let start = emu.screen.generation();
+    // a whole sprite is one generation, however many pixels it has
+    emu.step().unwrap();
+    assert_eq!(emu.screen.generation(), start + 1);
+    // erasing it leaves the screen as it was, but is still a change
+    emu.step().unwrap();
+    assert_eq!(emu.screen.generation(), start + 2);
+    assert_eq!(emu.screen.read_pixel(0, 0), 0);
+    emu.step().unwrap();
+    assert_eq!(emu.screen.generation(), start + 3);
+}
+
+#[cfg(any(feature = "super-chip", feature = "xo-chip"))]
+#[test]
+fn test_dirty_scroll() {
+    let mut display = Display::new();
+    display.take_dirty();
+    display.scroll(0, 0);
+    assert!(!display.is_dirty());
+    display.scroll(4, 0);
+    assert_eq!(
+        display.take_dirty(),
+        Some(ScreenRect::full(SCREEN_DIMENSIONS))
+    );
+    assert_eq!(display.generation(), 1);
+}
+
+#[cfg(any(feature = "super-chip", feature = "xo-chip"))]
+#[test]
+fn test_scroll_sideways() {
+    let mut display = Display::new();
+    display.write_pixel(10, 3);
+    display.scroll(-4, 0);
+    assert_eq!(display.read_pixel(6, 3), 1);
+    assert_eq!(display.read_pixel(10, 3), 0);
+    display.scroll(4, 0);
+    assert_eq!(display.read_pixel(10, 3), 1);
+    assert_eq!(display.read_pixel(6, 3), 0);
+    // pixels scrolled off the edge are gone
+    display.scroll(-12, 0);
+    display.scroll(12, 0);
+    assert_eq!(display.read_pixel(10, 3), 0);
+}
+
+#[test]
+fn test_idle_screen() {
+    const ROM: &[u8] = &[
+        0xF0, 0x29, // 0x200: I = font sprite for V0
+        0xD0, 0x15, // 0x202: draw it at (V0, V1)
+        0x70, 0x08, // 0x204: V0 += 8
+        0x30, 0x20, // 0x206: skip if V0 == 32
+        0x12, 0x02, // 0x208: jump 0x202
+        0x12, 0x0A, // 0x20A: jump 0x20A
+    ];
+    let mut emu = CPU::default();
+    emu.load_rom(ROM).unwrap();
+    let mut generation = emu.screen.generation();
+    let mut idle_frames = 0;
+    let mut frames = 0;
+    while idle_frames < 5 {
+        emu.run_frame().unwrap();
+        frames += 1;
+        assert!(frames < 60, "the screen never settled");
+        if emu.screen.generation() == generation {
+            idle_frames += 1;
+        } else {
+            generation = emu.screen.generation();
+            idle_frames = 0;
+        }
+    }
+    assert!(generation > 0);
+    assert!(emu.screen.take_dirty().is_some());
+    emu.run_frame().unwrap();
+    assert!(!emu.screen.is_dirty());
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_generation_survives_replacing() {
+    // 0x200: draw 0,0,5; 0x202: jump 0x202
+    const ROM: &[u8] = &[0xD0, 0x05, 0x12, 0x02];
+    let mut emu = CPU::default();
+    emu.load_rom(ROM).unwrap();
+    let state = emu.save_state().unwrap();
+    emu.step().unwrap();
+    let drawn = emu.screen.generation();
+    assert!(drawn > 0);
+
+    // the saved screen is from before the draw, but still counts as a change
+    emu.load_state(state);
+    assert!(emu.screen.generation() > drawn);
+    assert_eq!(emu.screen.read_pixel(0, 0), 0);
+    assert!(emu.screen.is_dirty());
+    let loaded = emu.screen.generation();
+    emu.load_rom(ROM).unwrap();
+    assert!(emu.screen.generation() > loaded);
+
+    let mut display = Display::new();
+    display.write_pixel(1, 1);
+    display.take_dirty();
+    display.replace(Display::new());
+    assert_eq!(display.generation(), 2);
+    assert_eq!(display.read_pixel(1, 1), 0);
+    assert_eq!(
+        display.take_dirty(),
+        Some(ScreenRect::full(SCREEN_DIMENSIONS))
+    );
+}
@@ -0,0 +1,145 @@
+#![cfg(any(feature = "super-chip", feature = "xo-chip"))]
+
+use chip8_core::display::SCREEN_WIDTH;
+use chip8_core::*;
+
+const SPRITE_ADDR: u16 = 0x300;
+
+#[cfg(feature = "super-chip")]
+const DRAW_MODE: Chip8Mode = Chip8Mode::SuperChip;
+#[cfg(not(feature = "super-chip"))]
+const DRAW_MODE: Chip8Mode = Chip8Mode::XoChip;
+
+// A CPU in the given resolution, about to draw `sprite` with `DXY0` at (V0,
+// V1) = (x, y).
+fn make_emu(quirks: Quirks, high_res: bool, x: u8, y: u8, sprite: &[u8]) -> CPU {
+    let mut emu = CPU::with_quirks(DRAW_MODE, quirks);
+    // 0x200: switch resolution; 0x202: draw the sprite at (V0, V1)
+    let res = if high_res { 0xFF } else { 0xFE };
+    emu.load_rom(&[0x00, res, 0xD0, 0x10]).unwrap();
+    emu.step().unwrap();
+    emu.registers[Register::V0] = x;
+    emu.registers[Register::V1] = y;
+    emu.index = SPRITE_ADDR;
+    let start = SPRITE_ADDR as usize;
+    emu.memory[start..start + sprite.len()].copy_from_slice(sprite);
+    emu
+}
+
+fn draw(emu: &mut CPU) {
+    emu.pc = 0x202;
+    emu.step().unwrap();
+}
+
+// Whether a pixel is set, in the coordinates of the current resolution.
+fn lit(emu: &CPU, x: usize, y: usize) -> bool {
+    let scale = SCREEN_WIDTH / emu.screen.dimensions().0;
+    emu.screen.read_pixel((x * scale) as u8, (y * scale) as u8) != 0
+}
+
+fn lit_count(emu: &CPU) -> usize {
+    let (width, height) = emu.screen.dimensions();
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter(|&(x, y)| lit(emu, x, y))
+        .count()
+}
+
+// Row i has its left byte's bit i % 8 set and the rightmost bit of its right
+// byte, so both halves of the 16x16 sprite can be told apart.
+fn diagonal_sprite() -> [u8; 32] {
+    let mut sprite = [0; 32];
+    for (i, row) in sprite.chunks_exact_mut(2).enumerate() {
+        row[0] = 0x80 >> (i % 8);
+        row[1] = 0x01;
+    }
+    sprite
+}
+
+#[test]
+fn test_draw_16x16() {
+    let mut quirks = Quirks::SUPER_CHIP;
+    quirks.wrap = false;
+    for high_res in [true, false] {
+        for lores_tall_sprite in [true, false] {
+            if !high_res && lores_tall_sprite {
+                continue;
+            }
+            quirks.lores_tall_sprite = lores_tall_sprite;
+            let mut emu = make_emu(quirks, high_res, 4, 2, &diagonal_sprite());
+            draw(&mut emu);
+            assert_eq!(emu.registers[Register::VF], 0);
+            for i in 0..16 {
+                assert!(lit(&emu, 4 + i % 8, 2 + i), "row {}", i);
+                assert!(lit(&emu, 4 + 15, 2 + i), "row {}", i);
+            }
+            assert_eq!(lit_count(&emu), 32);
+            // every row collides when drawn again
+            draw(&mut emu);
+            let expected = if high_res { 16 } else { 1 };
+            assert_eq!(emu.registers[Register::VF], expected);
+            assert_eq!(lit_count(&emu), 0);
+        }
+    }
+}
+
+#[test]
+fn test_draw_lores_tall_sprite() {
+    let mut quirks = Quirks::SUPER_CHIP;
+    quirks.lores_tall_sprite = true;
+    let sprite = diagonal_sprite();
+    let mut emu = make_emu(quirks, false, 4, 2, &sprite);
+    draw(&mut emu);
+    // one byte per row, so the sprite is every other byte of the 16x16 one
+    for (i, &row) in sprite[..16].iter().enumerate() {
+        for bit in 0..8 {
+            assert_eq!(lit(&emu, 4 + bit, 2 + i), row & (0x80 >> bit) != 0);
+        }
+    }
+    assert!(!lit(&emu, 4 + 15, 2));
+    assert_eq!(lit_count(&emu), 16);
+    // low resolution ignores `collision_rows`
+    draw(&mut emu);
+    assert_eq!(emu.registers[Register::VF], 1);
+}
+
+#[test]
+fn test_collision_rows() {
+    let mut quirks = Quirks::SUPER_CHIP;
+    quirks.wrap = false;
+    let sprite = [0xFF; 32];
+    for collision_rows in [true, false] {
+        quirks.collision_rows = collision_rows;
+        // the bottom 8 rows are clipped off the screen
+        let mut emu = make_emu(quirks, true, 10, 56, &sprite);
+        draw(&mut emu);
+        for y in 56..64 {
+            for x in 10..26 {
+                assert!(lit(&emu, x, y), "({}, {})", x, y);
+            }
+        }
+        assert_eq!(lit_count(&emu), 16 * 8);
+        assert_eq!(
+            emu.registers[Register::VF],
+            if collision_rows { 8 } else { 0 }
+        );
+
+        // 8 collided rows and the same 8 clipped ones
+        draw(&mut emu);
+        assert_eq!(lit_count(&emu), 0);
+        assert_eq!(
+            emu.registers[Register::VF],
+            if collision_rows { 16 } else { 1 }
+        );
+
+        // drawn again 4 rows higher, 8 rows collide and only 4 are clipped
+        draw(&mut emu);
+        emu.registers[Register::V1] = 52;
+        draw(&mut emu);
+        assert_eq!(
+            emu.registers[Register::VF],
+            if collision_rows { 12 } else { 1 }
+        );
+        assert_eq!(lit_count(&emu), 16 * 4);
+    }
+}
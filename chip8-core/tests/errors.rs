@@ -0,0 +1,128 @@
+use chip8_core::quirks::{IndexIncrement, IndexOverflow};
+use chip8_core::*;
+
+fn make_emu(rom: &[u8]) -> CPU {
+    let mut emu = CPU::default();
+    emu.load_rom(rom).unwrap();
+    emu
+}
+
+#[test]
+fn test_index_overflow() {
+    // I = 0xFFF; V0 = 123; BCD V0 writes past the end of 4 KiB
+    const ROM: &[u8] = &[0xAF, 0xFF, 0x60, 123, 0xF0, 0x33];
+    let mut emu = make_emu(ROM);
+    emu.quirks.index_overflow = IndexOverflow::Fault;
+    emu.step().unwrap();
+    emu.step().unwrap();
+    let res = emu.step();
+    if cpu::CHIP8_MEM_SIZE > 0x1000 {
+        // the address is in range with XO-CHIP's 64 KiB of memory
+        res.unwrap();
+        assert_eq!(emu.read_memory_byte(0x1000), Ok(2));
+    } else {
+        assert_eq!(res, Err(Error::MemoryFault(0x204, 0x1000)));
+    }
+
+    let mut emu = make_emu(ROM);
+    emu.quirks.index_overflow = IndexOverflow::Wrap12;
+    for _ in 0..3 {
+        emu.step().unwrap();
+    }
+    assert_eq!(emu.memory[0xFFF], 1);
+    assert_eq!(emu.memory[0x000..0x002], [2, 3]);
+
+    let mut emu = make_emu(ROM);
+    emu.quirks.index_overflow = IndexOverflow::Ignore;
+    for _ in 0..3 {
+        emu.step().unwrap();
+    }
+    assert_eq!(emu.memory[0xFFF], 1);
+    assert_eq!(emu.memory[0x000..0x002], [0, 0]);
+}
+
+#[test]
+fn test_index_at_16_bits() {
+    const ROM: &[u8] = &[
+        0x60, 0x02, // 0x200: V0 = 2
+        0xF0, 0x1E, // 0x202: I += V0
+        0x61, 0x07, // 0x204: V1 = 7
+        0xF1, 0x55, // 0x206: store V0-V1 at I
+    ];
+    let full_range = cpu::CHIP8_MEM_SIZE > 0xFFFF;
+    for overflow in [
+        IndexOverflow::Fault,
+        IndexOverflow::Wrap12,
+        IndexOverflow::Wrap16,
+        IndexOverflow::Ignore,
+    ] {
+        let mut emu = make_emu(ROM);
+        emu.quirks.index_overflow = overflow;
+        emu.quirks.load_store = IndexIncrement::ByXPlusOne;
+        let first = emu.memory[0];
+        emu.step().unwrap();
+        emu.index = 0xFFFF;
+        emu.step().unwrap();
+        // I is 16 bits, so it wraps around there whatever the setting
+        assert_eq!(emu.index, 0x0001, "{:?}", overflow);
+        assert_eq!(emu.registers[Register::VF], 1);
+
+        emu.step().unwrap();
+        emu.index = 0xFFFF;
+        let res = emu.step();
+        match overflow {
+            IndexOverflow::Fault => {
+                let addr = if full_range { 0x10000 } else { 0xFFFF };
+                assert_eq!(res, Err(Error::MemoryFault(0x206, addr)));
+            }
+            IndexOverflow::Wrap12 => {
+                res.unwrap();
+                assert_eq!(emu.memory[0xFFF], 2);
+                assert_eq!(emu.memory[0x000], 7);
+                assert_eq!(emu.index, 0x001);
+            }
+            IndexOverflow::Wrap16 if !full_range => {
+                assert_eq!(res, Err(Error::MemoryFault(0x206, 0xFFFF)));
+            }
+            IndexOverflow::Wrap16 => {
+                res.unwrap();
+                assert_eq!(emu.read_memory_byte(0xFFFF), Ok(2));
+                assert_eq!(emu.memory[0x0000], 7);
+                assert_eq!(emu.index, 0x0001);
+            }
+            IndexOverflow::Ignore => {
+                res.unwrap();
+                assert_eq!(emu.memory[0x0000], first);
+                assert_eq!(emu.index, 0x0001);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_fetch_fault() {
+    // 0x200: jump 0x200
+    let mut emu = make_emu(&[0x12, 0x00]);
+    let last = (cpu::CHIP8_MEM_SIZE - 1) as u16;
+    emu.pc = last;
+    assert_eq!(emu.step(), Err(Error::MemoryFault(last, last as u32)));
+    assert_eq!(emu.pc, last);
+}
+
+#[test]
+fn test_stack_limit() {
+    // 0x200: call 0x200
+    const RECURSE_ROM: &[u8] = &[0x22, 0x00];
+    let mut emu = make_emu(RECURSE_ROM);
+    emu.quirks.stack_limit = Some(12);
+    for _ in 0..12 {
+        emu.step().unwrap();
+    }
+    assert_eq!(emu.step(), Err(Error::StackOverflow(0x200)));
+    assert_eq!(emu.call_stack.len(), 12);
+
+    // 0x200: return
+    const RETURN_ROM: &[u8] = &[0x00, 0xEE];
+    let mut emu = make_emu(RETURN_ROM);
+    assert_eq!(emu.step(), Err(Error::StackUnderflow(0x200)));
+}
@@ -0,0 +1,138 @@
+#![cfg(all(feature = "std", any(feature = "super-chip", feature = "xo-chip")))]
+
+use chip8_core::flags::FLAG_COUNT;
+use chip8_core::*;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Default)]
+struct SharedFlags(Arc<Mutex<Option<[u8; FLAG_COUNT]>>>);
+
+impl FlagStorage for SharedFlags {
+    fn load(&mut self) -> Option<[u8; FLAG_COUNT]> {
+        *self.0.lock().unwrap()
+    }
+    fn save(&mut self, flags: &[u8; FLAG_COUNT]) {
+        *self.0.lock().unwrap() = Some(*flags);
+    }
+}
+
+// 0x200: V0-V3 = 0x11, 0x22, 0x33, 0x44; 0x208: save V0-V3; 0x20A: jump 0x20A
+const SAVE_ROM: &[u8] = &[
+    0x60, 0x11, 0x61, 0x22, 0x62, 0x33, 0x63, 0x44, 0xF3, 0x75, 0x12, 0x0A,
+];
+// 0x200: load V0-V3; 0x202: jump 0x202
+const LOAD_ROM: &[u8] = &[0xF3, 0x85, 0x12, 0x02];
+
+fn flags_mode() -> Chip8Mode {
+    Chip8Mode::try_from(2)
+        .or_else(|_| Chip8Mode::try_from(1))
+        .unwrap()
+}
+
+fn run(emu: &mut CPU, instructions: usize) {
+    for _ in 0..instructions {
+        emu.step().unwrap();
+    }
+}
+
+#[test]
+fn test_flags_survive_load_rom() {
+    let storage = SharedFlags::default();
+    let mut emu = CPU::new(flags_mode());
+    emu.set_flag_storage(Box::new(storage.clone()));
+    emu.load_rom(SAVE_ROM).unwrap();
+    run(&mut emu, 5);
+    let mut expected = [0; FLAG_COUNT];
+    expected[..4].copy_from_slice(&[0x11, 0x22, 0x33, 0x44]);
+    assert_eq!(*storage.0.lock().unwrap(), Some(expected));
+
+    emu.load_rom(LOAD_ROM).unwrap();
+    run(&mut emu, 1);
+    let values: Vec<u8> = (0..4)
+        .map(|reg| emu.registers[Register::try_from(reg).unwrap()])
+        .collect();
+    assert_eq!(values, [0x11, 0x22, 0x33, 0x44]);
+
+    // a fresh CPU picks the flags up from storage too
+    let mut emu = CPU::new(flags_mode());
+    emu.set_flag_storage(Box::new(storage));
+    emu.load_rom(LOAD_ROM).unwrap();
+    run(&mut emu, 1);
+    assert_eq!(emu.registers[Register::V3], 0x44);
+}
+
+#[test]
+fn test_each_rom_has_its_own_flags() {
+    // 0x200: V0-V1 = 0x55, 0x66; 0x204: save V0-V1; 0x206: load V0-V3
+    const SAVE_TWO_ROM: &[u8] = &[0x60, 0x55, 0x61, 0x66, 0xF1, 0x75, 0xF3, 0x85];
+    let first = SharedFlags::default();
+    let mut emu = CPU::new(flags_mode());
+    emu.load_rom(SAVE_ROM).unwrap();
+    emu.set_flag_storage(Box::new(first.clone()));
+    run(&mut emu, 5);
+
+    // another ROM without saved flags yet sees none of the first one's
+    let second = SharedFlags::default();
+    emu.load_rom(SAVE_TWO_ROM).unwrap();
+    emu.set_flag_storage(Box::new(second.clone()));
+    run(&mut emu, 4);
+    let values: Vec<u8> = (0..4)
+        .map(|reg| emu.registers[Register::try_from(reg).unwrap()])
+        .collect();
+    assert_eq!(values, [0x55, 0x66, 0, 0]);
+    let mut expected = [0; FLAG_COUNT];
+    expected[..2].copy_from_slice(&[0x55, 0x66]);
+    assert_eq!(*second.0.lock().unwrap(), Some(expected));
+    expected[..4].copy_from_slice(&[0x11, 0x22, 0x33, 0x44]);
+    assert_eq!(*first.0.lock().unwrap(), Some(expected));
+
+    // nor does one without storage
+    emu.load_rom(LOAD_ROM).unwrap();
+    emu.take_flag_storage();
+    run(&mut emu, 1);
+    assert_eq!(emu.registers[Register::V0], 0);
+}
+
+#[test]
+fn test_clone_keeps_flags_in_memory() {
+    let storage = SharedFlags::default();
+    let mut emu = CPU::new(flags_mode());
+    emu.set_flag_storage(Box::new(storage.clone()));
+    emu.load_rom(SAVE_ROM).unwrap();
+
+    let mut clone = emu.clone();
+    run(&mut clone, 5);
+    assert_eq!(*storage.0.lock().unwrap(), None);
+    assert!(clone.take_flag_storage().is_none());
+    assert!(emu.take_flag_storage().is_some());
+}
+
+#[test]
+fn test_flags_survive_load_rom_without_storage() {
+    let mut emu = CPU::new(flags_mode());
+    emu.load_rom(SAVE_ROM).unwrap();
+    run(&mut emu, 5);
+    emu.load_rom(LOAD_ROM).unwrap();
+    run(&mut emu, 1);
+    assert_eq!(emu.registers[Register::V0], 0x11);
+    assert_eq!(emu.registers[Register::V3], 0x44);
+}
+
+#[cfg(feature = "super-chip")]
+#[test]
+fn test_super_chip_has_eight_flags() {
+    let storage = SharedFlags::default();
+    let mut emu = CPU::new(Chip8Mode::SuperChip);
+    emu.set_flag_storage(Box::new(storage.clone()));
+    // 0x200: V7 = 7; 0x202: V8 = 8; 0x204: save V0-V8, only reaching V7;
+    // 0x206: V7 = 0; 0x208: load V0-VF, only reaching V7
+    emu.load_rom(&[0x67, 0x07, 0x68, 0x08, 0xF8, 0x75, 0x67, 0x00, 0xFF, 0x85])
+        .unwrap();
+    run(&mut emu, 3);
+    let mut expected = [0; FLAG_COUNT];
+    expected[7] = 7;
+    assert_eq!(*storage.0.lock().unwrap(), Some(expected));
+    run(&mut emu, 2);
+    assert_eq!(emu.registers[Register::V7], 7);
+    assert_eq!(emu.registers[Register::V8], 8);
+}
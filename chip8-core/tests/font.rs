@@ -0,0 +1,90 @@
+use chip8_core::font::{FontError, SMALL_FONT_LEN};
+use chip8_core::*;
+
+// 0x200: V0 = 0xA; 0x202: I = small glyph for V0
+const ROM: &[u8] = &[0x60, 0x0A, 0xF0, 0x29];
+
+#[test]
+fn test_default_font() {
+    let mut emu = CPU::default();
+    emu.load_rom(ROM).unwrap();
+    emu.step().unwrap();
+    emu.step().unwrap();
+    assert_eq!(emu.index, 0x50 + 0xA * 5);
+    assert_eq!(emu.font(), &Font::preset(FontPreset::Jaxe));
+    assert_eq!(&emu.memory[0x50..0xA0], &emu.font().small[..]);
+}
+
+#[test]
+fn test_custom_font() {
+    let mut emu = CPU::default();
+    let mut font = Font::preset(FontPreset::CosmacVip);
+    font.small_addr = 0x0;
+    font.big_addr = 0x100;
+    emu.set_font(font.clone()).unwrap();
+    // stays in place across loading a ROM
+    emu.load_rom(ROM).unwrap();
+    emu.step().unwrap();
+    emu.step().unwrap();
+    assert_eq!(emu.index, 0xA * 5);
+    assert_eq!(&emu.memory[..SMALL_FONT_LEN], &font.small[..]);
+    // nothing is left of the old font
+    assert!(emu.memory[SMALL_FONT_LEN..0x100]
+        .iter()
+        .all(|&byte| byte == 0));
+
+    font.small = [0x81; SMALL_FONT_LEN];
+    emu.set_font(font).unwrap();
+    assert_eq!(emu.memory[0x0], 0x81);
+}
+
+#[cfg(any(feature = "super-chip", feature = "xo-chip"))]
+#[test]
+fn test_big_font() {
+    // 0x200: V0 = 0x3; 0x202: I = big glyph for V0
+    const BIG_ROM: &[u8] = &[0x60, 0x03, 0xF0, 0x30];
+    let mode = Chip8Mode::try_from(2)
+        .or_else(|_| Chip8Mode::try_from(1))
+        .unwrap();
+    let mut emu = CPU::new(mode);
+    let mut font = Font::preset(FontPreset::SuperChip11);
+    font.big_addr = 0x150;
+    emu.set_font(font.clone()).unwrap();
+    emu.load_rom(BIG_ROM).unwrap();
+    emu.step().unwrap();
+    emu.step().unwrap();
+    assert_eq!(emu.index, 0x150 + 3 * 10);
+    assert_eq!(&emu.memory[0x16E..0x178], &font.big[30..40]);
+}
+
+#[test]
+fn test_font_errors() {
+    let mut emu = CPU::default();
+    let font = Font {
+        small_addr: 0x1D0,
+        ..Font::default()
+    };
+    assert_eq!(emu.set_font(font), Err(FontError::OutOfRange));
+
+    let font = Font {
+        big_addr: Font::DEFAULT_SMALL_ADDR + 40,
+        ..Font::default()
+    };
+    let overlap = emu.set_font(font).err();
+    if cfg!(any(feature = "super-chip", feature = "xo-chip")) {
+        assert_eq!(overlap, Some(FontError::Overlap));
+    } else {
+        // without `FX30` the big font is never loaded
+        assert_eq!(overlap, None);
+    }
+}
+
+#[test]
+fn test_parse_font_preset() {
+    assert_eq!("vip".parse(), Ok(FontPreset::CosmacVip));
+    assert_eq!("SCHIP".parse(), Ok(FontPreset::SuperChip11));
+    assert!("comic sans".parse::<FontPreset>().is_err());
+    for preset in FontPreset::ALL {
+        assert_eq!(preset.to_string().parse(), Ok(preset));
+    }
+}
@@ -0,0 +1,102 @@
+#![cfg(feature = "alloc")]
+
+use chip8_core::*;
+use core::time::Duration;
+
+// 0x200: V1 = 5; 0x202: V0 += 1; 0x204: skip if key V1 is down;
+// 0x206: jump 0x202; 0x208: jump 0x208
+const COUNT_ROM: &[u8] = &[0x61, 0x05, 0x70, 0x01, 0xE1, 0x9E, 0x12, 0x02, 0x12, 0x08];
+
+fn make_emu(clock_speed: ClockSpeed) -> CPU {
+    let mut emu = CPU::default();
+    emu.clock_speed = clock_speed;
+    emu.load_rom(COUNT_ROM).unwrap();
+    emu
+}
+
+fn run_for(emu: &mut CPU, dur: Duration) {
+    emu.run(Budget::Time(dur), &StopConditions::default())
+        .unwrap();
+}
+
+#[test]
+fn test_events_land_at_their_time() {
+    let ms = Duration::from_millis;
+    // pressing by hand between two runs
+    let mut expected = make_emu(ClockSpeed::Hertz(500));
+    run_for(&mut expected, ms(37));
+    expected.press_key(5);
+    run_for(&mut expected, ms(63));
+    assert_eq!(expected.pc, 0x208);
+    assert!(expected.registers[Register::V0] > 1);
+
+    // queued ahead of one long run, or many short ones
+    for slice in [ms(100), ms(1), ms(7)] {
+        let mut emu = make_emu(ClockSpeed::Hertz(500));
+        emu.press_key_at(5, ms(37));
+        let mut elapsed = Duration::ZERO;
+        while elapsed < ms(100) {
+            run_for(&mut emu, slice);
+            elapsed += slice;
+        }
+        assert_eq!(emu.registers, expected.registers, "{:?}", slice);
+        assert_eq!(emu.queued_key_events(), 0);
+    }
+}
+
+#[test]
+fn test_events_carry_over() {
+    let mut emu = make_emu(ClockSpeed::Hertz(500));
+    emu.press_key_at(5, Duration::from_millis(30));
+    run_for(&mut emu, Duration::from_millis(20));
+    assert!(!emu.is_key_down(5));
+    assert_eq!(emu.queued_key_events(), 1);
+    // the rest of the offset counts from where the last run stopped
+    run_for(&mut emu, Duration::from_millis(9));
+    assert!(!emu.is_key_down(5));
+    run_for(&mut emu, Duration::from_millis(2));
+    assert!(emu.is_key_down(5));
+
+    emu.release_key_at(5, Duration::from_millis(1));
+    let state = emu.save_state().unwrap();
+    emu.load_state(state);
+    assert_eq!(emu.queued_key_events(), 0);
+}
+
+#[test]
+fn test_hold_taps() {
+    for hold_taps in [false, true] {
+        // every instruction of a frame runs at once on the tick, so a tap
+        // between ticks is never seen without holding it
+        let mut emu = make_emu(ClockSpeed::InstructionsPerFrame(10));
+        emu.hold_taps = hold_taps;
+        emu.load_rom(COUNT_ROM).unwrap();
+        assert_eq!(emu.hold_taps, hold_taps);
+        emu.press_key_at(5, Duration::from_millis(20));
+        emu.release_key_at(5, Duration::from_millis(25));
+        for _ in 0..5 {
+            emu.run_frame().unwrap();
+        }
+        assert_eq!(emu.pc == 0x208, hold_taps);
+        assert!(!emu.is_key_down(5));
+    }
+}
+
+#[test]
+fn test_hold_taps_pressed_again() {
+    let ms = Duration::from_millis;
+    let mut emu = make_emu(ClockSpeed::Hertz(500));
+    emu.hold_taps = true;
+    emu.press_key_at(5, ms(0));
+    emu.release_key_at(5, ms(1));
+    emu.press_key_at(5, ms(10));
+    emu.release_key_at(5, ms(100));
+    run_for(&mut emu, ms(5));
+    assert!(emu.is_key_down(5));
+    // the short tap's release was put off, but the key is held again by then
+    run_for(&mut emu, ms(25));
+    assert!(emu.is_key_down(5));
+    run_for(&mut emu, ms(71));
+    assert!(!emu.is_key_down(5));
+    assert_eq!(emu.queued_key_events(), 0);
+}
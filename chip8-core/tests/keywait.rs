@@ -0,0 +1,101 @@
+use chip8_core::cpu::KeyWait;
+use chip8_core::*;
+
+// 0x200: V3 = key; 0x202: jump 0x202
+const ROM: &[u8] = &[0xF3, 0x0A, 0x12, 0x02];
+
+fn make_emu(quirks: Quirks) -> CPU {
+    let mut emu = CPU::default();
+    emu.quirks = quirks;
+    emu.load_rom(ROM).unwrap();
+    emu
+}
+
+fn waiting(emu: &mut CPU) -> bool {
+    let report = emu.run_frame().unwrap();
+    report.stop_reason == StopReason::WaitingForKey
+}
+
+#[test]
+fn test_waits_for_press_and_release() {
+    let mut emu = make_emu(Quirks::default());
+    // held from before the wait started
+    emu.press_key(0x5);
+    assert!(waiting(&mut emu));
+    assert_eq!(emu.key_wait(), Some(KeyWait::Press(Register::V3)));
+    emu.release_key(0x5);
+    assert!(waiting(&mut emu));
+
+    emu.press_key(0xA);
+    assert_eq!(emu.key_wait(), Some(KeyWait::Release(Register::V3, 0xA)));
+    assert!(waiting(&mut emu));
+    // other keys don't matter once one has been pressed
+    emu.press_key(0x1);
+    emu.release_key(0x1);
+    assert!(waiting(&mut emu));
+    assert_eq!(emu.registers[Register::V3], 0);
+
+    emu.release_key(0xA);
+    assert_eq!(emu.key_wait(), None);
+    assert_eq!(emu.registers[Register::V3], 0xA);
+    assert!(!waiting(&mut emu));
+    assert_eq!(emu.pc, 0x202);
+}
+
+#[test]
+fn test_key_wait_on_press() {
+    let mut emu = make_emu(Quirks {
+        key_wait_on_press: true,
+        ..Quirks::default()
+    });
+    emu.press_key(0x5);
+    assert!(waiting(&mut emu));
+    // pressing a held key again isn't a new press
+    emu.press_key(0x5);
+    assert!(waiting(&mut emu));
+
+    emu.press_key(0x7);
+    assert_eq!(emu.key_wait(), None);
+    assert_eq!(emu.registers[Register::V3], 0x7);
+    assert!(!waiting(&mut emu));
+}
+
+#[test]
+fn test_key_wait_beep() {
+    for beep in [false, true] {
+        let mut emu = make_emu(Quirks {
+            key_wait_beep: beep,
+            ..Quirks::default()
+        });
+        emu.run_frame().unwrap();
+        assert!(!emu.is_sound_playing());
+        emu.press_key(0x2);
+        assert_eq!(emu.is_sound_playing(), beep);
+        assert_eq!(emu.run_frame().unwrap().sound_active, beep);
+        let mut samples = [0.0; 64];
+        let read = emu.read_beep_samples_to(core::time::Duration::from_millis(1), &mut samples);
+        assert_eq!(read > 0, beep);
+
+        emu.release_key(0x2);
+        assert!(!emu.is_sound_playing());
+        assert_eq!(emu.sound_timer, 0);
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_key_wait_save_state() {
+    let mut emu = make_emu(Quirks::default());
+    emu.run_frame().unwrap();
+    emu.press_key(0xC);
+    let state = emu.save_state().unwrap().to_bytes();
+
+    let mut restored = make_emu(Quirks::default());
+    restored.load_state(cpu::SavedState::decode(&state).unwrap());
+    assert_eq!(
+        restored.key_wait(),
+        Some(KeyWait::Release(Register::V3, 0xC))
+    );
+    restored.release_key(0xC);
+    assert_eq!(restored.registers[Register::V3], 0xC);
+}
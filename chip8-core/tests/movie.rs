@@ -0,0 +1,184 @@
+#![cfg(feature = "alloc")]
+
+use chip8_core::movie::{MovieError, PlaybackError};
+use chip8_core::*;
+
+// 0x200: V0 = key; 0x202: V1 = rand; 0x204: V2 += V1; 0x206: jump 0x200
+const ROM: &[u8] = &[0xF0, 0x0A, 0xC1, 0xFF, 0x82, 0x14, 0x12, 0x00];
+
+fn make_emu() -> CPU {
+    let mut emu = CPU::default();
+    emu.clock_speed = ClockSpeed::InstructionsPerFrame(10);
+    emu.load_rom(ROM).unwrap();
+    emu.reseed(42);
+    emu
+}
+
+// Taps a key every few frames, with more than one event in some frames.
+fn record(emu: &mut CPU, recorder: &mut MovieRecorder, frames: u32) {
+    for _ in 0..frames {
+        let frame = recorder.frame();
+        if frame % 5 == 1 {
+            recorder.press_key(emu, (frame % 16) as u8);
+        }
+        if frame % 5 == 3 {
+            recorder.release_key(emu, ((frame - 2) % 16) as u8);
+            recorder.press_key(emu, 0xF);
+            recorder.release_key(emu, 0xF);
+        }
+        recorder.run_frame(emu).unwrap();
+    }
+}
+
+fn state(emu: &mut CPU) -> Vec<u8> {
+    emu.save_state().unwrap().to_bytes()
+}
+
+#[test]
+fn test_movie_playback() {
+    let mut emu = make_emu();
+    let mut recorder = MovieRecorder::with_checksum_interval(&mut emu, 10).unwrap();
+    record(&mut emu, &mut recorder, 100);
+    let movie = recorder.finish();
+    assert_eq!(movie.len(), 100);
+    assert_eq!(movie.checksums().len(), 10);
+
+    let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+    let mut replayed = CPU::default();
+    replayed.reseed(7);
+    let mut player = MoviePlayer::new(movie, &mut replayed);
+    assert_eq!(replayed.clock_speed, ClockSpeed::InstructionsPerFrame(10));
+    player.play(&mut replayed).unwrap();
+    assert!(player.is_finished());
+    assert_eq!(player.run_frame(&mut replayed), Ok(None));
+    assert_eq!(state(&mut replayed), state(&mut emu));
+}
+
+#[test]
+fn test_movie_desync() {
+    let mut emu = make_emu();
+    let mut recorder = MovieRecorder::with_checksum_interval(&mut emu, 10).unwrap();
+    record(&mut emu, &mut recorder, 50);
+    let movie = recorder.finish();
+
+    let mut replayed = CPU::default();
+    let mut player = MoviePlayer::new(movie, &mut replayed);
+    for _ in 0..23 {
+        player.run_frame(&mut replayed).unwrap();
+    }
+    replayed.registers[Register::V2] ^= 1;
+    for _ in 23..29 {
+        player.run_frame(&mut replayed).unwrap();
+    }
+    match player.run_frame(&mut replayed) {
+        Err(PlaybackError::Desync { frame: 30, .. }) => {}
+        other => panic!("expected a desync at frame 30, got {:?}", other),
+    }
+    // playback carries on
+    assert!(matches!(
+        player.play(&mut replayed),
+        Err(PlaybackError::Desync { frame: 40, .. })
+    ));
+}
+
+#[test]
+fn test_movie_branch() {
+    let mut emu = make_emu();
+    let mut recorder = MovieRecorder::with_checksum_interval(&mut emu, 10).unwrap();
+    record(&mut emu, &mut recorder, 60);
+    let original = recorder.finish();
+
+    // go back to frame 34 and play differently from there
+    let mut branched = CPU::default();
+    let mut player = MoviePlayer::new(original.clone(), &mut branched);
+    for _ in 0..34 {
+        player.run_frame(&mut branched).unwrap();
+    }
+    let mut recorder = player.into_recorder();
+    assert_eq!(recorder.frame(), 34);
+    assert_eq!(recorder.movie().checksums().len(), 3);
+    recorder.press_key(&mut branched, 0xA);
+    record(&mut branched, &mut recorder, 30);
+    let branch = recorder.finish();
+    assert_eq!(branch.len(), 64);
+    assert_ne!(state(&mut branched), state(&mut emu));
+
+    let mut replayed = CPU::default();
+    MoviePlayer::new(branch, &mut replayed)
+        .play(&mut replayed)
+        .unwrap();
+    assert_eq!(state(&mut replayed), state(&mut branched));
+}
+
+#[test]
+fn test_movie_from_save_state() {
+    let mut emu = make_emu();
+    emu.press_key(3);
+    for _ in 0..20 {
+        emu.run_frame().unwrap();
+    }
+    let saved = emu.save_state().unwrap();
+
+    let mut live = CPU::default();
+    live.clock_speed = ClockSpeed::InstructionsPerFrame(10);
+    live.load_state(saved);
+    let mut recorder = MovieRecorder::new(&mut live).unwrap();
+    record(&mut live, &mut recorder, 120);
+    let movie = recorder.finish();
+    assert_eq!(movie.checksums().len(), 2);
+
+    let mut replayed = make_emu();
+    MoviePlayer::new(movie, &mut replayed)
+        .play(&mut replayed)
+        .unwrap();
+    assert_eq!(state(&mut replayed), state(&mut live));
+}
+
+#[test]
+fn test_movie_version_1() {
+    let mut emu = make_emu();
+    emu.quirks.key_wait_beep = true;
+    let mut recorder = MovieRecorder::with_checksum_interval(&mut emu, 10).unwrap();
+    record(&mut emu, &mut recorder, 40);
+    let bytes = recorder.finish().to_bytes();
+
+    // version 1 had no second byte of quirk flags at the end of "SETT"
+    assert_eq!(&bytes[6..10], b"SETT");
+    let len = u32::from_le_bytes([bytes[10], bytes[11], bytes[12], bytes[13]]) as usize;
+    let mut old = bytes[..4].to_vec();
+    old.extend(1u16.to_le_bytes());
+    old.extend(b"SETT");
+    old.extend((len as u32 - 1).to_le_bytes());
+    old.extend(&bytes[14..14 + len - 1]);
+    old.extend(&bytes[14 + len..]);
+
+    let movie = Movie::from_bytes(&old).unwrap();
+    assert!(!movie.quirks().key_wait_on_press);
+    assert!(!movie.quirks().key_wait_beep);
+    assert_eq!(movie.len(), 40);
+    let mut replayed = CPU::default();
+    MoviePlayer::new(movie, &mut replayed)
+        .play(&mut replayed)
+        .unwrap();
+    assert_eq!(state(&mut replayed), state(&mut emu));
+}
+
+#[test]
+fn test_movie_errors() {
+    let mut emu = make_emu();
+    let bytes = MovieRecorder::new(&mut emu).unwrap().finish().to_bytes();
+    assert_eq!(
+        Movie::from_bytes(b"CH8S").unwrap_err(),
+        MovieError::NotAMovie
+    );
+    let mut newer = bytes.clone();
+    newer[4] = 9;
+    assert_eq!(
+        Movie::from_bytes(&newer).unwrap_err(),
+        MovieError::UnsupportedVersion(9)
+    );
+    assert_eq!(
+        Movie::from_bytes(&bytes[..bytes.len() - 3]).unwrap_err(),
+        MovieError::Corrupt
+    );
+}
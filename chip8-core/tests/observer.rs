@@ -0,0 +1,125 @@
+use chip8_core::display::ScreenRect;
+use chip8_core::*;
+
+#[derive(Debug, PartialEq)]
+enum Event {
+    Executed(u16, u16),
+    Screen(ScreenRect),
+    Sound(bool),
+    KeyWait(Register),
+    Write(u16, u8),
+}
+
+#[derive(Default)]
+struct Recorder(Vec<Event>);
+
+impl Chip8Observer for Recorder {
+    fn instruction_executed(&mut self, _cpu: &CPU, pc: u16, opcode: u16) {
+        self.0.push(Event::Executed(pc, opcode));
+    }
+    fn screen_modified(&mut self, _cpu: &CPU, rect: ScreenRect) {
+        self.0.push(Event::Screen(rect));
+    }
+    fn sound_changed(&mut self, _cpu: &CPU, playing: bool) {
+        self.0.push(Event::Sound(playing));
+    }
+    fn key_wait(&mut self, _cpu: &CPU, register: Register) {
+        self.0.push(Event::KeyWait(register));
+    }
+    fn memory_written(&mut self, _cpu: &CPU, addr: u16, value: u8) {
+        self.0.push(Event::Write(addr, value));
+    }
+}
+
+#[test]
+fn test_observer_events() {
+    const ROM: &[u8] = &[
+        0x60, 0x7B, // 0x200: V0 = 123
+        0xA3, 0x00, // 0x202: I = 0x300
+        0xF0, 0x33, // 0x204: BCD V0
+        0x61, 0x3E, // 0x206: V1 = 62
+        0xD1, 0x03, // 0x208: draw V1, V0, 3
+        0xF0, 0x18, // 0x20A: sound = V0
+        0x00, 0xE0, // 0x20C: clear
+        0xF2, 0x0A, // 0x20E: V2 = key
+    ];
+    let mut emu = CPU::default();
+    emu.quirks.wrap = false;
+    emu.load_rom(ROM).unwrap();
+    let mut recorder = Recorder::default();
+    let reason = emu.run_observed(Budget::Frames(2), &StopConditions::default(), &mut recorder);
+    assert_eq!(reason, Ok(StopReason::BudgetExhausted));
+
+    let (width, height) = emu.screen.dimensions();
+    assert_eq!(
+        recorder.0,
+        [
+            Event::Executed(0x200, 0x607B),
+            Event::Executed(0x202, 0xA300),
+            Event::Executed(0x204, 0xF033),
+            Event::Write(0x300, 1),
+            Event::Write(0x301, 2),
+            Event::Write(0x302, 3),
+            Event::Executed(0x206, 0x613E),
+            Event::Executed(0x208, 0xD103),
+            // clipped to the right and bottom edges
+            Event::Screen(ScreenRect {
+                x: 62,
+                y: 123 % height,
+                width: width - 62,
+                height: 3.min(height - 123 % height),
+            }),
+            Event::Executed(0x20A, 0xF018),
+            Event::Sound(true),
+            Event::Executed(0x20C, 0x00E0),
+            Event::Screen(ScreenRect::full((width, height))),
+            Event::Executed(0x20E, 0xF20A),
+            Event::KeyWait(Register::V2),
+        ]
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_sound_events() {
+    use core::time::Duration;
+
+    const ROM: &[u8] = &[
+        0x60, 0x02, // 0x200: V0 = 2
+        0xF0, 0x18, // 0x202: sound = V0
+        0xF1, 0x0A, // 0x204: V1 = key
+        0x12, 0x06, // 0x206: jump 0x206
+    ];
+    let mut emu = CPU::default();
+    emu.quirks.key_wait_beep = true;
+    emu.load_rom(ROM).unwrap();
+    let mut recorder = Recorder::default();
+    for _ in 0..3 {
+        emu.step_observed(&mut recorder).unwrap();
+    }
+    // the beep while the key is held counts as sound too
+    emu.press_key_at(3, Duration::from_millis(50));
+    emu.release_key_at(3, Duration::from_millis(100));
+    let reason = emu.run_observed(
+        Budget::Time(Duration::from_millis(200)),
+        &StopConditions::default(),
+        &mut recorder,
+    );
+    assert_eq!(reason, Ok(StopReason::BudgetExhausted));
+    assert_eq!(emu.registers[Register::V1], 3);
+
+    let sounds: Vec<_> = recorder
+        .0
+        .into_iter()
+        .filter(|event| matches!(event, Event::Sound(_)))
+        .collect();
+    assert_eq!(
+        sounds,
+        [
+            Event::Sound(true),
+            Event::Sound(false),
+            Event::Sound(true),
+            Event::Sound(false),
+        ]
+    );
+}
@@ -0,0 +1,108 @@
+#![cfg(feature = "alloc")]
+
+use chip8_core::*;
+
+const ROM: &[u8] = &[
+    0x22, 0x06, // 0x200: call 0x206
+    0x22, 0x0C, // 0x202: call 0x20C
+    0x12, 0x04, // 0x204: jump 0x204
+    0x70, 0x01, // 0x206: V0 += 1
+    0x22, 0x0C, // 0x208: call 0x20C
+    0x00, 0xEE, // 0x20A: return
+    0x71, 0x01, // 0x20C: V1 += 1
+    0x00, 0xEE, // 0x20E: return
+];
+
+fn profiled(steps: usize) -> CPU {
+    let mut emu = CPU::default();
+    emu.load_rom(ROM).unwrap();
+    emu.enable_profiling();
+    for _ in 0..steps {
+        emu.step().unwrap();
+    }
+    emu
+}
+
+#[test]
+fn test_address_counts() {
+    let emu = profiled(11);
+    let profile = emu.profile().unwrap();
+    assert_eq!(profile.instructions(), 11);
+    assert_eq!(profile.address(0x20C).executions, 2);
+    assert_eq!(profile.address(0x204).executions, 2);
+    assert_eq!(profile.address(0x210).executions, 0);
+
+    let spots = profile.hot_spots();
+    assert_eq!(spots.len(), 8);
+    assert_eq!(
+        spots.iter().map(|spot| spot.cycles).sum::<u64>(),
+        profile.total_cycles()
+    );
+    assert!(spots
+        .windows(2)
+        .all(|pair| pair[0].cycles >= pair[1].cycles));
+}
+
+#[test]
+fn test_routines() {
+    let emu = profiled(11);
+    let profile = emu.profile().unwrap();
+    let cycles = |addr| profile.address(addr).cycles;
+
+    // 0x20C is called from the top level and from 0x206
+    let inner = profile.routine_stats(0x20C).unwrap();
+    assert_eq!(inner.calls, 2);
+    assert_eq!(inner.instructions, 4);
+    assert_eq!(inner.self_cycles, cycles(0x20C) + cycles(0x20E));
+    assert_eq!(inner.total_cycles, inner.self_cycles);
+
+    // calls and returns are charged to the routine they're in
+    let outer = profile.routine_stats(0x206).unwrap();
+    assert_eq!(outer.calls, 1);
+    assert_eq!(outer.instructions, 3);
+    assert_eq!(
+        outer.self_cycles,
+        cycles(0x206) + cycles(0x208) + cycles(0x20A)
+    );
+    assert_eq!(
+        outer.total_cycles,
+        outer.self_cycles + inner.self_cycles / 2
+    );
+
+    let routines = profile.routines();
+    assert_eq!(routines.len(), 2);
+    assert!(routines[0].total_cycles >= routines[1].total_cycles);
+    let report = profile.report(10).to_string();
+    assert!(report.contains("0x20C"));
+    assert!(report.contains("0x0204"));
+}
+
+#[test]
+fn test_heatmap() {
+    let emu = profiled(11);
+    let profile = emu.profile().unwrap();
+    let heatmap = profile.heatmap();
+    for (addr, &value) in heatmap.iter().enumerate() {
+        let ran = profile.address(addr as u16).executions > 0;
+        assert_eq!(value > 0, ran, "at 0x{:X}", addr);
+    }
+    assert_eq!(heatmap.iter().max(), Some(&255));
+
+    let text = profile.heatmap_text(32).to_string();
+    assert_eq!(text.lines().count(), 1);
+    assert!(text.starts_with("0x0200 |"));
+}
+
+#[test]
+fn test_enable_and_take() {
+    let mut emu = profiled(3);
+    // reloading keeps profiling, starting over
+    emu.load_rom(ROM).unwrap();
+    assert_eq!(emu.profile().unwrap().instructions(), 0);
+    emu.step().unwrap();
+    let profile = emu.take_profile().unwrap();
+    assert_eq!(profile.instructions(), 1);
+    assert!(emu.profile().is_none());
+    emu.step().unwrap();
+    assert!(emu.take_profile().is_none());
+}
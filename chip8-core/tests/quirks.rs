@@ -0,0 +1,132 @@
+use chip8_core::display::SCREEN_WIDTH;
+use chip8_core::quirks::IndexIncrement;
+use chip8_core::*;
+
+fn run(quirks: Quirks, rom: &[u8], steps: usize) -> CPU {
+    let mut emu = CPU::default();
+    emu.quirks = quirks;
+    emu.load_rom(rom).unwrap();
+    for _ in 0..steps {
+        emu.step().unwrap();
+    }
+    emu
+}
+
+// Whether a pixel is set, in the coordinates of the current resolution.
+fn lit(emu: &CPU, x: usize, y: usize) -> bool {
+    let scale = SCREEN_WIDTH / emu.screen.dimensions().0;
+    emu.screen.read_pixel((x * scale) as u8, (y * scale) as u8) != 0
+}
+
+#[test]
+fn test_shift() {
+    const ROM: &[u8] = &[
+        0x60, 0x81, // 0x200: V0 = 0x81
+        0x61, 0x02, // 0x202: V1 = 0x02
+        0x80, 0x16, // 0x204: V0 >>= 1, from V1 unless shifting in place
+        0x60, 0x81, // 0x206: V0 = 0x81
+        0x80, 0x1E, // 0x208: V0 <<= 1, from V1 unless shifting in place
+    ];
+    let mut quirks = Quirks::default();
+    for shift in [false, true] {
+        quirks.shift = shift;
+        let emu = run(quirks, ROM, 3);
+        let expected = if shift { (0x40, 1) } else { (0x01, 0) };
+        assert_eq!(
+            (emu.registers[Register::V0], emu.registers[Register::VF]),
+            expected
+        );
+        let emu = run(quirks, ROM, 5);
+        let expected = if shift { (0x02, 1) } else { (0x04, 0) };
+        assert_eq!(
+            (emu.registers[Register::V0], emu.registers[Register::VF]),
+            expected
+        );
+        assert_eq!(emu.registers[Register::V1], 0x02);
+    }
+}
+
+#[test]
+fn test_vf_reset() {
+    let mut quirks = Quirks::default();
+    for vf_reset in [false, true] {
+        quirks.vf_reset = vf_reset;
+        for (op, result) in [(0x1, 0x0E), (0x2, 0x08), (0x3, 0x06)] {
+            // VF = 5; V0 = 0x0C; V1 = 0x0A; V0 |=, &= or ^= V1
+            let rom = [0x6F, 0x05, 0x60, 0x0C, 0x61, 0x0A, 0x80, 0x10 | op];
+            let emu = run(quirks, &rom, 4);
+            assert_eq!(emu.registers[Register::V0], result);
+            let expected = if vf_reset { 0 } else { 5 };
+            assert_eq!(emu.registers[Register::VF], expected, "8XY{:X}", op);
+        }
+    }
+}
+
+#[test]
+fn test_jump() {
+    const ROM: &[u8] = &[
+        0x60, 0x10, // 0x200: V0 = 0x10
+        0x62, 0x20, // 0x202: V2 = 0x20
+        0xB2, 0x34, // 0x204: jump 0x234 + V0, or 0x234 + V2 for BXNN
+    ];
+    let mut quirks = Quirks::default();
+    for jump in [false, true] {
+        quirks.jump = jump;
+        let emu = run(quirks, ROM, 3);
+        assert_eq!(emu.pc, if jump { 0x254 } else { 0x244 });
+    }
+}
+
+#[test]
+fn test_load_store() {
+    const ROM: &[u8] = &[
+        0xA3, 0x00, // 0x200: I = 0x300
+        0x60, 0x01, // 0x202: V0 = 1
+        0x61, 0x02, // 0x204: V1 = 2
+        0x62, 0x03, // 0x206: V2 = 3
+        0xF2, 0x55, // 0x208: store V0-V2 at I
+        0xA3, 0x01, // 0x20A: I = 0x301
+        0xF1, 0x65, // 0x20C: load V0-V1 from I
+    ];
+    let mut quirks = Quirks::default();
+    for (load_store, store_index, load_index) in [
+        (IndexIncrement::Unchanged, 0x300, 0x301),
+        (IndexIncrement::ByX, 0x302, 0x302),
+        (IndexIncrement::ByXPlusOne, 0x303, 0x303),
+    ] {
+        quirks.load_store = load_store;
+        let emu = run(quirks, ROM, 5);
+        assert_eq!(&emu.memory[0x300..0x303], &[1, 2, 3]);
+        assert_eq!(emu.index, store_index, "{:?}", load_store);
+        let emu = run(quirks, ROM, 7);
+        assert_eq!(emu.registers[Register::V0], 2);
+        assert_eq!(emu.registers[Register::V1], 3);
+        assert_eq!(emu.index, load_index, "{:?}", load_store);
+    }
+}
+
+#[test]
+fn test_wrap() {
+    const ROM: &[u8] = &[
+        0xA2, 0x08, // 0x200: I = 0x208
+        0x60, 0x3C, // 0x202: V0 = 60
+        0x61, 0x1F, // 0x204: V1 = 31
+        0xD0, 0x12, // 0x206: draw 2 rows at (V0, V1)
+        0xFF, 0xFF, // 0x208: sprite
+    ];
+    let mut quirks = Quirks::default();
+    for wrap in [false, true] {
+        quirks.wrap = wrap;
+        let emu = run(quirks, ROM, 4);
+        for x in 60..64 {
+            assert!(lit(&emu, x, 31));
+            assert_eq!(lit(&emu, x, 0), wrap);
+        }
+        for x in 0..4 {
+            assert_eq!(lit(&emu, x, 31), wrap);
+            assert_eq!(lit(&emu, x, 0), wrap);
+        }
+        assert!(!lit(&emu, 4, 31));
+        assert!(!lit(&emu, 59, 31));
+    }
+}
@@ -0,0 +1,115 @@
+use chip8_core::display::Display;
+use chip8_core::render::{BufferTooSmall, PixelFormat, Renderer, Resolution};
+
+const WHITE: u32 = 0x00FFFFFF;
+
+fn make_display() -> Display {
+    let mut display = Display::new();
+    display.write_to_screen(0, 0);
+    display.write_to_screen(5, 2);
+    display.write_to_screen(63, 31);
+    display
+}
+
+fn render(renderer: &Renderer, display: &Display) -> Vec<u8> {
+    let mut out = vec![0xAA; renderer.buffer_len(display)];
+    renderer.render(display, &mut out).unwrap();
+    out
+}
+
+#[test]
+fn test_render_formats() {
+    let display = make_display();
+    let mut renderer = Renderer {
+        scale: 2,
+        ..Renderer::new(PixelFormat::Rgba8)
+    };
+    renderer.palette[1] = 0x00123456;
+    assert_eq!(renderer.dimensions(&display), (128, 64));
+    let out = render(&renderer, &display);
+    assert_eq!(renderer.stride(&display), 128 * 4);
+    let rgba = |x: usize, y: usize| &out[(x + y * 128) * 4..][..4];
+    assert_eq!(rgba(0, 0), [0x12, 0x34, 0x56, 0xFF]);
+    assert_eq!(rgba(1, 1), [0x12, 0x34, 0x56, 0xFF]);
+    assert_eq!(rgba(2, 0), [0, 0, 0, 0xFF]);
+    assert_eq!(rgba(11, 5), [0x12, 0x34, 0x56, 0xFF]);
+    assert_eq!(rgba(127, 63), [0x12, 0x34, 0x56, 0xFF]);
+
+    renderer.format = PixelFormat::Bgra8;
+    let out = render(&renderer, &display);
+    assert_eq!(out[..4], [0x56, 0x34, 0x12, 0xFF]);
+
+    renderer.format = PixelFormat::Rgb565;
+    renderer.palette[1] = WHITE;
+    let out = render(&renderer, &display);
+    assert_eq!(renderer.stride(&display), 128 * 2);
+    assert_eq!(out[..6], [0xFF, 0xFF, 0xFF, 0xFF, 0, 0]);
+
+    renderer.format = PixelFormat::Gray8;
+    renderer.palette[1] = 0x00808080;
+    let out = render(&renderer, &display);
+    assert_eq!(out[..3], [0x80, 0x80, 0]);
+    assert_eq!(out[128..131], [0x80, 0x80, 0]);
+
+    renderer.format = PixelFormat::Mono1;
+    let out = render(&renderer, &display);
+    assert_eq!(renderer.stride(&display), 16);
+    assert_eq!(out.len(), 16 * 64);
+    assert_eq!(out[0], 0b1100_0000);
+    assert_eq!(out[4 * 16 + 1], 0b0011_0000);
+    assert_eq!(out[63 * 16 + 15], 0b0000_0011);
+    assert_eq!(out.iter().map(|b| b.count_ones()).sum::<u32>(), 12);
+}
+
+#[test]
+fn test_render_resolution() {
+    let display = make_display();
+    let native = Renderer::new(PixelFormat::Gray8);
+    assert_eq!(native.dimensions(&display), (64, 32));
+    let fixed = Renderer {
+        resolution: Resolution::Fixed,
+        ..native
+    };
+    assert_eq!(fixed.dimensions(&display), (128, 64));
+    // the same as scaling the native image by 2
+    let scaled = Renderer { scale: 2, ..native };
+    assert_eq!(render(&fixed, &display), render(&scaled, &display));
+}
+
+#[test]
+fn test_render_buffer() {
+    let display = make_display();
+    let renderer = Renderer::new(PixelFormat::Rgba8);
+    let len = renderer.buffer_len(&display);
+    let mut out = vec![0xAA; len + 3];
+    assert_eq!(
+        renderer.render(&display, &mut out[..len - 1]),
+        Err(BufferTooSmall { needed: len })
+    );
+    assert_eq!(renderer.render(&display, &mut out), Ok((64, 32)));
+    assert_eq!(out[len..], [0xAA; 3]);
+
+    let empty = Renderer {
+        scale: 0,
+        ..renderer
+    };
+    assert_eq!(empty.buffer_len(&display), 0);
+    assert_eq!(empty.render(&display, &mut []), Ok((0, 0)));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_to_buffer() {
+    use chip8_core::display::{COLOR_SET, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+    let display = make_display();
+    let buf = display.to_buffer(2, 3);
+    assert_eq!(buf.len(), SCREEN_WIDTH * SCREEN_HEIGHT * 6);
+    for (idx, &color) in buf.iter().enumerate() {
+        let x = idx % (SCREEN_WIDTH * 2) / 2;
+        let y = idx / (SCREEN_WIDTH * 2) / 3;
+        let pixel = display.read_pixel_unchecked(x as u8, y as u8);
+        assert_eq!(color, COLOR_SET[pixel]);
+    }
+    assert!(display.to_buffer(0, 1).is_empty());
+}
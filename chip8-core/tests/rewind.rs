@@ -0,0 +1,62 @@
+#![cfg(feature = "alloc")]
+
+use chip8_core::*;
+
+// 0x200: V0 += 1; 0x202: draw V0, V0, 1; 0x204: jump 0x200
+const ROM: &[u8] = &[0x70, 0x01, 0xD0, 0x01, 0x12, 0x00];
+
+fn make_emu() -> CPU {
+    let mut emu = CPU::default();
+    emu.clock_speed = ClockSpeed::InstructionsPerFrame(6);
+    emu.load_rom(ROM).unwrap();
+    emu
+}
+
+#[test]
+fn test_rewind() {
+    let mut emu = make_emu();
+    let mut rewind = Rewind::with_keyframe_interval(100, 8);
+    let snapshot = |emu: &CPU| (emu.pc, emu.registers, format!("{:?}", emu.screen));
+    let mut history = Vec::new();
+    for _ in 0..30 {
+        emu.run_frame().unwrap();
+        rewind.record(&mut emu).unwrap();
+        history.push(snapshot(&emu));
+    }
+    assert_eq!(rewind.len(), 30);
+
+    assert_eq!(rewind.rewind(&mut emu, 10), 10);
+    assert_eq!(rewind.len(), 20);
+    assert_eq!(snapshot(&emu), history[19]);
+
+    // resuming from the restored frame continues the same way
+    for expected in &history[20..] {
+        emu.run_frame().unwrap();
+        rewind.record(&mut emu).unwrap();
+        assert_eq!(&snapshot(&emu), expected);
+    }
+    assert_eq!(rewind.len(), 30);
+
+    // only as far back as the oldest frame
+    assert_eq!(rewind.rewind(&mut emu, 1000), 29);
+    assert_eq!(snapshot(&emu), history[0]);
+}
+
+#[test]
+fn test_rewind_capacity() {
+    let mut emu = make_emu();
+    let mut rewind = Rewind::with_keyframe_interval(20, 8);
+    let mut v0 = Vec::new();
+    for _ in 0..100 {
+        emu.run_frame().unwrap();
+        rewind.record(&mut emu).unwrap();
+        v0.push(emu.registers[Register::V0]);
+    }
+    assert_eq!(rewind.len(), 20);
+    assert_eq!(rewind.rewind(&mut emu, 50), 19);
+    assert_eq!(emu.registers[Register::V0], v0[80]);
+
+    // deltas are far smaller than full states
+    let full_state = cpu::CHIP8_MEM_SIZE + display::SCREEN_WIDTH * display::SCREEN_HEIGHT;
+    assert!(rewind.size_in_bytes() < full_state * 3);
+}
@@ -0,0 +1,94 @@
+#![cfg(feature = "alloc")]
+
+use chip8_core::rng::{CosmacVipRng, SequenceRng, WyRand};
+use chip8_core::*;
+
+// 0x200: V0 = rand & 0xFF; 0x202: V1 = rand & 0x0F
+const ROM: &[u8] = &[0xC0, 0xFF, 0xC1, 0x0F];
+
+#[test]
+fn test_sequence_rng() {
+    let mut emu = CPU::default();
+    emu.load_rom(ROM).unwrap();
+    emu.set_rng(Box::new(SequenceRng::new([0xAB, 0x5C])));
+    emu.step().unwrap();
+    emu.step().unwrap();
+    assert_eq!(emu.registers[Register::V0], 0xAB);
+    assert_eq!(emu.registers[Register::V1], 0x0C);
+    // wraps around, and clones continue from the same place
+    let mut clone = emu.clone();
+    assert_eq!(emu.random(), 0xAB);
+    assert_eq!(clone.random(), 0xAB);
+}
+
+#[test]
+fn test_random_survives_save_state() {
+    let mut emu = CPU::default();
+    emu.reseed(1234);
+    let mut unsaved = emu.clone();
+    let state = emu.save_state().unwrap();
+    let expected: Vec<u8> = (0..8).map(|_| unsaved.random()).collect();
+    // saving doesn't disturb the sequence
+    let drawn: Vec<u8> = (0..8).map(|_| emu.random()).collect();
+    assert_eq!(drawn, expected);
+
+    emu.load_state(state);
+    let replayed: Vec<u8> = (0..8).map(|_| emu.random()).collect();
+    assert_eq!(replayed, expected);
+}
+
+#[test]
+fn test_custom_rng_state_is_saved() {
+    let mut emu = CPU::default();
+    emu.set_rng(Box::new(SequenceRng::new([1, 2, 3, 4, 5])));
+    emu.random();
+    let state = emu.save_state().unwrap();
+    assert_eq!(emu.random(), 2);
+    assert_eq!(emu.random(), 3);
+    emu.load_state(state);
+    assert_eq!(emu.random(), 2);
+}
+
+#[test]
+fn test_wyrand_seed() {
+    let mut a = WyRand::with_seed(42);
+    let mut b = WyRand::with_seed(42);
+    let seq_a: Vec<u8> = (0..16).map(|_| a.next_byte()).collect();
+    let seq_b: Vec<u8> = (0..16).map(|_| b.next_byte()).collect();
+    assert_eq!(seq_a, seq_b);
+    assert_eq!(a.state(), b.state());
+    assert!(seq_a.iter().any(|&byte| byte != seq_a[0]));
+}
+
+#[test]
+fn test_cosmac_vip_rng() {
+    // a stand-in for the interpreter, whose first page the generator never
+    // reads
+    let mut interpreter = [0xEE; 512];
+    for (idx, byte) in interpreter[0x100..].iter_mut().enumerate() {
+        *byte = (idx * 7 + 3) as u8;
+    }
+    assert!(CosmacVipRng::from_interpreter(&interpreter[..511], 0).is_none());
+    let mut rng = CosmacVipRng::from_interpreter(&interpreter, 0x12FE).unwrap();
+    // 0x12 + 0xFC from 0x01FF, then + 0x03 from 0x0100 as the low byte
+    // wraps around, then + 0x0A from 0x0101
+    assert_eq!(rng.next_byte(), 0x0E);
+    assert_eq!(rng.next_byte(), 0x11);
+    assert_eq!(rng.next_byte(), 0x1B);
+    assert_eq!(rng.state(), 0x1B01);
+
+    // 0x200: V0 = rand & 0xFF; 0x202: V1 = rand & 0x0F
+    let mut emu = CPU::default();
+    emu.load_rom(ROM).unwrap();
+    emu.set_rng(Box::new(rng));
+    emu.step().unwrap();
+    emu.step().unwrap();
+    // + 0x11 from 0x0102, then + 0x18 from 0x0103
+    assert_eq!(emu.registers[Register::V0], 0x2C);
+    assert_eq!(emu.registers[Register::V1], 0x04);
+
+    let state = emu.save_state().unwrap();
+    emu.random();
+    emu.load_state(state);
+    assert_eq!(emu.random(), 0x44 + 0x1F);
+}
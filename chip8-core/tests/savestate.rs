@@ -0,0 +1,264 @@
+#![cfg(feature = "alloc")]
+
+use chip8_core::cpu::SavedState;
+use chip8_core::savestate::{MAGIC, VERSION};
+use chip8_core::*;
+use core::time::Duration;
+
+// 0x200: V0 += 1; 0x202: call 0x208; 0x204: jump 0x200
+// 0x208: draw V0, V0, 1; 0x20A: return
+const ROM: &[u8] = &[
+    0x70, 0x01, 0x22, 0x08, 0x12, 0x00, 0x00, 0x00, 0xD0, 0x01, 0x00, 0xEE,
+];
+
+fn make_emu() -> CPU {
+    let mut emu = CPU::default();
+    emu.clock_speed = ClockSpeed::InstructionsPerFrame(7);
+    emu.load_rom(ROM).unwrap();
+    emu
+}
+
+// Sections in the order and layout `SavedState::encode` writes them.
+fn encode_sections(mode: u8, sections: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(VERSION.to_le_bytes());
+    bytes.push(mode);
+    for (tag, data) in sections {
+        bytes.extend(*tag);
+        bytes.extend((data.len() as u32).to_le_bytes());
+        bytes.extend(data);
+    }
+    bytes
+}
+
+fn cpu_section(pc: u16) -> Vec<u8> {
+    let mut data = pc.to_le_bytes().to_vec();
+    data.extend([0; 2 + 16]);
+    data.push(0xFF);
+    data.extend([0; 8 + 16 + 2]);
+    data.push(0xFF);
+    data
+}
+
+#[test]
+fn test_round_trip() {
+    let mut emu = make_emu();
+    for _ in 0..5 {
+        emu.run_frame().unwrap();
+    }
+    let bytes = emu.save_state().unwrap().to_bytes();
+    let state = SavedState::decode(&bytes).unwrap();
+    assert_eq!(state.encoded_len(), bytes.len());
+    assert_eq!(state.to_bytes(), bytes);
+
+    let mut restored = make_emu();
+    restored.load_state(state);
+    assert_eq!(restored.pc, emu.pc);
+    assert_eq!(restored.registers, emu.registers);
+    assert_eq!(
+        format!("{:?}", restored.screen),
+        format!("{:?}", emu.screen)
+    );
+    assert_eq!(restored.call_stack.len(), emu.call_stack.len());
+
+    // both continue identically, including random numbers
+    for _ in 0..5 {
+        emu.run_frame().unwrap();
+        restored.run_frame().unwrap();
+        assert_eq!(restored.registers, emu.registers);
+        assert_eq!(restored.random(), emu.random());
+    }
+}
+
+#[test]
+fn test_encode_into_buffer() {
+    let mut emu = make_emu();
+    let state = emu.save_state().unwrap();
+    let len = state.encoded_len();
+    let mut buf = vec![0; len + 10];
+    assert_eq!(state.encode(&mut buf), Ok(len));
+    assert_eq!(&buf[..len], &state.to_bytes()[..]);
+    assert_eq!(
+        state.encode(&mut buf[..len - 1]),
+        Err(SaveStateError::BufferTooSmall)
+    );
+}
+
+#[test]
+fn test_decode_errors() {
+    let mode = Chip8Mode::default() as u8;
+    let decode = |bytes: &[u8]| SavedState::decode(bytes).map(|_| ());
+    assert_eq!(decode(b"not a state"), Err(SaveStateError::NotASaveState));
+
+    let mut newer = encode_sections(mode, &[]);
+    newer[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+    assert_eq!(
+        decode(&newer),
+        Err(SaveStateError::UnsupportedVersion(VERSION + 1))
+    );
+
+    assert_eq!(
+        decode(&encode_sections(mode, &[])),
+        Err(SaveStateError::Corrupt)
+    );
+    assert_eq!(
+        decode(&encode_sections(0x7F, &[(b"CPU ", cpu_section(0x200))])),
+        Err(SaveStateError::Corrupt)
+    );
+
+    let mut emu = make_emu();
+    let bytes = emu.save_state().unwrap().to_bytes();
+    assert_eq!(
+        decode(&bytes[..bytes.len() - 1]),
+        Err(SaveStateError::Corrupt)
+    );
+}
+
+#[test]
+fn test_decode_minimal() {
+    // unknown sections are skipped and missing optional ones are defaulted
+    let mode = Chip8Mode::default() as u8;
+    let bytes = encode_sections(
+        mode,
+        &[
+            (b"CPU ", cpu_section(0x234)),
+            (b"NEW!", vec![1, 2, 3]),
+            (b"MEM ", vec![0xAB; 0x300]),
+        ],
+    );
+    let mut emu = make_emu();
+    emu.load_state(SavedState::decode(&bytes).unwrap());
+    assert_eq!(emu.pc, 0x234);
+    assert_eq!(emu.memory[0x2FF], 0xAB);
+    assert_eq!(emu.memory[0x300], 0);
+    assert_eq!(emu.delay_timer, 0);
+}
+
+#[test]
+fn test_portability() {
+    // a COSMAC state loads in any build with COSMAC enabled
+    let bytes = encode_sections(0, &[(b"CPU ", cpu_section(0x200)), (b"MEM ", vec![1])]);
+    let result = SavedState::decode(&bytes).map(|_| ());
+    if cfg!(feature = "cosmac") {
+        assert_eq!(result, Ok(()));
+    } else {
+        assert_eq!(result, Err(SaveStateError::ModeNotEnabled("cosmac")));
+    }
+
+    // high resolution needs SUPER-CHIP or XO-CHIP support
+    let mode = Chip8Mode::default() as u8;
+    let mut display = vec![1, 1, 1];
+    display.extend([0xFF; 128 * 64 / 8]);
+    let bytes = encode_sections(
+        mode,
+        &[
+            (b"CPU ", cpu_section(0x200)),
+            (b"MEM ", vec![]),
+            (b"DISP", display),
+        ],
+    );
+    let result = SavedState::decode(&bytes).map(|_| ());
+    if cfg!(any(feature = "super-chip", feature = "xo-chip")) {
+        assert_eq!(result, Ok(()));
+    } else {
+        assert_eq!(result, Err(SaveStateError::Incompatible));
+    }
+
+    // and memory past 4 KiB needs XO-CHIP
+    let bytes = encode_sections(
+        mode,
+        &[(b"CPU ", cpu_section(0x200)), (b"MEM ", vec![1; 0x1001])],
+    );
+    let result = SavedState::decode(&bytes).map(|_| ());
+    if cfg!(feature = "xo-chip") {
+        assert_eq!(result, Ok(()));
+    } else {
+        assert_eq!(result, Err(SaveStateError::Incompatible));
+    }
+}
+
+#[test]
+fn test_load_version_1() {
+    // version 1 only stored the timers themselves
+    let mode = Chip8Mode::default() as u8;
+    let mut bytes = encode_sections(
+        mode,
+        &[
+            (b"CPU ", cpu_section(0x200)),
+            (b"TIME", vec![3, 4]),
+            (b"MEM ", vec![]),
+        ],
+    );
+    bytes[4..6].copy_from_slice(&1u16.to_le_bytes());
+    let mut emu = make_emu();
+    emu.load_state(SavedState::decode(&bytes).unwrap());
+    assert_eq!((emu.delay_timer, emu.sound_timer), (3, 4));
+}
+
+// Random draws, timers, sound and key checks, plus an audio pattern and pitch
+// in XO-CHIP.
+fn trajectory_rom(mode: Chip8Mode) -> Vec<u8> {
+    // 0x200: I = 0x300; 0x202: V0 = rand; 0x204: draw V0, V1, 1;
+    // 0x206: delay = V0; 0x208: sound = V1; 0x20A: skip if key V1 down;
+    // 0x20C: V1 += 1
+    let mut rom = vec![
+        0xA3, 0x00, 0xC0, 0xFF, 0xD0, 0x11, 0xF0, 0x15, 0xF1, 0x18, 0xE1, 0x9E, 0x71, 0x01,
+    ];
+    // 0x20E: audio pattern = [I]; 0x210: pitch = V0, in XO-CHIP only (mode 2,
+    // which may not be enabled)
+    if Chip8Mode::try_from(2) == Ok(mode) {
+        rom.extend([0xF0, 0x02, 0xF0, 0x3A]);
+    }
+    // jump 0x202
+    rom.extend([0x12, 0x02]);
+    rom.resize(0x100, 0);
+    rom.extend([0x5A, 0xC3, 0x99, 0xFF, 0x00, 0x81, 0x3C, 0x7E]);
+    rom.extend([0x11, 0x22, 0x44, 0x88, 0xF0, 0x0F, 0xAA, 0x55]);
+    rom
+}
+
+#[test]
+fn test_trajectory() {
+    let modes = (0..3).filter_map(|mode| Chip8Mode::try_from(mode).ok());
+    for mode in modes {
+        let mut emu = CPU::new(mode);
+        emu.clock_speed = ClockSpeed::Hertz(700);
+        emu.load_rom(&trajectory_rom(mode)).unwrap();
+        // uneven slices of time leave cycles and part of a frame pending
+        let step = |emu: &mut CPU, idx: u8| {
+            emu.press_key(idx % 16);
+            emu.release_key((idx + 15) % 16);
+            emu.run(
+                Budget::Time(Duration::from_micros(7_919)),
+                &StopConditions::default(),
+            )
+            .unwrap();
+        };
+        for idx in 0..23 {
+            step(&mut emu, idx);
+        }
+
+        let bytes = emu.save_state().unwrap().to_bytes();
+        let mut restored = CPU::new(mode);
+        restored.clock_speed = emu.clock_speed;
+        restored.load_state(SavedState::decode(&bytes).unwrap());
+        for idx in 23..100 {
+            step(&mut emu, idx);
+            step(&mut restored, idx);
+            assert_eq!(
+                restored.save_state().unwrap().to_bytes(),
+                emu.save_state().unwrap().to_bytes(),
+                "{} diverged after step {}",
+                mode,
+                idx
+            );
+            let (mut expected, mut actual) = ([0.0; 200], [0.0; 200]);
+            let dur = Duration::from_millis(4);
+            assert_eq!(
+                restored.read_beep_samples_to(dur, &mut actual),
+                emu.read_beep_samples_to(dur, &mut expected)
+            );
+            assert_eq!(actual, expected);
+        }
+    }
+}
@@ -0,0 +1,146 @@
+#![cfg(feature = "alloc")]
+
+use chip8_core::script::{KeyAction, ScriptEvent};
+use chip8_core::*;
+use core::time::Duration;
+
+// 0x200: jump to itself
+const ROM: &[u8] = &[0x12, 0x00];
+
+#[test]
+fn test_script_round_trip() {
+    let script: InputScript = "
+        # select an option
+        10 press a
+        12 RELEASE A   # trailing comment
+
+        5 press 3
+    "
+    .parse()
+    .unwrap();
+    assert_eq!(
+        script.events(),
+        [
+            ScriptEvent {
+                frame: 5,
+                action: KeyAction::Press,
+                key: 3
+            },
+            ScriptEvent {
+                frame: 10,
+                action: KeyAction::Press,
+                key: 0xA
+            },
+            ScriptEvent {
+                frame: 12,
+                action: KeyAction::Release,
+                key: 0xA
+            },
+        ]
+    );
+    assert_eq!(script.to_string(), "5 press 3\n10 press A\n12 release A\n");
+    assert_eq!(script.to_string().parse(), Ok(script.clone()));
+    assert_eq!(script, InputScript::new().tap(10, 0xA, 2).press(5, 3));
+    assert_eq!(script.last_frame(), Some(12));
+}
+
+#[test]
+fn test_script_parse_errors() {
+    for (text, line) in [
+        ("1 press 1\nx press 1", 2),
+        ("1 hold 1", 1),
+        ("\n\n1 press 10", 3),
+        ("1 press", 1),
+        ("1 press 1 2", 1),
+    ] {
+        assert_eq!(
+            text.parse::<InputScript>(),
+            Err(script::InputScriptParseError { line }),
+            "{:?}",
+            text
+        );
+    }
+}
+
+#[test]
+fn test_replay_timing() {
+    let script = InputScript::new().press(0, 1).tap(2, 5, 1).release(3, 1);
+    let mut emu = CPU::default();
+    emu.load_rom(ROM).unwrap();
+    emu.play_input(script.clone());
+    // frame 0 starts straight away
+    assert!(emu.is_key_down(1));
+    let mut held = Vec::new();
+    for _ in 0..5 {
+        emu.run_frame().unwrap();
+        held.push((emu.is_key_down(1), emu.is_key_down(5)));
+    }
+    assert_eq!(
+        held,
+        [
+            (true, false),
+            (true, true),
+            (false, false),
+            (false, false),
+            (false, false),
+        ]
+    );
+    let replay = emu.input_replay().unwrap();
+    assert_eq!(replay.frame(), 5);
+    assert!(replay.is_finished());
+    assert_eq!(emu.stop_input(), Some(script));
+    assert!(emu.input_replay().is_none());
+}
+
+#[test]
+fn test_replay_seen_in_its_frame() {
+    // 0x200: V1 = 1; 0x202: skip if key V1 is down; 0x204: jump 0x202;
+    // 0x206: jump 0x206
+    const POLL_ROM: &[u8] = &[0x61, 0x01, 0xE1, 0x9E, 0x12, 0x02, 0x12, 0x06];
+    let mut emu = CPU::default();
+    emu.clock_speed = ClockSpeed::InstructionsPerFrame(10);
+    emu.load_rom(POLL_ROM).unwrap();
+    emu.play_input(InputScript::new().press(1, 1));
+    emu.run_frame().unwrap();
+    assert_ne!(emu.pc, 0x206);
+    emu.run_frame().unwrap();
+    assert_eq!(emu.pc, 0x206);
+}
+
+#[test]
+fn test_replay_ignores_budgets() {
+    let script = InputScript::new().tap(3, 7, 4);
+    let mut by_frame = CPU::default();
+    by_frame.load_rom(ROM).unwrap();
+    by_frame.play_input(script);
+    let mut by_time = by_frame.clone();
+    let held = |emu: &CPU| (emu.input_replay().unwrap().frame(), emu.is_key_down(7));
+
+    let mut frames = vec![held(&by_frame)];
+    for _ in 0..10 {
+        by_frame.run_frame().unwrap();
+        frames.push(held(&by_frame));
+    }
+    // the same frames in slices of time that don't line up with them
+    let mut slices = vec![held(&by_time)];
+    while slices.len() < frames.len() {
+        by_time
+            .run(
+                Budget::Time(Duration::from_millis(7)),
+                &StopConditions::default(),
+            )
+            .unwrap();
+        if slices.last() != Some(&held(&by_time)) {
+            slices.push(held(&by_time));
+        }
+    }
+    assert_eq!(slices, frames);
+}
+
+#[test]
+fn test_load_rom_stops_replay() {
+    let mut emu = CPU::default();
+    emu.play_input(InputScript::new().press(5, 1));
+    emu.load_rom(ROM).unwrap();
+    assert!(emu.input_replay().is_none());
+}
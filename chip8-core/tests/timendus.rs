@@ -5,8 +5,8 @@ const IBM_ROM: &[u8] = include_bytes!("./timendus-tests/2-ibm-logo.ch8");
 const CORAX_ROM: &[u8] = include_bytes!("./timendus-tests/3-corax+.ch8");
 const FLAGS_ROM: &[u8] = include_bytes!("./timendus-tests/4-flags.ch8");
 const QUIRKS_ROM: &[u8] = include_bytes!("./timendus-tests/5-quirks.ch8");
-// Not sure how to make an automated test for this ROM
-// const KEYPAD_ROM: &[u8] = include_bytes!("./timendus-tests/6-keypad.ch8");
+#[cfg(feature = "alloc")]
+const KEYPAD_ROM: &[u8] = include_bytes!("./timendus-tests/6-keypad.ch8");
 
 fn insta_settings() -> insta::Settings {
     let mut settings = insta::Settings::clone_current();
@@ -23,6 +23,32 @@ fn make_emu(rom: &[u8]) -> CPU {
     emu
 }
 
+#[cfg(feature = "alloc")]
+fn run_frames(emu: &mut CPU, frames: u32) {
+    for _ in 0..frames {
+        emu.run_frame().unwrap();
+    }
+}
+
+// The screen at its current resolution, for snapshots that are easier to
+// check by eye than the debug output.
+#[cfg(feature = "alloc")]
+fn screen_text(emu: &CPU) -> String {
+    let (width, height) = emu.screen.dimensions();
+    let scale = display::SCREEN_WIDTH / width;
+    let mut out = String::new();
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = emu
+                .screen
+                .read_pixel_unchecked((x * scale) as u8, (y * scale) as u8);
+            out.push(if pixel == 0 { '.' } else { '#' });
+        }
+        out.push('\n');
+    }
+    out
+}
+
 #[test]
 fn test_splash_screen() {
     let _guard = insta_settings().bind_to_scope();
@@ -102,3 +128,90 @@ fn test_quirks() {
     }
     insta::assert_debug_snapshot!(emu);
 }
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_quirks_menu() {
+    let _guard = insta_settings().bind_to_scope();
+    let mode = Chip8Mode::default() as u8;
+    // picking the platform from the menu should end up where setting it in
+    // memory beforehand does
+    let mut expected = make_emu(QUIRKS_ROM);
+    expected.memory[0x1FF] = mode + 1;
+    run_frames(&mut expected, 1200);
+    let results = screen_text(&expected);
+    assert!(results.contains('#'), "the screen is blank");
+    insta::assert_snapshot!(results);
+
+    let mut by_number = make_emu(QUIRKS_ROM);
+    by_number.play_input(InputScript::new().tap(60, mode + 1, 10));
+    run_frames(&mut by_number, 1200);
+    assert_eq!(screen_text(&by_number), results);
+
+    // F moves down the menu and A picks the highlighted entry
+    let mut script = InputScript::new();
+    for idx in 0..mode as u32 {
+        script = script.tap(60 + idx * 20, 0xF, 10);
+    }
+    let mut by_cursor = make_emu(QUIRKS_ROM);
+    by_cursor.play_input(script.tap(60 + mode as u32 * 20, 0xA, 10));
+    run_frames(&mut by_cursor, 1200);
+    assert_eq!(screen_text(&by_cursor), results);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_keypad_ex9e() {
+    let _guard = insta_settings().bind_to_scope();
+    let mut emu = make_emu(KEYPAD_ROM);
+    let script: InputScript = "
+        # pick EX9E from the menu
+        60 press 1
+        70 release 1
+        # then hold down 1 and F
+        100 press 1
+        100 press F
+    "
+    .parse()
+    .unwrap();
+    emu.play_input(script);
+    run_frames(&mut emu, 200);
+    insta::assert_snapshot!(screen_text(&emu));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_keypad_exa1() {
+    let _guard = insta_settings().bind_to_scope();
+    let mut emu = make_emu(KEYPAD_ROM);
+    emu.play_input(
+        InputScript::new()
+            .tap(60, 2, 10)
+            .press(100, 5)
+            .press(100, 0xA),
+    );
+    run_frames(&mut emu, 200);
+    insta::assert_snapshot!(screen_text(&emu));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_keypad_fx0a() {
+    let _guard = insta_settings().bind_to_scope();
+    let mut emu = make_emu(KEYPAD_ROM);
+    emu.play_input(InputScript::new().tap(60, 3, 10).tap(100, 7, 10));
+    run_frames(&mut emu, 200);
+    insta::assert_snapshot!(screen_text(&emu));
+}
+
+// Timendus's test notices when `FX0A` doesn't wait for the key to be released
+#[cfg(feature = "alloc")]
+#[test]
+fn test_keypad_fx0a_on_press() {
+    let _guard = insta_settings().bind_to_scope();
+    let mut emu = make_emu(KEYPAD_ROM);
+    emu.quirks.key_wait_on_press = true;
+    emu.play_input(InputScript::new().tap(60, 3, 10).tap(100, 7, 10));
+    run_frames(&mut emu, 200);
+    insta::assert_snapshot!(screen_text(&emu));
+}
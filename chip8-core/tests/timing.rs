@@ -0,0 +1,140 @@
+use chip8_core::*;
+use core::time::Duration;
+
+// 0x200: V0 += 1; 0x202: jump 0x200
+const COUNT_ROM: &[u8] = &[0x70, 0x01, 0x12, 0x00];
+
+fn run_for(emu: &mut CPU, dur: Duration) -> StopReason {
+    emu.run(Budget::Time(dur), &StopConditions::default()).unwrap()
+}
+
+#[test]
+fn test_instructions_per_frame() {
+    let mut emu = CPU::default();
+    emu.clock_speed = ClockSpeed::InstructionsPerFrame(10);
+    emu.load_rom(COUNT_ROM).unwrap();
+    assert_eq!(emu.clock_speed, ClockSpeed::InstructionsPerFrame(10));
+
+    // nothing runs until the first timer tick
+    run_for(&mut emu, Duration::from_millis(10));
+    assert_eq!(emu.registers[Register::V0], 0);
+    run_for(&mut emu, Duration::from_millis(10));
+    assert_eq!(emu.registers[Register::V0], 5);
+    // two ticks at once run two frames' worth of cycles
+    run_for(&mut emu, Duration::from_millis(34));
+    assert_eq!(emu.registers[Register::V0], 15);
+}
+
+#[test]
+fn test_hertz() {
+    let mut emu = CPU::default();
+    emu.clock_speed = ClockSpeed::Hertz(100);
+    emu.load_rom(COUNT_ROM).unwrap();
+    run_for(&mut emu, Duration::from_secs(1));
+    assert_eq!(emu.registers[Register::V0], 50);
+}
+
+#[test]
+fn test_parse_clock_speed() {
+    assert_eq!("500".parse(), Ok(ClockSpeed::Hertz(500)));
+    assert_eq!("700Hz".parse(), Ok(ClockSpeed::Hertz(700)));
+    assert_eq!("15 ipf".parse(), Ok(ClockSpeed::InstructionsPerFrame(15)));
+    assert!("fast".parse::<ClockSpeed>().is_err());
+    assert_eq!(ClockSpeed::InstructionsPerFrame(15).cycles_per_second(), 900);
+    assert_eq!(ClockSpeed::Hertz(500).to_string(), "500 Hz");
+}
+
+#[test]
+fn test_run_frame() {
+    let mut emu = CPU::default();
+    emu.clock_speed = ClockSpeed::InstructionsPerFrame(10);
+    emu.load_rom(COUNT_ROM).unwrap();
+    let report = emu.run_frame().unwrap();
+    assert_eq!(report.instructions, 10);
+    assert!(!report.screen_changed);
+    assert!(!report.sound_active);
+    assert_eq!(report.stop_reason, StopReason::BudgetExhausted);
+    assert_eq!(emu.registers[Register::V0], 5);
+
+    // 500 Hz doesn't divide evenly into 60 frames; the remainder carries over
+    emu.clock_speed = ClockSpeed::Hertz(500);
+    let total: u32 = (0..60).map(|_| emu.run_frame().unwrap().instructions).sum();
+    assert!((499..=501).contains(&total));
+}
+
+#[test]
+fn test_run_frame_waits() {
+    // 0x200: V0 = 1; 0x202: sound = V0; 0x204: draw 0,0,1; 0x206: wait for key
+    const ROM: &[u8] = &[0x60, 0x01, 0xF0, 0x18, 0xD0, 0x01, 0xF1, 0x0A];
+    let mut emu = CPU::default();
+    emu.quirks.display_wait = true;
+    emu.load_rom(ROM).unwrap();
+
+    let report = emu.run_frame().unwrap();
+    assert_eq!(report.instructions, 3);
+    assert!(report.screen_changed);
+    assert!(report.sound_active);
+    assert_eq!(report.stop_reason, StopReason::WaitingForVBlank);
+
+    let report = emu.run_frame().unwrap();
+    assert_eq!(report.instructions, 1);
+    assert!(!report.screen_changed);
+    assert!(!report.sound_active);
+    assert_eq!(report.stop_reason, StopReason::WaitingForKey);
+
+    let report = emu.run_frame().unwrap();
+    assert_eq!(report.instructions, 0);
+    assert_eq!(report.stop_reason, StopReason::WaitingForKey);
+}
+
+#[test]
+fn test_deterministic_pacing() {
+    // 0x200: V0 += 1; 0x202: V1 = delay; 0x204: jump 0x200
+    const ROM: &[u8] = &[0x70, 0x01, 0xF1, 0x07, 0x12, 0x00];
+    let run = |steps: &[u64]| {
+        let mut emu = CPU::default();
+        emu.clock_speed = ClockSpeed::Hertz(700);
+        emu.load_rom(ROM).unwrap();
+        emu.delay_timer = 0xFF;
+        for &nanos in steps {
+            run_for(&mut emu, Duration::from_nanos(nanos));
+        }
+        (emu.pc, emu.registers, emu.delay_timer)
+    };
+    // one second, split up in different ways
+    let whole = run(&[1_000_000_000]);
+    assert_eq!(whole.1[Register::V0], 234);
+    assert_eq!(whole.2, 0xFF - 60);
+    let mut frames = vec![16_666_667; 59];
+    frames.push(16_666_647);
+    assert_eq!(run(&frames), whole);
+    assert_eq!(run(&[1_000_000; 1000]), whole);
+    assert_eq!(run(&[333_333_333, 333_333_333, 333_333_334]), whole);
+}
+
+#[test]
+fn test_stop_conditions() {
+    // 0x200: V0 += 1; 0x202: jump 0x200
+    let mut emu = CPU::default();
+    emu.load_rom(COUNT_ROM).unwrap();
+    let halt = |cpu: &CPU| cpu.registers[Register::V0] == 3;
+    let reason = emu.run(Budget::Frames(10), &StopConditions::until(&halt));
+    assert_eq!(reason, Ok(StopReason::PredicateHit(0x202)));
+    assert_eq!(run_for(&mut emu, Duration::from_millis(100)), StopReason::BudgetExhausted);
+
+    // 0x200: wait for key; 0x202: jump 0x202
+    const ROM: &[u8] = &[0xF0, 0x0A, 0x12, 0x02];
+    let mut emu = CPU::default();
+    emu.load_rom(ROM).unwrap();
+    let stop = StopConditions {
+        key_wait: true,
+        infinite_loop: true,
+        ..Default::default()
+    };
+    assert_eq!(emu.run(Budget::Frames(1), &stop), Ok(StopReason::WaitingForKey));
+    // without stopping, the wait idles until the budget is used up
+    assert_eq!(run_for(&mut emu, Duration::from_secs(1)), StopReason::BudgetExhausted);
+    emu.press_key(0x5);
+    emu.release_key(0x5);
+    assert_eq!(emu.run(Budget::Frames(1), &stop), Ok(StopReason::InfiniteLoop(0x202)));
+}
@@ -0,0 +1,112 @@
+#![cfg(feature = "std")]
+
+use chip8_core::trace::{
+    first_divergence, parse_trace, TraceField, TraceFormat, TraceWriter, MAGIC, RECORD_LEN,
+};
+use chip8_core::*;
+
+const ROM: &[u8] = &[
+    0x60, 0x7B, // 0x200: V0 = 123
+    0x61, 0x06, // 0x202: V1 = 6
+    0x22, 0x0A, // 0x204: call 0x20A
+    0x80, 0x16, // 0x206: V0 = V1 >> 1, or V0 >>= 1 with the shift quirk
+    0x12, 0x08, // 0x208: jump 0x208
+    0xA3, 0x21, // 0x20A: I = 0x321
+    0xF1, 0x15, // 0x20C: delay = V1
+    0x00, 0xEE, // 0x20E: return
+];
+
+fn trace(shift: bool, steps: usize) -> Vec<TraceRecord> {
+    let mut emu = CPU::default();
+    emu.quirks.shift = shift;
+    emu.load_rom(ROM).unwrap();
+    let mut records = Vec::new();
+    let mut tracer = Tracer::new(|record: &TraceRecord| records.push(*record));
+    for _ in 0..steps {
+        emu.step_observed(&mut tracer).unwrap();
+    }
+    records
+}
+
+#[test]
+fn test_records() {
+    let records = trace(false, 6);
+    let pcs: Vec<u16> = records.iter().map(|record| record.pc).collect();
+    assert_eq!(pcs, [0x200, 0x202, 0x204, 0x20A, 0x20C, 0x20E]);
+    // each record holds the state after its instruction
+    assert_eq!(records[0].registers[0], 123);
+    assert_eq!(records[2].stack_depth, 1);
+    assert_eq!(records[3].index, 0x321);
+    assert_eq!(records[4].delay_timer, 6);
+    assert_eq!(records[5].stack_depth, 0);
+    assert_eq!(
+        records[0].to_string(),
+        "PC:0200 OP:607B V:7B000000000000000000000000000000 I:0000 DT:00 ST:00 SP:00 ; v0 := 0x7B"
+    );
+}
+
+#[test]
+fn test_text_round_trip() {
+    let records = trace(false, 8);
+    let text: String = records
+        .iter()
+        .map(|record| format!("{}\n", record))
+        .collect();
+    assert_eq!(parse_trace(text.as_bytes()), Ok(records.clone()));
+    // the mnemonic is only there for people
+    let line = records[1].to_string();
+    let stripped = line.split(';').next().unwrap();
+    assert_eq!(stripped.parse(), Ok(records[1]));
+
+    let long = "PC:0200 OP:F0001234 V:00000000000000000000000000000000 I:1234 DT:00 ST:00 SP:00";
+    let record: TraceRecord = long.parse().unwrap();
+    assert_eq!((record.opcode, record.operand), (0xF000, 0x1234));
+    assert_eq!(record.to_string(), format!("{} ; i := long 0x1234", long));
+
+    let bad = format!("# comment\n\n{}\nPC:0200 OP:6\n", records[0]);
+    assert_eq!(parse_trace(bad.as_bytes()).map_err(|e| e.record), Err(4));
+}
+
+#[test]
+fn test_writer() {
+    let records = trace(false, 8);
+    for format in [TraceFormat::Text, TraceFormat::Binary] {
+        let mut emu = CPU::default();
+        emu.quirks.shift = false;
+        emu.load_rom(ROM).unwrap();
+        let mut writer = TraceWriter::new(Vec::new(), format).unwrap();
+        for _ in 0..8 {
+            emu.step_observed(&mut writer).unwrap();
+        }
+        let bytes = writer.finish().unwrap();
+        if format == TraceFormat::Binary {
+            assert_eq!(bytes[..4], MAGIC);
+            assert_eq!(bytes.len(), 6 + 8 * RECORD_LEN);
+        }
+        assert_eq!(parse_trace(&bytes), Ok(records.clone()));
+    }
+}
+
+#[test]
+fn test_divergence() {
+    let expected = trace(false, 8);
+    assert_eq!(first_divergence(expected.clone(), expected.clone()), None);
+
+    let actual = trace(true, 8);
+    let divergence = first_divergence(expected.clone(), actual).unwrap();
+    assert_eq!(divergence.index, 6);
+    let (left, right) = (divergence.left.unwrap(), divergence.right.unwrap());
+    assert_eq!(left.pc, 0x206);
+    assert_eq!(
+        left.differences(&right).collect::<Vec<_>>(),
+        [
+            TraceField::Register(Register::V0),
+            TraceField::Register(Register::VF)
+        ]
+    );
+
+    let divergence = first_divergence(expected.clone(), trace(false, 5)).unwrap();
+    assert_eq!(divergence.index, 5);
+    assert_eq!(divergence.left, Some(expected[5]));
+    assert_eq!(divergence.right, None);
+}
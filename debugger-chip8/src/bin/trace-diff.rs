@@ -0,0 +1,59 @@
+// Compares two instruction traces, as written by `chip8_core::trace`, and
+// reports where they first differ.
+//
+//     trace-diff <expected> <actual>
+//
+// Either trace can be in the text or the binary format. Exits with 0 if the
+// traces match, 1 if they diverge and 2 if one couldn't be read.
+
+extern crate chip8_core;
+
+use chip8_core::trace::{first_divergence, parse_trace};
+use chip8_core::TraceRecord;
+use std::process::ExitCode;
+
+fn read(path: &str) -> Result<Vec<TraceRecord>, String> {
+    let data = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    parse_trace(&data).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn describe(record: Option<&TraceRecord>) -> String {
+    match record {
+        Some(record) => record.to_string(),
+        None => "<end of trace>".into(),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() != 2 {
+        eprintln!("usage: trace-diff <expected> <actual>");
+        return ExitCode::from(2);
+    }
+    let (expected, actual) = match (read(&args[0]), read(&args[1])) {
+        (Ok(expected), Ok(actual)) => (expected, actual),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    let divergence = match first_divergence(expected.iter().copied(), actual.iter().copied()) {
+        Some(divergence) => divergence,
+        None => {
+            println!("Traces match ({} instructions)", expected.len());
+            return ExitCode::SUCCESS;
+        }
+    };
+    println!("Traces diverge at instruction {}", divergence.index + 1);
+    if let Some(prev) = divergence.index.checked_sub(1) {
+        println!("  previous: {}", expected[prev]);
+    }
+    println!("  expected: {}", describe(divergence.left.as_ref()));
+    println!("  actual:   {}", describe(divergence.right.as_ref()));
+    if let (Some(left), Some(right)) = (divergence.left, divergence.right) {
+        let fields: Vec<String> = left.differences(&right).map(|f| f.to_string()).collect();
+        println!("  differs in: {}", fields.join(", "));
+    }
+    ExitCode::from(1)
+}
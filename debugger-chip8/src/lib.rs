@@ -6,7 +6,7 @@ extern crate once_cell;
 extern crate strum;
 
 use chip8_core::display::{COLOR_SET, SCREEN_HEIGHT, SCREEN_WIDTH};
-use chip8_core::{Chip8Mode, Error, Register, CPU};
+use chip8_core::{Budget, Chip8Mode, ClockSpeed, Error, Register, StopConditions, StopReason, CPU};
 use enum_map::{enum_map, EnumMap};
 use funty::Unsigned;
 use once_cell::sync::Lazy;
@@ -84,6 +84,7 @@ enum DebugCommand {
     SetAddr,
     #[strum(serialize = "setreg")]
     SetReg,
+    Speed,
     Step,
     Timers,
     ToggleKey,
@@ -114,6 +115,7 @@ static CMD_ARGC: Lazy<EnumMap<DebugCommand, RangeInclusive<usize>>> = Lazy::new(
         DebugCommand::Resume => 0..=0,
         DebugCommand::SetAddr => 1..=1,
         DebugCommand::SetReg => 2..=2,
+        DebugCommand::Speed => 0..=1,
         DebugCommand::Step => 0..=0,
         DebugCommand::Timers => 0..=0,
         DebugCommand::ToggleKey => 1..=1,
@@ -145,6 +147,7 @@ static CMD_HELP_TEXT: Lazy<EnumMap<DebugCommand, &'static str>> = Lazy::new(|| {
         DebugCommand::Resume => "resume - start or continue execution",
         DebugCommand::SetAddr => "setaddr <x> - set the address register to <x>",
         DebugCommand::SetReg => "set <x> <y> - set register <x> to byte <y>",
+        DebugCommand::Speed => "speed [rate] - query the clock speed or change it to <rate> (e.g. `500hz` or `15ipf`)",
         DebugCommand::Step => "step - execute only the next instruction",
         DebugCommand::Timers => "timers - display the current timer status",
         DebugCommand::ToggleKey => "toggle_key <key> - toggle holding a key down",
@@ -178,6 +181,7 @@ static CMD_FUNCS: Lazy<EnumMap<DebugCommand, CommandBody>> = Lazy::new(|| {
         DebugCommand::Resume => Chip8Debugger::cmd_resume,
         DebugCommand::SetAddr => Chip8Debugger::cmd_setaddr,
         DebugCommand::SetReg => Chip8Debugger::cmd_setreg,
+        DebugCommand::Speed => Chip8Debugger::cmd_speed,
         DebugCommand::Step => Chip8Debugger::cmd_step,
         DebugCommand::Timers => Chip8Debugger::cmd_timers,
         DebugCommand::ToggleKey => Chip8Debugger::cmd_toggle_key,
@@ -226,12 +230,12 @@ impl Chip8Debugger {
             self.cpu
                 .disassemble(idx)
                 .ok_or(format!("Unknown instruction at {}", idx).into())
-                .map(Into::into)
+                .map(|inst| inst.to_string())
         } else {
             self.cpu
                 .disassemble_next()
                 .ok_or("Unknown instruction ahead".into())
-                .map(Into::into)
+                .map(|inst| inst.to_string())
         }
     }
 
@@ -294,7 +298,7 @@ impl Chip8Debugger {
     fn cmd_help(&mut self, args: &[&str]) -> CommandResult {
         if !args.is_empty() {
             if let Ok(cmd) = DebugCommand::from_str(args[0]) {
-                Ok(format!("{}", CMD_HELP_TEXT[cmd]))
+                Ok(CMD_HELP_TEXT[cmd].to_string())
             } else {
                 Err(format!("Unknown command `{}`.\nFor help, use `help`.", args[0]).into())
             }
@@ -349,7 +353,7 @@ impl Chip8Debugger {
         self.cpu
             .disassemble_next()
             .ok_or("Unknown instruction ahead".into())
-            .map(Into::into)
+            .map(|inst| inst.to_string())
     }
 
     fn cmd_pause(&mut self, _args: &[&str]) -> CommandResult {
@@ -417,6 +421,15 @@ impl Chip8Debugger {
         Ok("".into())
     }
 
+    fn cmd_speed(&mut self, args: &[&str]) -> CommandResult {
+        if !args.is_empty() {
+            self.cpu.clock_speed = ClockSpeed::from_str(args[0])?;
+            Ok("".into())
+        } else {
+            Ok(format!("{}", self.cpu.clock_speed))
+        }
+    }
+
     fn cmd_step(&mut self, _args: &[&str]) -> CommandResult {
         if !self.has_rom {
             return Err(Box::new(Error::NoRomLoaded));
@@ -486,22 +499,23 @@ impl Chip8Debugger {
         }
     }
 
-    pub fn emulate_until_breakpoints(&mut self, dur: Duration) -> Result<(), Error> {
+    pub fn emulate_until_breakpoints(&mut self, dur: Duration) -> Result<StopReason, Error> {
         if self.paused {
-            return Ok(());
+            // nothing is run while paused, so the whole budget goes unused
+            return Ok(StopReason::BudgetExhausted);
         }
         if !self.has_rom {
             return Err(Error::NoRomLoaded);
         }
-        self.cpu
-            .emulate_for_until(dur, |cpu| self.breaks.contains(&cpu.pc))
-            .map_err(|err| match err {
-                Error::EarlyExitRequested => {
-                    self.paused = true;
-                    Error::Breakpoint(self.cpu.pc)
-                }
-                x => x,
-            })
+        let breaks = &self.breaks;
+        let halt = |cpu: &CPU| breaks.contains(&cpu.pc);
+        let reason = self
+            .cpu
+            .run(Budget::Time(dur), &StopConditions::until(&halt))?;
+        if matches!(reason, StopReason::PredicateHit(_) | StopReason::Exited) {
+            self.paused = true;
+        }
+        Ok(reason)
     }
 }
 
@@ -1,4 +1,6 @@
-use chip8_core::{audio, Chip8Mode, Error, CPU};
+use chip8_core::{audio, Budget, Chip8Mode, Error, StopConditions, StopReason, CPU};
+
+use crate::flags::FileFlagStorage;
 
 use std::fs;
 use std::path::Path;
@@ -13,16 +15,21 @@ pub struct Emulator {
     has_rom: bool,
     breakpoints: Vec<u16>,
     last_time: Instant,
+    // scale of the last buffer handed out by `display_buffer`
+    drawn_scale: Option<usize>,
     audio_output: (OutputStream, OutputStreamHandle),
 }
 
 impl Emulator {
     pub fn new() -> Emulator {
+        let mut cpu = CPU::new(Chip8Mode::XoChip);
+        cpu.hold_taps = true;
         Emulator {
-            cpu: CPU::new(Chip8Mode::XoChip),
+            cpu,
             has_rom: false,
             breakpoints: vec![],
             last_time: Instant::now(),
+            drawn_scale: None,
             audio_output: OutputStream::try_default().unwrap(),
         }
     }
@@ -33,26 +40,28 @@ impl Emulator {
         last.elapsed()
     }
 
-    pub fn update(&mut self) -> Result<(), Error> {
+    pub fn update(&mut self) -> Result<StopReason, Error> {
         let dt = self.step();
         if !self.has_rom {
             return Err(Error::NoRomLoaded);
         }
 
-        self.cpu.emulate_for_until(dt, |cpu| self.breakpoints.contains(&cpu.pc))?;
+        let breakpoints = &self.breakpoints;
+        let halt = |cpu: &CPU| breakpoints.contains(&cpu.pc);
+        let reason = self
+            .cpu
+            .run(Budget::Time(dt), &StopConditions::until(&halt))?;
         if let Some(samples) = self.cpu.get_beep_samples(dt) {
             let buf = SamplesBuffer::new(1, audio::SAMPLE_RATE, samples);
             self.audio_output.1.play_raw(buf).unwrap();
         }
-        Ok(())
+        Ok(reason)
     }
 
     pub fn load_rom_file(&mut self, file: impl AsRef<Path>) -> Result<(), Error> {
         let contents = fs::read(file.as_ref());
         if let Ok(contents) = contents {
-            self.cpu.load_rom(&contents[..])?;
-            self.has_rom = true;
-            Ok(())
+            self.load_rom(&contents)
         } else {
             Err(Error::InvalidFile)
         }
@@ -60,19 +69,39 @@ impl Emulator {
 
     pub fn load_rom(&mut self, contents: &[u8]) -> Result<(), Error> {
         self.cpu.load_rom(contents)?;
+        match FileFlagStorage::for_rom(contents) {
+            Some(storage) => self.cpu.set_flag_storage(Box::new(storage)),
+            None => {
+                self.cpu.take_flag_storage();
+            }
+        }
         self.has_rom = true;
         Ok(())
     }
 
-    pub fn display_buffer(&self, scale_factor: usize) -> Vec<u32> {
-        self.cpu.screen.to_buffer(scale_factor, scale_factor)
+    // None if the screen looks the same as in the last buffer.
+    pub fn display_buffer(&mut self, scale_factor: usize) -> Option<Vec<u32>> {
+        let dirty = self.cpu.screen.take_dirty().is_some();
+        if !dirty && self.drawn_scale == Some(scale_factor) {
+            return None;
+        }
+        self.drawn_scale = Some(scale_factor);
+        Some(self.cpu.screen.to_buffer(scale_factor, scale_factor))
+    }
+
+    #[inline]
+    pub fn invalidate_display(&mut self) {
+        self.cpu.screen.invalidate();
     }
 
+    // Keys are queued for the time they happened within the next update,
+    // rather than all landing at its start.
     pub fn key_press(&mut self, key: u8, press: bool) {
+        let at = self.last_time.elapsed();
         if press {
-            self.cpu.press_key(key);
+            self.cpu.press_key_at(key, at);
         } else {
-            self.cpu.release_key(key);
+            self.cpu.release_key_at(key, at);
         }
     }
 }
@@ -0,0 +1,61 @@
+use chip8_core::flags::{FlagStorage, FLAG_COUNT};
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+// Keeps the flag registers of each ROM in its own file under the user's data
+// directory, named after a hash of the ROM so that renaming it doesn't lose
+// them.
+pub struct FileFlagStorage {
+    path: PathBuf,
+}
+
+impl FileFlagStorage {
+    // None if there's no data directory to save to.
+    pub fn for_rom(rom: &[u8]) -> Option<FileFlagStorage> {
+        let dir = data_dir()?.join("desktop-chip8").join("flags");
+        Some(FileFlagStorage {
+            path: dir.join(format!("{:016x}.flags", rom_hash(rom))),
+        })
+    }
+}
+
+impl FlagStorage for FileFlagStorage {
+    fn load(&mut self) -> Option<[u8; FLAG_COUNT]> {
+        let contents = fs::read(&self.path).ok()?;
+        let mut flags = [0; FLAG_COUNT];
+        let len = contents.len().min(FLAG_COUNT);
+        flags[..len].copy_from_slice(&contents[..len]);
+        Some(flags)
+    }
+
+    fn save(&mut self, flags: &[u8; FLAG_COUNT]) {
+        let res = match self.path.parent() {
+            Some(dir) => fs::create_dir_all(dir),
+            None => Ok(()),
+        };
+        if let Err(e) = res.and_then(|_| fs::write(&self.path, flags)) {
+            warn!("Couldn't save flags to {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+fn data_dir() -> Option<PathBuf> {
+    let var = |name| {
+        env::var_os(name)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+    };
+    var("XDG_DATA_HOME")
+        .or_else(|| var("APPDATA"))
+        .or_else(|| var("HOME").map(|home| home.join(".local").join("share")))
+}
+
+// 64-bit FNV-1a, which unlike `DefaultHasher` won't change between Rust
+// releases and orphan everyone's saved flags.
+fn rom_hash(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
@@ -9,6 +9,7 @@ extern crate tracing_subscriber;
 
 pub mod debug_window;
 pub mod emulator;
+pub mod flags;
 
 use softbuffer::GraphicsContext;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -18,7 +19,7 @@ use winit::event_loop::{ControlFlow, EventLoop, EventLoopWindowTarget};
 use winit::window::{Window, WindowBuilder};
 use crate::debug_window::DebugWindow;
 use crate::emulator::Emulator;
-use chip8_core::Error;
+use chip8_core::{Error, StopReason};
 use chip8_core::display::{SCREEN_WIDTH, SCREEN_HEIGHT};
 
 pub const SCALE_FACTOR: usize = 8;
@@ -83,11 +84,15 @@ fn main() {
                                 *control_flow = ControlFlow::Exit;
                             }
                         }
-                        Err(Error::Breakpoint(_)) => {
+                        Ok(StopReason::PredicateHit(_)) => {
                             debug.open(target);
                             info!("Breakpoint reached");
                             running = false;
                         }
+                        Ok(StopReason::Exited) => {
+                            info!("Program exited");
+                            *control_flow = ControlFlow::Exit;
+                        }
                         Err(e) => {
                             error!("{}", e);
                             *control_flow = ControlFlow::Exit;
@@ -97,12 +102,13 @@ fn main() {
                 }
                 let dpi_scale = window.scale_factor();
                 let full_scale = SCALE_FACTOR * dpi_scale as usize;
-                let buf = emu.display_buffer(full_scale);
-                graphics_context.set_buffer(
-                    &buf,
-                    (SCREEN_WIDTH * full_scale) as u16,
-                    (SCREEN_HEIGHT * full_scale) as u16,
-                );
+                if let Some(buf) = emu.display_buffer(full_scale) {
+                    graphics_context.set_buffer(
+                        &buf,
+                        (SCREEN_WIDTH * full_scale) as u16,
+                        (SCREEN_HEIGHT * full_scale) as u16,
+                    );
+                }
             }
             Event::WindowEvent { event, window_id } => {
                 if window_id == window.id() {
@@ -151,6 +157,10 @@ fn main() {
                     debug.handle_event(event);
                 }
             }
+            // the window lost its contents, so upload them again next time
+            Event::RedrawRequested(window_id) if window_id == window.id() => {
+                emu.invalidate_display();
+            }
             _ => {}
         }
     });
@@ -0,0 +1,221 @@
+extern crate chip8_core;
+extern crate image;
+
+pub mod options;
+
+use chip8_core::display::{COLOR_SET, SCREEN_WIDTH};
+use chip8_core::{FrameReport, Movie, MoviePlayer, MovieRecorder, Register, StopReason, CPU};
+use options::{Options, USAGE};
+use std::error::Error;
+use std::fmt;
+use std::process::ExitCode;
+
+// Why the run ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Outcome {
+    FrameLimit,
+    Exited,
+    Idle,
+    MovieEnd,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FrameLimit => write!(f, "frame limit"),
+            Self::Exited => write!(f, "exited"),
+            Self::Idle => write!(f, "idle"),
+            Self::MovieEnd => write!(f, "end of movie"),
+        }
+    }
+}
+
+// The screen at its current resolution, one plane bitmask per pixel, row by
+// row.
+fn logical_screen(emu: &CPU) -> (usize, usize, Vec<u8>) {
+    let (width, height) = emu.screen.dimensions();
+    let scale = SCREEN_WIDTH / width;
+    let pixels = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            emu.screen
+                .read_pixel_unchecked((x * scale) as u8, (y * scale) as u8) as u8
+        })
+        .collect();
+    (width, height, pixels)
+}
+
+// 64-bit FNV-1a of the resolution and pixels, which stays the same between
+// builds and Rust releases so it can be checked into a regression suite.
+fn screen_hash(emu: &CPU) -> u64 {
+    let (width, height, pixels) = logical_screen(emu);
+    [width as u8, height as u8]
+        .iter()
+        .chain(&pixels)
+        .fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+fn write_screenshot(emu: &CPU, path: &std::path::Path, scale: u32) -> image::ImageResult<()> {
+    let (width, _, pixels) = logical_screen(emu);
+    let height = pixels.len() / width;
+    let buf = image::ImageBuffer::from_fn(scale * width as u32, scale * height as u32, |x, y| {
+        let pos = (x / scale) as usize + (y / scale) as usize * width;
+        let color = COLOR_SET[pixels[pos] as usize];
+        image::Rgb([(color >> 16) as u8, (color >> 8) as u8, color as u8])
+    });
+    buf.save(path)
+}
+
+fn register_dump(emu: &CPU) -> String {
+    let mut out = format!(
+        "PC=0x{:04X} I=0x{:04X} DT=0x{:02X} ST=0x{:02X} SP={}",
+        emu.pc,
+        emu.index,
+        emu.delay_timer,
+        emu.sound_timer,
+        emu.call_stack.len()
+    );
+    for idx in 0..16u8 {
+        let reg = Register::try_from(idx).unwrap();
+        let sep = if idx % 8 == 0 { '\n' } else { ' ' };
+        out += &format!("{}{}=0x{:02X}", sep, reg, emu.registers[reg]);
+    }
+    out
+}
+
+// Spinning on a jump to itself, or waiting for a key that no script will
+// press or release. FX0A has already moved past itself while it waits, so
+// the instruction after it doesn't count as a jump until the wait is over.
+// 1NNN only holds the low 12 bits of an address, which is all it's compared
+// on in XO-CHIP's high memory.
+fn is_idle(emu: &CPU, report: &FrameReport, keys_pending: bool) -> bool {
+    if emu.key_wait().is_some() || report.stop_reason == StopReason::WaitingForKey {
+        return !keys_pending;
+    }
+    emu.read_memory_word(emu.pc).map_or(false, |word| {
+        word & 0xF000 == 0x1000 && word & 0x0FFF == emu.pc & 0x0FFF
+    })
+}
+
+fn read_file(path: &std::path::Path) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|e| format!("couldn't read {}: {}", path.display(), e))
+}
+
+fn write_file(path: &std::path::Path, contents: &[u8]) -> Result<(), String> {
+    std::fs::write(path, contents).map_err(|e| format!("couldn't write {}: {}", path.display(), e))
+}
+
+fn run(options: &Options) -> Result<(), Box<dyn Error>> {
+    let mut emu = CPU::new(options.mode);
+    let mut player = None;
+    if let Some(path) = &options.movie {
+        let movie = Movie::from_bytes(&read_file(path)?)
+            .map_err(|e| format!("couldn't load {}: {}", path.display(), e))?;
+        player = Some(MoviePlayer::new(movie, &mut emu));
+    } else if let Some(path) = &options.rom {
+        if let Some(speed) = options.speed {
+            emu.clock_speed = speed;
+        }
+        emu.load_rom(&read_file(path)?)?;
+        emu.reseed(options.seed);
+    }
+    let mut recorder = match options.record {
+        Some(_) => Some(MovieRecorder::new(&mut emu)?),
+        None => None,
+    };
+    let frames = match (options.frames, &player) {
+        (Some(frames), _) => frames,
+        // movies play to the end
+        (None, Some(_)) => u32::MAX,
+        (None, None) => 600,
+    };
+
+    let mut frame = 0;
+    let mut outcome = Outcome::FrameLimit;
+    while frame < frames {
+        for press in &options.keys {
+            if press.release_frame() == frame {
+                match &mut recorder {
+                    Some(recorder) => recorder.release_key(&mut emu, press.key),
+                    None => emu.release_key(press.key),
+                }
+            }
+            if press.frame == frame {
+                match &mut recorder {
+                    Some(recorder) => recorder.press_key(&mut emu, press.key),
+                    None => emu.press_key(press.key),
+                }
+            }
+        }
+        let report = match (&mut player, &mut recorder) {
+            (Some(player), _) => match player.run_frame(&mut emu)? {
+                Some(report) => report,
+                None => {
+                    outcome = Outcome::MovieEnd;
+                    break;
+                }
+            },
+            (None, Some(recorder)) => recorder.run_frame(&mut emu)?,
+            (None, None) => emu.run_frame()?,
+        };
+        frame += 1;
+        if report.stop_reason == StopReason::Exited {
+            outcome = Outcome::Exited;
+            break;
+        }
+        // a key is pending until its release has happened too
+        let keys_pending = options
+            .keys
+            .iter()
+            .any(|press| press.release_frame() >= frame)
+            || player
+                .as_ref()
+                .map_or(false, |player| !player.is_finished());
+        if options.until_idle && is_idle(&emu, &report, keys_pending) {
+            outcome = Outcome::Idle;
+            break;
+        }
+    }
+
+    println!("frames: {} ({})", frame, outcome);
+    if options.hash {
+        println!("hash: {:016x}", screen_hash(&emu));
+    }
+    if options.regs {
+        println!("{}", register_dump(&emu));
+    }
+    if let Some(path) = &options.screenshot {
+        write_screenshot(&emu, path, options.scale)
+            .map_err(|e| format!("couldn't write {}: {}", path.display(), e))?;
+    }
+    if let Some(path) = &options.save_state {
+        write_file(path, &emu.save_state()?.to_bytes())?;
+    }
+    if let (Some(path), Some(recorder)) = (&options.record, recorder) {
+        write_file(path, &recorder.finish().to_bytes())?;
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match run(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use chip8_core::{Chip8Mode, ClockSpeed};
+
+use std::path::PathBuf;
+use std::str::FromStr;
+
+pub const USAGE: &str = "\
+usage: headless-chip8 [options] <rom>
+       headless-chip8 [options] --movie <file>
+
+Runs a CHIP-8 ROM without a window or audio, then reports on the result.
+
+options:
+  -m, --mode <mode>         cosmac, super-chip or xo-chip (default: cosmac)
+  -s, --speed <rate>        clock speed, e.g. `15ipf` or `700hz`
+  -f, --frames <n>          run for at most <n> frames (default: 600, or the
+                            length of the movie)
+      --until-idle          stop early once the program jumps to itself, or
+                            waits for a key when no more are scripted
+  -k, --key <frame:key[:n]> hold hex <key> for <n> frames (default: 1),
+                            starting at <frame>; may be repeated
+      --seed <n>            seed for random numbers (default: 0)
+      --screenshot <file>   write the screen to a PNG file
+      --scale <n>           scale the screenshot up <n> times (default: 1)
+      --hash                print a hash of the screen
+      --regs                print the registers
+      --save-state <file>   write a save state
+      --record <file>       record the run as a movie
+      --movie <file>        play back a movie, stopping at the first desync;
+                            the ROM, mode, speed and seed come from the movie
+  -h, --help                print this message";
+
+// A key held down for `frames` frames starting at frame `frame`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyPress {
+    pub frame: u32,
+    pub key: u8,
+    pub frames: u32,
+}
+
+impl KeyPress {
+    // The frame the key is released on, which never comes for a press that
+    // would last past the last frame there can be.
+    #[inline]
+    pub fn release_frame(&self) -> u32 {
+        self.frame.saturating_add(self.frames)
+    }
+}
+
+impl FromStr for KeyPress {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        let err = || {
+            format!(
+                "invalid key press `{}`; expected <frame>:<key>[:<frames>]",
+                s
+            )
+        };
+        let mut parts = s.split(':');
+        let frame = parts.next().and_then(|frame| frame.parse().ok());
+        let key = parts
+            .next()
+            .and_then(|key| u8::from_str_radix(key, 16).ok())
+            .filter(|&key| key < 16);
+        let frames = match parts.next() {
+            Some(frames) => frames.parse().ok().filter(|&frames| frames > 0),
+            None => Some(1),
+        };
+        match (frame, key, frames, parts.next()) {
+            (Some(frame), Some(key), Some(frames), None) => Ok(KeyPress { frame, key, frames }),
+            _ => Err(err()),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    // None when playing a movie
+    pub rom: Option<PathBuf>,
+    pub mode: Chip8Mode,
+    pub speed: Option<ClockSpeed>,
+    pub frames: Option<u32>,
+    pub until_idle: bool,
+    pub keys: Vec<KeyPress>,
+    pub seed: u64,
+    pub screenshot: Option<PathBuf>,
+    pub scale: u32,
+    pub hash: bool,
+    pub regs: bool,
+    pub save_state: Option<PathBuf>,
+    pub record: Option<PathBuf>,
+    pub movie: Option<PathBuf>,
+}
+
+impl Options {
+    // None if help was asked for.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Option<Options>, String> {
+        let mut options = Options {
+            rom: None,
+            mode: Chip8Mode::Cosmac,
+            speed: None,
+            frames: None,
+            until_idle: false,
+            keys: Vec::new(),
+            seed: 0,
+            screenshot: None,
+            scale: 1,
+            hash: false,
+            regs: false,
+            save_state: None,
+            record: None,
+            movie: None,
+        };
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for `{}`", arg))
+            };
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "-m" | "--mode" => {
+                    let mode = value()?;
+                    options.mode = mode
+                        .parse()
+                        .map_err(|e| format!("invalid mode `{}`: {}", mode, e))?;
+                }
+                "-s" | "--speed" => {
+                    let speed = value()?;
+                    options.speed = Some(
+                        speed
+                            .parse()
+                            .map_err(|e| format!("invalid speed `{}`: {}", speed, e))?,
+                    );
+                }
+                "-f" | "--frames" => options.frames = Some(parse_number(&arg, &value()?)?),
+                "--until-idle" => options.until_idle = true,
+                "-k" | "--key" => options.keys.push(value()?.parse()?),
+                "--seed" => options.seed = parse_number(&arg, &value()?)?,
+                "--screenshot" => options.screenshot = Some(value()?.into()),
+                "--scale" => {
+                    options.scale = parse_number(&arg, &value()?)?;
+                    if options.scale == 0 {
+                        return Err("`--scale` must be at least 1".into());
+                    }
+                }
+                "--hash" => options.hash = true,
+                "--regs" => options.regs = true,
+                "--save-state" => options.save_state = Some(value()?.into()),
+                "--record" => options.record = Some(value()?.into()),
+                "--movie" => options.movie = Some(value()?.into()),
+                _ if arg.starts_with('-') && arg.len() > 1 => {
+                    return Err(format!("unknown option `{}`", arg))
+                }
+                _ if options.rom.is_none() => options.rom = Some(arg.into()),
+                _ => return Err(format!("unexpected argument `{}`", arg)),
+            }
+        }
+        if options.movie.is_some() {
+            // input would desync the movie, and the ROM comes with it
+            if !options.keys.is_empty() || options.record.is_some() {
+                return Err("`--movie` can't be combined with `--key` or `--record`".into());
+            }
+            if options.rom.is_some() {
+                return Err("`--movie` can't be combined with a ROM".into());
+            }
+        } else if options.rom.is_none() {
+            return Err("no ROM given".into());
+        }
+        options.keys.sort_by_key(|press| press.frame);
+        Ok(Some(options))
+    }
+}
+
+fn parse_number<T: FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid number `{}` for `{}`", value, option))
+}
@@ -0,0 +1,145 @@
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+const IBM_ROM: &str = "../chip8-core/tests/timendus-tests/2-ibm-logo.ch8";
+const KEYPAD_ROM: &str = "../chip8-core/tests/timendus-tests/6-keypad.ch8";
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_headless-chip8"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("headless-chip8-{}-{}", std::process::id(), name))
+}
+
+#[test]
+fn test_until_idle() {
+    let out = stdout(&run(&["--until-idle", "--hash", "--regs", IBM_ROM]));
+    let mut lines = out.lines();
+    assert!(lines.next().unwrap().ends_with("(idle)"));
+    assert_eq!(lines.next(), Some("hash: dc1160cf91169306"));
+    assert!(lines.next().unwrap().starts_with("PC=0x0228 I=0x0275"));
+
+    // the logo looks the same in every mode
+    for mode in ["super-chip", "xo-chip"] {
+        let out = stdout(&run(&["-m", mode, "--until-idle", "--hash", IBM_ROM]));
+        assert!(out.contains("hash: dc1160cf91169306"), "{}", out);
+    }
+}
+
+#[test]
+fn test_until_idle_waits_for_keys() {
+    // 0x200: V0 = key; 0x202: V1 = 1; 0x204: jump 0x204
+    let waits = temp_path("waits.ch8");
+    std::fs::write(&waits, [0xF0, 0x0A, 0x61, 0x01, 0x12, 0x04]).unwrap();
+    let out = stdout(&run(&[
+        "--until-idle",
+        "--regs",
+        "-k",
+        "5:1:3",
+        waits.to_str().unwrap(),
+    ]));
+    assert!(out.contains("(idle)"), "{}", out);
+    assert!(out.contains("V0=0x01 V1=0x01"), "{}", out);
+
+    // 0x200: V0 = key; 0x202: jump 0x202, which is already the PC during
+    // the wait
+    let spins = temp_path("spins.ch8");
+    std::fs::write(&spins, [0xF0, 0x0A, 0x12, 0x02]).unwrap();
+    let out = stdout(&run(&[
+        "--until-idle",
+        "--regs",
+        "-k",
+        "5:1:3",
+        spins.to_str().unwrap(),
+    ]));
+    assert!(out.contains("V0=0x01"), "{}", out);
+
+    // a press that would be released past the last frame is never released
+    let out = stdout(&run(&[
+        "--frames",
+        "3",
+        "-k",
+        "4294967295:1:2",
+        waits.to_str().unwrap(),
+    ]));
+    assert_eq!(out.trim(), "frames: 3 (frame limit)");
+    std::fs::remove_file(waits).unwrap();
+    std::fs::remove_file(spins).unwrap();
+}
+
+#[test]
+fn test_outputs() {
+    let screenshot = temp_path("screen.png");
+    let state = temp_path("state");
+    let out = stdout(&run(&[
+        "--frames",
+        "10",
+        "--screenshot",
+        screenshot.to_str().unwrap(),
+        "--scale",
+        "2",
+        "--save-state",
+        state.to_str().unwrap(),
+        IBM_ROM,
+    ]));
+    assert_eq!(out.trim(), "frames: 10 (frame limit)");
+
+    let png = std::fs::read(&screenshot).unwrap();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    // width and height in the IHDR chunk
+    assert_eq!(&png[16..24], &[0, 0, 0, 128, 0, 0, 0, 64]);
+    assert_eq!(&std::fs::read(&state).unwrap()[..4], b"CH8S");
+    std::fs::remove_file(screenshot).unwrap();
+    std::fs::remove_file(state).unwrap();
+}
+
+#[test]
+fn test_movie() {
+    let movie = temp_path("movie");
+    let recorded = stdout(&run(&[
+        "--frames",
+        "200",
+        "-k",
+        "60:1:10",
+        "-k",
+        "100:5:50",
+        "--hash",
+        "--record",
+        movie.to_str().unwrap(),
+        KEYPAD_ROM,
+    ]));
+    let played = stdout(&run(&["--hash", "--movie", movie.to_str().unwrap()]));
+    assert_eq!(
+        played.replace("frame limit", "end of movie"),
+        recorded.replace("frame limit", "end of movie")
+    );
+    assert!(
+        played.starts_with("frames: 200 (end of movie)"),
+        "{}",
+        played
+    );
+    std::fs::remove_file(movie).unwrap();
+}
+
+#[test]
+fn test_bad_arguments() {
+    for args in [
+        &["--frames", "x", IBM_ROM][..],
+        &["-k", "1:G", IBM_ROM],
+        &["--movie", "movie", IBM_ROM],
+        &[],
+    ] {
+        assert_eq!(run(args).status.code(), Some(2), "{:?}", args);
+    }
+    assert_eq!(run(&["no-such-rom.ch8"]).status.code(), Some(1));
+}